@@ -2,24 +2,88 @@ use std::process::Command;
 
 fn main() {
     let branch_output = Command::new("git")
-        .args(&["rev-parse", "--abbrev-ref", "HEAD"])
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
         .output()
         .unwrap();
     let git_branch = String::from_utf8(branch_output.stdout).unwrap();
     println!("cargo:rustc-env=GIT_BRANCH={}", git_branch);
 
     let output = Command::new("git")
-        .args(&["rev-parse", "HEAD"])
+        .args(["rev-parse", "HEAD"])
         .output()
         .unwrap();
     let git_hash = String::from_utf8(output.stdout).unwrap();
     println!("cargo:rustc-env=GIT_HASH={}", git_hash);
 
-    gen_proto("./src/grpc/proto/hello.proto", "./src/grpc");
-    gen_proto("./src/rocksdb/proto/graph.proto", "./src/rocksdb");
+    gen_proto(
+        tonic_build::configure(),
+        "./src/grpc/proto/hello.proto",
+        "./src/grpc",
+    );
+
+    // Serde for rocksdb export / import, with the bytes in hex
+    let mut graph = tonic_build::configure()
+        .type_attribute(
+            ".rocksdb.graph.v1",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute(".rocksdb.graph.v1", "#[serde(default)]");
+    for field in [
+        "Node.ts_nano",
+        "Edge.ts_nano",
+        "Attribute.content",
+        "Attribute.content_hash",
+        "Attribute.ts_nano",
+        "Symbol.name_hash",
+        "Symbol.ts_nano",
+    ] {
+        graph = graph.field_attribute(
+            format!(".rocksdb.graph.v1.{}", field),
+            "#[serde(with = \"crate::rocksdb::export::hex\")]",
+        );
+    }
+
+    // Keys, indexes and operations, see playrs-derive; node.rs and edge.rs
+    // re-export the index structs as ById, ByType, ...  The type indexes
+    // have an entry per node or edge, keyed by type code then id.
+    for (message, attributes) in [
+        (
+            "Node",
+            [
+                "#[index(name = \"index.node.id\", value)]",
+                "#[index(name = \"index.node.type\", fields = (type_code, id), unique)]",
+                "#[index(name = \"index.node.name\", fields = (name), unique)]",
+                "#[index(name = \"index.node.name_hash\", fields = (name_hash()))]",
+            ]
+            .as_slice(),
+        ),
+        (
+            "Edge",
+            [
+                "#[index(name = \"index.edge.id\", value)]",
+                "#[index(name = \"index.edge.type\", fields = (type_code, id), unique)]",
+                "#[index(name = \"index.edge.name\", fields = (name), unique)]",
+                "#[index(name = \"index.edge.head-tail\", fields = (head, tail), unique)]",
+                "#[index(name = \"index.edge.tail-head\", fields = (tail, head), unique)]",
+            ]
+            .as_slice(),
+        ),
+    ] {
+        let path = format!(".rocksdb.graph.v1.{}", message);
+        graph = graph
+            .type_attribute(&path, "#[derive(playrs_derive::GraphEntity)]")
+            .type_attribute(
+                &path,
+                "#[graph(timestamp = ts_nano, type_name = type_name, type_code = type_code)]",
+            );
+        for attribute in attributes {
+            graph = graph.type_attribute(&path, attribute);
+        }
+    }
+    gen_proto(graph, "./src/rocksdb/proto/graph.proto", "./src/rocksdb");
 }
-fn gen_proto(proto_file: &str, out_dir: &str) {
-    tonic_build::configure()
+fn gen_proto(builder: tonic_build::Builder, proto_file: &str, out_dir: &str) {
+    builder
         .build_server(true)
         .out_dir(out_dir)
         .compile_protos(&[proto_file], &["."])
@@ -0,0 +1,343 @@
+//! `#[derive(GraphEntity)]` generates what playrs needs to store an entity:
+//! `HasKey<u64>`, `Entity`, a struct and an `Index` impl per index,
+//! `Indexes`, the `IndexHelper` that defaults the id, timestamp and type code
+//! in `before_put`, and `OperationsBuilder`.  Node and Edge get theirs from
+//! attributes added in playrs's build.rs.
+//!
+//! The struct must be a prost message with a `u64` key field.  The generated
+//! code refers to `crate::rocksdb`, so it is only for use inside playrs.
+//!
+//! ```ignore
+//! #[derive(Clone, PartialEq, prost::Message, GraphEntity)]
+//! #[graph(timestamp = ts_nano, type_name = type_name, type_code = type_code)]
+//! #[index(name = "index.task.id", value)]
+//! #[index(name = "index.task.name", fields = (name), unique)]
+//! #[index(name = "index.task.owner", fields = (owner, type_code))]
+//! pub struct Task { ... }
+//! ```
+//!
+//! `#[graph(...)]`, all optional:
+//! - `name = "Task"`: Entity::TYPE, the struct name by default
+//! - `key = id`: the u64 key field, `id` by default; 0 means not yet assigned
+//! - `timestamp = field`: a `Vec<u8>` set to the put time if empty
+//! - `type_name = field, type_code = field`: type_code is looked up from the
+//!   type name on put
+//!
+//! `#[index(...)]`, one per column family:
+//! - `name = "..."`: the column family
+//! - `value`: the index storing (id, entity); exactly one is required
+//! - `fields = (a, b, c())`: the key, as the fields (or methods returning a
+//!   field type) concatenated; the value is the id
+//! - `unique`: one entry per key, the last put wins; otherwise entries are
+//!   appended under the key
+//!
+//! Each index becomes a unit struct named after the entity and the last part
+//! of the column family, e.g. `TaskByOwner` for "index.task.owner".
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parenthesized, parse_macro_input, DeriveInput, Ident, LitStr, Token};
+
+#[proc_macro_derive(GraphEntity, attributes(graph, index))]
+pub fn derive_graph_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// A field or a method with no arguments, e.g. name or name_hash().
+struct FieldRef {
+    ident: Ident,
+    call: bool,
+}
+
+impl Parse for FieldRef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        let call = input.peek(syn::token::Paren);
+        if call {
+            let args;
+            parenthesized!(args in input);
+            if !args.is_empty() {
+                return Err(args.error("methods in index fields take no arguments"));
+            }
+        }
+        Ok(FieldRef { ident, call })
+    }
+}
+
+struct IndexAttr {
+    name: LitStr,
+    value: bool,
+    unique: bool,
+    fields: Vec<FieldRef>,
+}
+
+#[derive(Default)]
+struct GraphAttr {
+    name: Option<LitStr>,
+    key: Option<Ident>,
+    timestamp: Option<Ident>,
+    type_name: Option<Ident>,
+    type_code: Option<Ident>,
+}
+
+fn parse_graph(input: &DeriveInput) -> syn::Result<GraphAttr> {
+    let mut graph = GraphAttr::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("graph")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                graph.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("key") {
+                graph.key = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("timestamp") {
+                graph.timestamp = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("type_name") {
+                graph.type_name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("type_code") {
+                graph.type_code = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected name, key, timestamp, type_name or type_code"));
+            }
+            Ok(())
+        })?;
+    }
+    if graph.type_name.is_some() != graph.type_code.is_some() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "type_name and type_code go together",
+        ));
+    }
+    Ok(graph)
+}
+
+fn parse_indexes(input: &DeriveInput) -> syn::Result<Vec<IndexAttr>> {
+    let mut indexes = vec![];
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("index")) {
+        let mut name = None;
+        let mut value = false;
+        let mut unique = false;
+        let mut fields = vec![];
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("value") {
+                value = true;
+            } else if meta.path.is_ident("unique") {
+                unique = true;
+            } else if meta.path.is_ident("fields") {
+                let value = meta.value()?;
+                let content;
+                parenthesized!(content in value);
+                fields = Punctuated::<FieldRef, Token![,]>::parse_terminated(&content)?
+                    .into_iter()
+                    .collect();
+            } else {
+                return Err(meta.error("expected name, value, unique or fields"));
+            }
+            Ok(())
+        })?;
+        let name = match name {
+            Some(n) => n,
+            None => return Err(syn::Error::new_spanned(attr, "index needs a name")),
+        };
+        if value && !fields.is_empty() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "the value index is keyed by id and takes no fields",
+            ));
+        }
+        if !value && fields.is_empty() {
+            return Err(syn::Error::new_spanned(attr, "index needs fields"));
+        }
+        indexes.push(IndexAttr {
+            name,
+            value,
+            unique,
+            fields,
+        });
+    }
+    match indexes.iter().filter(|i| i.value).count() {
+        1 => Ok(indexes),
+        _ => Err(syn::Error::new(
+            Span::call_site(),
+            "exactly one #[index(name = \"...\", value)] is required",
+        )),
+    }
+}
+
+// TaskByOwner for "index.task.owner"; TaskByHeadTail for "index.edge.head-tail".
+fn index_ident(entity: &Ident, cf_name: &str) -> Ident {
+    let last = cf_name.rsplit('.').next().unwrap_or(cf_name);
+    let camel: String = last
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut chars = s.chars();
+            match chars.next() {
+                Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    format_ident!("{}By{}", entity, camel)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let graph = parse_graph(input)?;
+    let indexes = parse_indexes(input)?;
+
+    let entity = &input.ident;
+    let vis = &input.vis;
+    let type_name = match &graph.name {
+        Some(n) => n.clone(),
+        None => LitStr::new(&entity.to_string(), entity.span()),
+    };
+    let key = graph.key.clone().unwrap_or_else(|| format_ident!("id"));
+    let helper = format_ident!("{}IndexHelper", entity);
+
+    let db = quote!(crate::rocksdb::db);
+    let index = quote!(crate::rocksdb::index);
+    let store = quote!(crate::rocksdb::store);
+
+    let mut index_items = vec![];
+    let mut index_idents = vec![];
+    let mut value_ident = None;
+    for i in indexes.iter() {
+        let ident = index_ident(entity, &i.name.value());
+        let cf_name = &i.name;
+        let key_value = if i.value {
+            value_ident = Some(ident.clone());
+            quote! {
+                (
+                    <#entity as #db::HasKey<u64>>::id(e).as_bytes(),
+                    #db::Entity::as_bytes(e),
+                )
+            }
+        } else {
+            let parts = i.fields.iter().map(|f| {
+                let ident = &f.ident;
+                if f.call {
+                    quote!(key.extend(#index::KeyBytes::key_bytes(&e.#ident()));)
+                } else {
+                    quote!(key.extend(#index::KeyBytes::key_bytes(&e.#ident));)
+                }
+            });
+            quote! {{
+                let mut key = Vec::<u8>::new();
+                #(#parts)*
+                (key, e.#key.to_le_bytes().to_vec())
+            }}
+        };
+        let append = !i.value && !i.unique;
+        index_items.push(quote! {
+            #[derive(Debug, Clone, PartialEq)]
+            #vis struct #ident;
+
+            impl #index::Index<#entity> for #ident {
+                fn cf_name(&self) -> &'static str {
+                    #cf_name
+                }
+                fn key_value(&self, e: &#entity) -> (Vec<u8>, Vec<u8>) {
+                    #key_value
+                }
+                fn append_if_same_key(&self) -> bool {
+                    #append
+                }
+            }
+        });
+        index_idents.push(ident);
+    }
+    let value_ident = value_ident.unwrap();
+
+    let set_timestamp = graph.timestamp.as_ref().map(|ts| {
+        quote! {
+            if e.#ts.is_empty() {
+                e.#ts = ::time::OffsetDateTime::now_utc()
+                    .unix_timestamp_nanos()
+                    .to_le_bytes()
+                    .to_vec();
+            }
+        }
+    });
+    let set_type_code = match (&graph.type_name, &graph.type_code) {
+        (Some(name), Some(code)) => Some(quote! {
+            e.#code = #db::stage_type_code(db, txn, &e.#name)?;
+        }),
+        _ => None,
+    };
+
+    Ok(quote! {
+        impl #db::HasKey<u64> for #entity {
+            fn key(&self) -> Option<u64> {
+                if self.#key > 0 {
+                    Some(self.#key)
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl #db::Entity for #entity {
+            const TYPE: &'static str = #type_name;
+            fn as_bytes(&self) -> Vec<u8> {
+                ::prost::Message::encode_to_vec(self)
+            }
+            fn from_bytes(
+                _key: &[u8],
+                bytes: &[u8],
+            ) -> Result<#entity, Box<dyn std::error::Error>> {
+                Ok(<#entity as ::prost::Message>::decode(bytes)?)
+            }
+        }
+
+        #(#index_items)*
+
+        impl #index::Indexes<#entity> for #entity {
+            fn indexes() -> Vec<Box<dyn #index::Index<#entity>>> {
+                vec![#(Box::new(#index_idents)),*]
+            }
+            fn value_index() -> Box<dyn #index::Index<#entity>> {
+                Box::new(#value_ident)
+            }
+        }
+
+        struct #helper;
+
+        impl #db::IndexHelper<u64, #entity> for #helper {
+            fn value_index(&self) -> &dyn #index::Index<#entity> {
+                &#value_ident
+            }
+            fn indexes(&self) -> Vec<Box<dyn #index::Index<#entity>>> {
+                <#entity as #index::Indexes<#entity>>::indexes()
+            }
+            fn before_put(
+                &self,
+                db: &dyn #store::Store,
+                txn: &mut #store::Batch,
+                e: &mut #entity,
+            ) -> Result<(), Box<dyn std::error::Error>> {
+                if e.#key == 0 {
+                    e.#key = #db::next_id(db, txn)?;
+                }
+                #set_timestamp
+                #set_type_code
+                Ok(())
+            }
+            fn from_bytes(&self, buff: &[u8]) -> Result<#entity, Box<dyn std::error::Error>> {
+                <#entity as #db::Entity>::from_bytes(&[], buff)
+            }
+        }
+
+        impl #db::OperationsBuilder<#entity> for #entity {
+            fn operations(db: &dyn #store::Store) -> Box<dyn #db::Operations<#entity> + '_> {
+                #db::entity_operations::<u64, #entity>(db, Box::new(#helper))
+            }
+        }
+    })
+}
@@ -97,28 +97,26 @@ pub fn go(cmd: &Command) {
         Verb::Read(args) => {
             trace!("Read: {:?}", args);
 
-            match &args.name {
-                Some(name) => match db::read_person(&conn, "name=?", &name) {
+            if let Some(name) = &args.name {
+                match db::read_person(&conn, "name=?", &name) {
                     Ok(cur) => {
                         for p in cur {
                             info!("{:?}", p);
                         }
                     }
                     Err(e) => error!("Error: {:?}", e),
-                },
-                None => {}
+                }
             }
 
-            match args.id {
-                Some(id) => match db::read_person(&conn, "id=?", &id) {
+            if let Some(id) = args.id {
+                match db::read_person(&conn, "id=?", &id) {
                     Ok(cur) => {
                         for p in cur {
                             info!("{:?}", p);
                         }
                     }
                     Err(e) => error!("Error: {:?}", e),
-                },
-                None => {}
+                }
             }
         }
         Verb::Update(args) => {
@@ -134,10 +132,7 @@ pub fn go(cmd: &Command) {
                 None => "".to_string(),
             };
 
-            person.data = match &args.data {
-                Some(d) => Some(Vec::from(d.as_bytes())),
-                None => None,
-            };
+            person.data = args.data.as_ref().map(|d| Vec::from(d.as_bytes()));
 
             match db::update_person(&conn, &person) {
                 Ok(p) => info!("Updated person {:?}", p),
@@ -146,20 +141,18 @@ pub fn go(cmd: &Command) {
         }
         Verb::Delete(args) => {
             trace!("Delete: {:?}", args);
-            match &args.name {
-                Some(name) => match db::delete_person(&conn, "name=?", &name) {
+            if let Some(name) = &args.name {
+                match db::delete_person(&conn, "name=?", &name) {
                     Ok(ret) => info!("deleted {:?}", ret),
                     Err(e) => error!("Error: {:?}", e),
-                },
-                None => {}
+                }
             }
 
-            match args.id {
-                Some(id) => match db::delete_person(&conn, "id=?", &id) {
+            if let Some(id) = args.id {
+                match db::delete_person(&conn, "id=?", &id) {
                     Ok(ret) => info!("deleted {:?}", ret),
                     Err(e) => error!("Error: {:?}", e),
-                },
-                None => {}
+                }
             }
         }
     }
@@ -13,7 +13,7 @@ pub fn read_person<KT: ToSql>(
     conn: &Connection,
     whereq: &str,
     val: &KT,
-) -> Result<Vec<Box<Person>>, Box<dyn std::error::Error>> {
+) -> Result<Vec<Person>, Box<dyn std::error::Error>> {
     let sql: String = "SELECT id, name, data FROM person WHERE ".to_string() + whereq;
     let mut stmt = conn.prepare(&sql)?;
     let person_iter = stmt.query_map([val], |row| {
@@ -24,9 +24,9 @@ pub fn read_person<KT: ToSql>(
         })
     })?;
 
-    let mut v: Vec<Box<Person>> = Vec::new();
+    let mut v: Vec<Person> = Vec::new();
     for person in person_iter {
-        v.push(Box::new(person.unwrap()));
+        v.push(person.unwrap());
     }
 
     trace!("got vec: {:?}", v);
@@ -52,12 +52,12 @@ pub struct CallArgs {
 
 pub fn start(args: &StartArgs) -> Result<(), Box<dyn std::error::Error>> {
     debug!("Starting server {:?}", args.name);
-    return server::start(&args.port);
+    server::start(&args.port)
 }
 
 pub fn call(args: &CallArgs) -> Result<(), Box<dyn std::error::Error>> {
     debug!("Calling server {:?}", args);
-    return client::call(&args.port, &args.name);
+    client::call(&args.port, &args.name)
 }
 
 pub fn go(cmd: &Command) {
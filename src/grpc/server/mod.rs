@@ -30,7 +30,7 @@ impl HelloService for MyServer {
 }
 
 #[tokio::main]
-pub async fn start(addr: &String) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn start(addr: &str) -> Result<(), Box<dyn std::error::Error>> {
     let addr = addr.parse()?; // 0.0.0.0:5001
 
     info!("Starting MyServer at {:?}", addr);
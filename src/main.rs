@@ -31,6 +31,7 @@ struct Cli {
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// DuckDB examples
     Duckdb(duckdb::command::Command),
@@ -39,7 +40,7 @@ enum Commands {
     Rocksdb(rocksdb::command::Command),
 
     /// gRPC examples
-    GRPC(grpc::command::Command),
+    Grpc(grpc::command::Command),
 
     /// Serde examples
     Serde(serde::command::Command),
@@ -80,7 +81,7 @@ fn main() {
         Commands::Watch(args) => {
             watch::watch(&args);
         }
-        Commands::GRPC(args) => {
+        Commands::Grpc(args) => {
             grpc::command::go(&args);
         }
         Commands::Rocksdb(args) => {
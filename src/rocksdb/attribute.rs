@@ -0,0 +1,452 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use prost::Message; // need the trait to encode protobuf
+
+use crate::rocksdb::db::{self, Entity, HasKey, KeyCodec};
+use crate::rocksdb::error::ErrBadQuery;
+use crate::rocksdb::graph::Attribute;
+use crate::rocksdb::hash;
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::store::{Batch, Store};
+use crate::rocksdb::value::{self, Value};
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+use time::OffsetDateTime;
+
+// An attribute is keyed by its parent, the node or edge, and its name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrKey {
+    pub parent_id: u64,
+    pub name: String,
+}
+
+impl KeyCodec for AttrKey {
+    fn encode_key(&self) -> Vec<u8> {
+        let mut key = self.parent_id.to_le_bytes().to_vec();
+        key.extend(self.name.as_bytes());
+        key
+    }
+    fn decode_key(buff: Vec<u8>) -> AttrKey {
+        AttrKey {
+            parent_id: u64::from_le_bytes(buff[0..8].try_into().unwrap()),
+            name: String::from_utf8_lossy(&buff[8..]).to_string(),
+        }
+    }
+}
+
+impl HasKey<AttrKey> for Attribute {
+    fn key(&self) -> Option<AttrKey> {
+        if self.parent_id > 0 && !self.name.is_empty() {
+            Some(AttrKey {
+                parent_id: self.parent_id,
+                name: self.name.clone(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl Entity for Attribute {
+    const TYPE: &'static str = "Attribute";
+    fn as_bytes(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+    fn from_bytes(_key: &[u8], bytes: &[u8]) -> Result<Attribute, Box<dyn Error>> {
+        Ok(Attribute::decode(Cursor::new(bytes))?)
+    }
+}
+
+impl db::OperationsBuilder<Attribute> for Attribute {
+    fn operations(db: &dyn Store) -> Box<dyn db::Operations<Attribute> + '_> {
+        db::entity_operations::<AttrKey, Attribute>(db, Box::new(IndexHelper {}))
+    }
+}
+
+impl Attribute {
+    pub fn typed(parent_id: u64, name: &str, v: &Value) -> Attribute {
+        Attribute {
+            parent_id,
+            name: name.to_string(),
+            content: v.content(),
+            content_type: v.content_type().to_string(),
+            ..Default::default()
+        }
+    }
+
+    // The typed value, if the content type is one of value's.
+    pub fn value(&self) -> Option<Value> {
+        if !Value::is_typed(&self.content_type) {
+            return None;
+        }
+        match Value::from_content(&self.content_type, &self.content) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("Attribute {}/{:?}: {}", self.parent_id, self.name, e);
+                None
+            }
+        }
+    }
+}
+
+pub struct AttributePrinter(pub usize);
+
+impl db::Visitor<Attribute> for AttributePrinter {
+    fn visit(&mut self, a: Attribute) -> bool {
+        match a.value() {
+            Some(v) => println!("{} {:?} = {}", a.parent_id, a.name, v),
+            None => println!("{:?}", a),
+        }
+        self.0 -= 1;
+        self.0 > 0
+    }
+}
+
+struct IndexHelper {}
+
+impl db::IndexHelper<AttrKey, Attribute> for IndexHelper {
+    fn value_index(&self) -> &dyn Index<Attribute> {
+        &ById
+    }
+    fn indexes(&self) -> Vec<Box<dyn Index<Attribute>>> {
+        Attribute::indexes()
+    }
+    fn before_put(
+        &self,
+        _db: &dyn Store,
+        _txn: &mut Batch,
+        a: &mut Attribute,
+    ) -> Result<(), Box<dyn Error>> {
+        if a.parent_id == 0 || a.name.is_empty() {
+            return Err("An attribute needs a parent id and a name".into());
+        }
+        if Value::is_typed(&a.content_type) {
+            Value::from_content(&a.content_type, &a.content)?;
+        }
+        a.content_hash = hash::compute_sha256_hash(&String::from_utf8_lossy(&a.content))
+            .as_bytes()
+            .to_vec();
+        if a.ts_nano.is_empty() {
+            a.ts_nano = OffsetDateTime::now_utc()
+                .unix_timestamp_nanos()
+                .to_le_bytes()
+                .to_vec();
+        }
+        Ok(())
+    }
+    fn from_bytes(&self, buff: &[u8]) -> Result<Attribute, Box<dyn Error>> {
+        Ok(Message::decode(buff)?)
+    }
+}
+
+impl Indexes<Attribute> for Attribute {
+    fn indexes() -> Vec<Box<dyn Index<Attribute>>> {
+        vec![
+            // By parent id and name
+            Box::new(ById),
+            // By name and typed value
+            Box::new(ByValue),
+        ]
+    }
+    fn value_index() -> Box<dyn Index<Attribute>> {
+        Box::new(ById)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ById;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ByValue;
+
+impl Index<Attribute> for ById {
+    fn cf_name(&self) -> &'static str {
+        "index.attr.id"
+    }
+    fn key_value(&self, a: &Attribute) -> (Vec<u8>, Vec<u8>) {
+        (a.id().as_bytes(), a.as_bytes())
+    }
+}
+
+// Prefix of the entries in index.attr.value for the name and value: the
+// name, then the value's sort key.  The parent id (u64 be) follows.
+pub fn value_key(name: &str, v: &Value) -> Vec<u8> {
+    let mut key = value::sortable_str(name);
+    key.extend(v.sort_key());
+    key
+}
+
+impl Index<Attribute> for ByValue {
+    fn cf_name(&self) -> &'static str {
+        "index.attr.value"
+    }
+    fn key_value(&self, a: &Attribute) -> (Vec<u8>, Vec<u8>) {
+        self.key_values(a).into_iter().next().unwrap_or_default()
+    }
+    // Only typed values are indexed; the key sorts by value.
+    fn key_values(&self, a: &Attribute) -> Vec<(Vec<u8>, Vec<u8>)> {
+        match a.value() {
+            Some(v) => {
+                let mut key = value_key(&a.name, &v);
+                key.extend(a.parent_id.to_be_bytes());
+                vec![(key, a.id().as_bytes())]
+            }
+            None => vec![],
+        }
+    }
+}
+
+// Visits the attributes of the parent, in name order.
+pub fn visit_parent(
+    db: &dyn Store,
+    parent_id: u64,
+    visitor: Box<dyn db::Visitor<Attribute> + '_>,
+) -> Result<(), Box<dyn Error>> {
+    use crate::rocksdb::db::OperationsBuilder;
+
+    let start = Attribute::id_from(AttrKey {
+        parent_id,
+        name: String::new(),
+    });
+    Attribute::operations(db).visit(start, Box::new(ParentVisitor(parent_id, visitor)))
+}
+
+// Stops at the first attribute of another parent.
+struct ParentVisitor<'a>(u64, Box<dyn db::Visitor<Attribute> + 'a>);
+
+impl db::Visitor<Attribute> for ParentVisitor<'_> {
+    fn visit(&mut self, a: Attribute) -> bool {
+        a.parent_id == self.0 && self.1.visit(a)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// A test of an attribute against a value, e.g. size > 100.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub name: String,
+    pub op: Op,
+    pub value: Value,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.name, self.op, self.value)
+    }
+}
+
+impl Condition {
+    // Ids of the parents with a matching attribute, from a range scan of
+    // index.attr.value.  Ints and floats match each other; values of
+    // another type don't match.
+    pub fn parents(&self, db: &dyn Store) -> Result<BTreeSet<u64>, Box<dyn Error>> {
+        db::check_ready(db, ByValue.cf_name())?;
+        let mut prefix = value::sortable_str(&self.name);
+        prefix.extend(self.value.type_prefix());
+        let at = value_key(&self.name, &self.value);
+        let start = match self.op {
+            Op::Eq | Op::Ge | Op::Gt => &at,
+            Op::Ne | Op::Lt | Op::Le => &prefix,
+        };
+
+        let mut ids = BTreeSet::new();
+        db.scan_from(ByValue.cf_name(), start, &mut |k, v| {
+            if !k.starts_with(&prefix) {
+                return false;
+            }
+            let equal = k.starts_with(&at);
+            let below = !equal && k < at.as_slice();
+            let (keep, more) = match self.op {
+                Op::Eq => (equal, equal),
+                Op::Ne => (!equal, true),
+                Op::Lt => (below, below),
+                Op::Le => (below || equal, below || equal),
+                Op::Gt => (!below && !equal, true),
+                Op::Ge => (!below, true),
+            };
+            if keep {
+                ids.insert(AttrKey::decode_key(v.to_vec()).parent_id);
+            }
+            more
+        })?;
+        trace!("{} matched {} parents", self, ids.len());
+        Ok(ids)
+    }
+}
+
+// Ids of the parents matching all the conditions, ascending.
+pub fn select(db: &dyn Store, conditions: &[Condition]) -> Result<Vec<u64>, Box<dyn Error>> {
+    let mut found: Option<BTreeSet<u64>> = None;
+    for c in conditions.iter() {
+        let ids = c.parents(db)?;
+        found = Some(match found {
+            Some(f) => f.intersection(&ids).cloned().collect(),
+            None => ids,
+        });
+        if found.as_ref().is_some_and(|f| f.is_empty()) {
+            break;
+        }
+    }
+    Ok(found.unwrap_or_default().into_iter().collect())
+}
+
+// Parses conditions joined by `and`, e.g. size > 100 and owner = "infra".
+// Literals are ints, floats, true, false, "strings" and t"RFC 3339 times".
+pub fn parse_where(s: &str) -> Result<Vec<Condition>, ErrBadQuery> {
+    let tokens = tokenize(s)?;
+    let mut conditions = vec![];
+    let mut i = 0;
+    loop {
+        let name = match tokens.get(i) {
+            Some(Token::Name(n)) => n.clone(),
+            _ => return Err(ErrBadQuery::new(s, "expected an attribute name")),
+        };
+        let op = match tokens.get(i + 1) {
+            Some(Token::Op(op)) => *op,
+            _ => return Err(ErrBadQuery::new(s, "expected =, !=, <, <=, > or >=")),
+        };
+        let value = match tokens.get(i + 2) {
+            Some(Token::Literal(v)) => v.clone(),
+            Some(Token::Name(n)) if n == "true" || n == "false" => Value::Bool(n == "true"),
+            _ => return Err(ErrBadQuery::new(s, "expected a value")),
+        };
+        conditions.push(Condition { name, op, value });
+        match tokens.get(i + 3) {
+            None => return Ok(conditions),
+            Some(Token::Name(n)) if n.eq_ignore_ascii_case("and") => i += 4,
+            _ => return Err(ErrBadQuery::new(s, "expected and")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Name(String),
+    Op(Op),
+    Literal(Value),
+    Punct(char),
+}
+
+// Splits into names, comparison operators, literals and other punctuation.
+// Names may be quoted in backticks.
+pub fn tokenize(s: &str) -> Result<Vec<Token>, ErrBadQuery> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || (c == 't' && chars.get(i + 1) == Some(&'"')) {
+            let timestamp = c == 't';
+            i += if timestamp { 2 } else { 1 };
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None => return Err(ErrBadQuery::new(s, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some(c) => text.push(*c),
+                            None => return Err(ErrBadQuery::new(s, "unterminated string")),
+                        }
+                    }
+                    Some(c) => text.push(*c),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Literal(if timestamp {
+                Value::parse(value::TIMESTAMP, &text)
+                    .map_err(|e| ErrBadQuery::new(s, &e.to_string()))?
+            } else {
+                Value::String(text)
+            }));
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric()
+                    || (chars[i] == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+                    || ((chars[i] == '-' || chars[i] == '+') && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Literal(match text.parse::<i64>() {
+                Ok(v) => Value::Int(v),
+                Err(_) => Value::parse(value::FLOAT, &text)
+                    .map_err(|_| ErrBadQuery::new(s, &format!("bad number {}", text)))?,
+            }));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            // Names like depends-on may have a - between letters.
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || chars[i] == '_'
+                    || (chars[i] == '-' && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric())))
+            {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if c == '`' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i] != '`' {
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err(ErrBadQuery::new(s, "unterminated `name`"));
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+            i += 1;
+        } else if "=!<>".contains(c) {
+            let next = chars.get(i + 1) == Some(&'=');
+            let op = match (c, next) {
+                ('=', _) => Op::Eq,
+                ('!', true) => Op::Ne,
+                ('<', true) => Op::Le,
+                ('<', false) => Op::Lt,
+                ('>', true) => Op::Ge,
+                ('>', false) => Op::Gt,
+                _ => return Err(ErrBadQuery::new(s, "expected != after !")),
+            };
+            i += if next { 2 } else { 1 };
+            tokens.push(Token::Op(op));
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,246 @@
+use std::error::Error;
+
+use crate::rocksdb::attribute::{self, AttrKey, Condition, Op};
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Attribute, Node};
+use crate::rocksdb::registry;
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::{self, Value};
+use crate::rocksdb::All;
+
+#[test]
+fn test_sort_keys() {
+    let sorted = |values: Vec<Value>| {
+        for w in values.windows(2) {
+            assert!(w[0] < w[1], "{} < {}", w[0], w[1]);
+            assert!(w[0].sort_key() < w[1].sort_key(), "{} < {}", w[0], w[1]);
+        }
+    };
+    sorted(vec![
+        Value::Int(i64::MIN),
+        Value::Int(-100),
+        Value::Int(-1),
+        Value::Int(0),
+        Value::Int(1),
+        Value::Int(100),
+        Value::Int(i64::MAX),
+    ]);
+    sorted(vec![
+        Value::Float(f64::NEG_INFINITY),
+        Value::Float(-2.5),
+        Value::Float(-0.1),
+        Value::Float(0.0),
+        Value::Float(0.1),
+        Value::Float(2.5),
+        Value::Float(f64::INFINITY),
+    ]);
+    sorted(vec![
+        Value::String("".into()),
+        Value::String("a".into()),
+        Value::String("a\0".into()),
+        Value::String("ab".into()),
+        Value::String("b".into()),
+    ]);
+    sorted(vec![
+        Value::Timestamp(-5),
+        Value::Timestamp(0),
+        Value::Timestamp(5),
+    ]);
+    sorted(vec![Value::Bool(false), Value::Bool(true)]);
+
+    // Ints and floats sort together, also beyond what a float holds exactly
+    sorted(vec![
+        Value::Float(f64::NEG_INFINITY),
+        Value::Int(i64::MIN),
+        Value::Float(-2.5),
+        Value::Int(-2),
+        Value::Float(-0.1),
+        Value::Int(0),
+        Value::Float(0.5),
+        Value::Int(1),
+        Value::Float(9007199254740992.0),
+        Value::Int(9007199254740993),
+        Value::Float(9007199254740994.0),
+        Value::Int(i64::MAX),
+        Value::Float(f64::INFINITY),
+    ]);
+    for (i, f) in [(3, 3.0), (0, -0.0), (i64::MIN, -9223372036854775808.0)] {
+        assert_eq!(Value::Int(i), Value::Float(f));
+        assert_eq!(Value::Int(i).sort_key(), Value::Float(f).sort_key());
+    }
+    assert_eq!(None, Value::Int(1).partial_cmp(&Value::Float(f64::NAN)));
+
+    // A string's key is not a prefix of a longer one's
+    let a = Value::String("a".into()).sort_key();
+    assert!(!Value::String("ab".into()).sort_key().starts_with(&a));
+}
+
+#[test]
+fn test_values() -> Result<(), Box<dyn Error>> {
+    for v in [
+        Value::Int(-7),
+        Value::Float(1.5),
+        Value::Bool(true),
+        Value::String("infra".into()),
+        Value::Timestamp(1_700_000_000_000_000_000),
+    ] {
+        assert_eq!(v, Value::from_content(v.content_type(), &v.content())?);
+    }
+    assert_eq!(Value::Int(3), Value::infer("3"));
+    assert_eq!(Value::Float(3.5), Value::infer("3.5"));
+    assert_eq!(Value::Bool(false), Value::infer("false"));
+    assert_eq!(Value::String("x".into()), Value::infer("x"));
+    assert_eq!(
+        Value::Timestamp(86_400_000_000_000),
+        Value::parse(value::TIMESTAMP, "1970-01-02T00:00:00Z")?
+    );
+    assert!(Value::parse(value::INT, "x").is_err());
+    assert!(Value::parse(value::FLOAT, "NaN").is_err());
+    assert!(Value::from_content(value::INT, &[1, 2]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_where() {
+    let c = attribute::parse_where(r#"size > 100 and owner = "infra" AND up == true"#).unwrap();
+    assert_eq!(
+        vec![
+            Condition {
+                name: "size".into(),
+                op: Op::Gt,
+                value: Value::Int(100),
+            },
+            Condition {
+                name: "owner".into(),
+                op: Op::Eq,
+                value: Value::String("infra".into()),
+            },
+            Condition {
+                name: "up".into(),
+                op: Op::Eq,
+                value: Value::Bool(true),
+            },
+        ],
+        c
+    );
+    let c = attribute::parse_where(r#"load <= -1.5e0 and at >= t"1970-01-01T00:00:01Z""#).unwrap();
+    assert_eq!(Value::Float(-1.5), c[0].value);
+    assert_eq!(Op::Ge, c[1].op);
+    assert_eq!(Value::Timestamp(1_000_000_000), c[1].value);
+    assert_eq!(
+        r#"size != "big""#,
+        attribute::parse_where(r#"size != "big""#).unwrap()[0].to_string()
+    );
+
+    assert!(attribute::parse_where("").is_err());
+    assert!(attribute::parse_where("size >").is_err());
+    assert!(attribute::parse_where("size > 1 or x = 2").is_err());
+    assert!(attribute::parse_where(r#"owner = "infra"#).is_err());
+}
+
+fn where_ids(db: &dyn Store, s: &str) -> Vec<u64> {
+    attribute::select(db, &attribute::parse_where(s).unwrap()).unwrap()
+}
+
+fn exercise(db: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut nodes = Node::operations(db);
+    let mut ops = Attribute::operations(db);
+    let mut ids = vec![];
+    for (name, size, owner) in [
+        ("a", 50, "infra"),
+        ("b", 100, "infra"),
+        ("c", 150, "infra"),
+        ("d", 200, "web"),
+        ("e", -10, "infra"),
+    ] {
+        let mut n = Node {
+            name: name.into(),
+            ..Default::default()
+        };
+        nodes.put(&mut n)?;
+        ops.put(&mut Attribute::typed(n.id, "size", &Value::Int(size)))?;
+        ops.put(&mut Attribute::typed(
+            n.id,
+            "owner",
+            &Value::String(owner.into()),
+        ))?;
+        ids.push(n.id);
+    }
+    let (a, b, c, d, e) = (ids[0], ids[1], ids[2], ids[3], ids[4]);
+
+    assert_eq!(vec![c, d], where_ids(db, "size > 100"));
+    assert_eq!(vec![b, c, d], where_ids(db, "size >= 100"));
+    assert_eq!(vec![a, e], where_ids(db, "size < 100"));
+    assert_eq!(vec![a, b, e], where_ids(db, "size <= 100"));
+    assert_eq!(vec![b], where_ids(db, "size = 100"));
+    assert_eq!(vec![a, c, d, e], where_ids(db, "size != 100"));
+    assert_eq!(vec![c], where_ids(db, r#"size > 100 and owner = "infra""#));
+    assert!(where_ids(db, r#"owner = "inf""#).is_empty());
+    // Ints and floats compare; other types don't
+    assert_eq!(vec![a, b, c, d], where_ids(db, "size > 1.5"));
+    assert_eq!(vec![b], where_ids(db, "size = 100.0"));
+    assert!(where_ids(db, r#"size > "1""#).is_empty());
+
+    // Updating moves the entry
+    ops.put(&mut Attribute::typed(a, "size", &Value::Int(500)))?;
+    assert_eq!(vec![a, c, d], where_ids(db, "size > 100"));
+    let found = ops.get(Attribute::id_from(AttrKey {
+        parent_id: a,
+        name: "size".into(),
+    }))?;
+    assert_eq!(
+        Some(Value::Int(500)),
+        found.as_ref().and_then(|f| f.value())
+    );
+
+    ops.delete(&found.unwrap())?;
+    assert_eq!(vec![c, d], where_ids(db, "size > 100"));
+
+    // Opaque attributes are stored but not indexed by value
+    ops.put(&mut Attribute {
+        parent_id: b,
+        name: "blob".into(),
+        content: vec![1, 2, 3],
+        content_type: "application/octet-stream".into(),
+        ..Default::default()
+    })?;
+    assert!(ops
+        .put(&mut Attribute {
+            parent_id: b,
+            name: "size".into(),
+            content: vec![1],
+            content_type: value::INT.into(),
+            ..Default::default()
+        })
+        .is_err());
+
+    let mut names = vec![];
+    attribute::visit_parent(db, b, Box::new(&mut names))?;
+    assert_eq!(vec!["blob", "owner", "size"], names);
+    Ok(())
+}
+
+impl db::Visitor<Attribute> for &mut Vec<String> {
+    fn visit(&mut self, a: Attribute) -> bool {
+        self.push(a.name);
+        true
+    }
+}
+
+#[test]
+fn test_attributes_mem_store() -> Result<(), Box<dyn Error>> {
+    exercise(&MemStore::new(&All))
+}
+
+#[test]
+fn test_attributes() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    exercise(&db)?;
+
+    let report = fsck::fsck_with(&db, &registry::global())?;
+    assert!(report.is_clean(), "{:?}", report);
+    Ok(())
+}
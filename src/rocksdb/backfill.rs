@@ -0,0 +1,185 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Entity};
+use crate::rocksdb::error::ErrMissingIndex;
+use crate::rocksdb::fsck;
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::registry::{self, Registry};
+use crate::rocksdb::store::{Batch, Store};
+
+use std::error::Error;
+
+// Number of entities indexed per write batch.  Progress is saved with each
+// batch, so an interrupted backfill picks up from there.
+const BACKFILL_BATCH_SIZE: usize = 10_000;
+
+// Progress of the backfill of one index, stored in cf.system under
+// backfill.<cf_name> as the last id handed out when the index was added
+// (u64 le), then the count (u64 le) and the last id done.  Entities with
+// ids above the first were put with the index in place and are skipped.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Progress {
+    pub cf_name: String,
+    pub until: Option<u64>,
+    pub count: u64,
+    pub last_key: Option<Vec<u8>>,
+    pub done: bool,
+}
+
+impl Progress {
+    fn from_bytes(cf_name: &str, bytes: &[u8]) -> Progress {
+        let mut p = Progress {
+            cf_name: cf_name.to_string(),
+            ..Default::default()
+        };
+        if bytes.len() >= 8 {
+            p.until = Some(u64::from_le_bytes(bytes[0..8].try_into().unwrap()));
+        }
+        if bytes.len() >= 16 {
+            p.count = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+            p.last_key = Some(bytes[16..].to_vec());
+        }
+        p
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.until.unwrap_or(u64::MAX).to_le_bytes().to_vec();
+        bytes.extend(self.count.to_le_bytes());
+        if let Some(k) = &self.last_key {
+            bytes.extend_from_slice(k);
+        }
+        bytes
+    }
+
+    // Was the entity put after the index was added, and so indexed by put?
+    // Only ids from the sequence can tell; other keys are always indexed.
+    fn is_after(&self, id: &[u8]) -> bool {
+        match (self.until, <[u8; 8]>::try_from(id)) {
+            (Some(until), Ok(le)) => u64::from_le_bytes(le) > until,
+            _ => false,
+        }
+    }
+}
+
+// Lists the indexes waiting for backfill.
+pub fn pending(db: &dyn Store) -> Result<Vec<Progress>, Box<dyn Error>> {
+    let prefix = db::BACKFILL_PREFIX.as_bytes();
+    let mut result = vec![];
+    db.scan_from(db::CF_SYSTEM, prefix, &mut |k, v| {
+        if !k.starts_with(prefix) {
+            return false;
+        }
+        let cf_name = String::from_utf8_lossy(&k[prefix.len()..]).to_string();
+        result.push(Progress::from_bytes(&cf_name, v));
+        true
+    })?;
+    Ok(result)
+}
+
+// Indexes up to n more entities of E, in key order, and saves the progress
+// in the same write.  Clears the backfill key once all are done.  Ids are
+// little endian, so key order isn't id order and the entities put after the
+// index was added are skipped rather than ending the scan.  Appending
+// indexes also skip the entities that already have their entries, e.g. ones
+// updated since, so as not to get them twice.
+pub(crate) fn step<E: Entity + Indexes<E>>(
+    db: &dyn Store,
+    index: &dyn Index<E>,
+    progress: &mut Progress,
+    n: usize,
+) -> Result<(), Box<dyn Error>> {
+    let value_cf = E::value_index().cf_name();
+    if !db.has_cf(value_cf) {
+        return Err(Box::new(ErrMissingIndex::new(value_cf.to_string())));
+    }
+    let from = progress.last_key.clone().unwrap_or_default();
+    let mut txn = Batch::default();
+    let mut done = 0;
+    let mut result = Ok(());
+    db.scan_from(value_cf, &from, &mut |id, bytes| {
+        if progress.last_key.as_deref() == Some(id) {
+            return true;
+        }
+        if done == n {
+            return false;
+        }
+        progress.last_key = Some(id.to_vec());
+        done += 1;
+        if progress.is_after(id) {
+            return true;
+        }
+        result = E::from_bytes(id, bytes).and_then(|e| {
+            if index.append_if_same_key() && fsck::has_entry(db, index, value_cf, &e)? {
+                return Ok(());
+            }
+            progress.count += 1;
+            index.update_entry(db, &mut txn, &e)
+        });
+        result.is_ok()
+    })?;
+    result?;
+    progress.done = done < n;
+
+    let key = db::backfill_key(&progress.cf_name);
+    if progress.done {
+        txn.delete(db::CF_SYSTEM, key);
+    } else {
+        txn.put(db::CF_SYSTEM, key, progress.as_bytes());
+    }
+    db.commit(txn)?;
+    trace!("Backfill progress {:?}", progress);
+    Ok(())
+}
+
+// Runs one step of the backfill of the index, looking it up among the
+// indexes of the registered types.
+pub fn backfill_step(
+    db: &dyn Store,
+    registry: &Registry,
+    progress: &mut Progress,
+    n: usize,
+) -> Result<(), Box<dyn Error>> {
+    let cf_name = progress.cf_name.clone();
+    match registry.find_index(&cf_name) {
+        Some(t) if t.value_cf() != cf_name => return t.backfill_step(db, progress, n),
+        Some(_) => {}
+        None => warn!("No index declared for {:?}; dropping its backfill", cf_name),
+    }
+    // Value indexes are the source of the data; there is nothing to fill.
+    let mut txn = Batch::default();
+    txn.delete(db::CF_SYSTEM, db::backfill_key(&cf_name));
+    db.commit(txn)?;
+    progress.done = true;
+    Ok(())
+}
+
+// Backfills the named index, or all that are pending, to completion.
+pub fn backfill(db: &dyn Store, name: Option<&str>) -> Result<Vec<Progress>, Box<dyn Error>> {
+    backfill_with(db, &registry::global(), name)
+}
+
+pub fn backfill_with(
+    db: &dyn Store,
+    registry: &Registry,
+    name: Option<&str>,
+) -> Result<Vec<Progress>, Box<dyn Error>> {
+    let mut todo = pending(db)?;
+    if let Some(n) = name {
+        todo.retain(|p| p.cf_name == n);
+    }
+    for progress in todo.iter_mut() {
+        info!(
+            "Backfilling {:?} from {:?}",
+            progress.cf_name, progress.count
+        );
+        while !progress.done {
+            backfill_step(db, registry, progress, BACKFILL_BATCH_SIZE)?;
+        }
+        info!(
+            "Backfilled {:?}, count={:?}",
+            progress.cf_name, progress.count
+        );
+    }
+    Ok(todo)
+}
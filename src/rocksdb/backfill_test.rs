@@ -0,0 +1,117 @@
+use std::error::Error;
+
+use crate::rocksdb::backfill::{self, Progress};
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::Node;
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::registry;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use rocksdb::IteratorMode;
+
+// Puts some nodes, then drops the index so that it comes back as a new,
+// empty index the next time the db is opened.
+fn setup_index(db_info: &TestDbInfo, cf_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut db = db::init(db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    for name in ["api", "db", "cache", "queue", "auth"] {
+        node_ops.put(&mut Node {
+            name: name.into(),
+            type_name: "service".into(),
+            ..Default::default()
+        })?;
+    }
+    drop(node_ops);
+    db.drop_cf(cf_name)?;
+    Ok(())
+}
+
+fn setup(db_info: &TestDbInfo) -> Result<(), Box<dyn Error>> {
+    setup_index(db_info, node::ByName.cf_name())
+}
+
+#[test]
+fn test_new_db_no_backfill() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    assert!(backfill::pending(&db)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_backfill() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    setup(&db_info)?;
+
+    let db = db::open_db(&db_info, &All)?;
+    let by_name = node::ByName.cf_name().to_string();
+    assert_eq!(
+        vec![Progress {
+            cf_name: by_name.clone(),
+            until: Some(5),
+            ..Default::default()
+        }],
+        backfill::pending(&db)?
+    );
+
+    // Refused until the backfill is done
+    let node_ops = Node::operations(&db);
+    assert!(node_ops.first(&by_name, b"auth").is_err());
+
+    // Resumes from the saved progress
+    let mut progress = backfill::pending(&db)?.remove(0);
+    backfill::backfill_step(&db, &registry::global(), &mut progress, 2)?;
+    assert!(!progress.done);
+    let saved = backfill::pending(&db)?.remove(0);
+    assert_eq!(
+        (2, Some(2u64.to_le_bytes().to_vec())),
+        (saved.count, saved.last_key)
+    );
+    assert!(node_ops.first(&by_name, b"auth").is_err());
+
+    let done = backfill::backfill(&db, None)?;
+    assert_eq!(5, done[0].count);
+    assert!(done[0].done);
+    assert!(backfill::pending(&db)?.is_empty());
+    assert_eq!(5, node_ops.first(&by_name, b"auth")?.unwrap().id);
+    assert!(fsck::fsck(&db)?.is_clean());
+    Ok(())
+}
+
+#[test]
+fn test_reindex_clears_backfill() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    setup(&db_info)?;
+
+    let db = db::open_db(&db_info, &All)?;
+    fsck::reindex_all(&db, Some(node::ByName.cf_name()))?;
+    assert!(backfill::pending(&db)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_backfill_skips_indexed() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    setup_index(&db_info, node::ByNameHash.cf_name())?;
+
+    // Put with the index in place: a new node, and an update of an old one
+    let db = db::open_db(&db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    node_ops.put(&mut Node {
+        name: "cdn".into(),
+        type_name: "service".into(),
+        ..Default::default()
+    })?;
+    let mut api = node_ops.get(Node::id_from(1u64))?.unwrap();
+    api.type_name = "gateway".into();
+    node_ops.put(&mut api)?;
+
+    let done = backfill::backfill(&db, None)?;
+    assert_eq!(4, done[0].count);
+    let cf = db.cf_handle(node::ByNameHash.cf_name()).unwrap();
+    assert_eq!(6, db.iterator_cf(cf, IteratorMode::Start).count());
+    assert!(fsck::fsck(&db)?.is_clean());
+    Ok(())
+}
@@ -0,0 +1,162 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, DbInfo};
+use crate::rocksdb::error::ErrDbInUse;
+use crate::rocksdb::All;
+
+use rocksdb::backup::{BackupEngine, BackupEngineOptions, RestoreOptions};
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{Env, Options, DB};
+use rustix::fs::{fcntl_lock, FlockOperation};
+use rustix::io::Errno;
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+
+// Same as rocksdb::backup::BackupEngineInfo, which can't be printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub id: u32,
+    pub timestamp: i64,
+    pub size: u64,
+    pub num_files: u32,
+}
+
+// Backups go next to the db unless a directory is given.
+pub fn backup_dir(info: &dyn DbInfo, dir: Option<&str>) -> String {
+    match dir {
+        Some(d) => d.to_string(),
+        None => format!("{}.backups", info.path()),
+    }
+}
+
+fn engine(dir: &str) -> Result<BackupEngine, Box<dyn Error>> {
+    Ok(BackupEngine::open(
+        &BackupEngineOptions::new(dir)?,
+        &Env::new()?,
+    )?)
+}
+
+pub fn list(dir: &str) -> Result<Vec<BackupInfo>, Box<dyn Error>> {
+    Ok(engine(dir)?
+        .get_backup_info()
+        .iter()
+        .map(|b| BackupInfo {
+            id: b.backup_id,
+            timestamp: b.timestamp,
+            size: b.size,
+            num_files: b.num_files,
+        })
+        .collect())
+}
+
+// Takes a new backup, sharing the files already in earlier ones.
+pub fn create(info: &dyn DbInfo, dir: &str) -> Result<BackupInfo, Box<dyn Error>> {
+    let db = db::open_db(info, &All)?;
+    let mut engine = engine(dir)?;
+    engine.create_new_backup_flush(&db, true)?;
+    info!("Backup created in {:?}", dir);
+    match list(dir)?.pop() {
+        Some(b) => Ok(b),
+        None => Err("Backup not found after create".into()),
+    }
+}
+
+pub fn verify(dir: &str, id: u32) -> Result<(), Box<dyn Error>> {
+    Ok(engine(dir)?.verify_backup(id)?)
+}
+
+// Deletes all but the latest keep backups.
+pub fn purge(dir: &str, keep: usize) -> Result<(), Box<dyn Error>> {
+    Ok(engine(dir)?.purge_old_backups(keep)?)
+}
+
+// Fails if a handle in this process has the db at path open.  RocksDB's
+// file locks are per process, so lock() can't tell.
+fn check_not_open(path: &str) -> Result<(), Box<dyn Error>> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+    let options = Options::default();
+    let cfs = match DB::list_cf(&options, path) {
+        Ok(cfs) => cfs,
+        Err(_) => return Ok(()), // not a db
+    };
+    match DB::open_cf(&options, path, cfs) {
+        Ok(_) => Ok(()),
+        Err(e) if db::is_lock_error(&e, path) => {
+            error!("Db in use: {:?}", e);
+            Err(Box::new(ErrDbInUse::new(path)))
+        }
+        Err(e) => {
+            warn!("Restoring over a db that doesn't open: {:?}", e);
+            Ok(())
+        }
+    }
+}
+
+// Takes the lock RocksDB takes on the LOCK file of the db, so that no other
+// process has the db open or opens it until the returned file is closed.
+fn lock(path: &str) -> Result<File, Box<dyn Error>> {
+    fs::create_dir_all(path)?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(Path::new(path).join("LOCK"))?;
+    match fcntl_lock(&file, FlockOperation::NonBlockingLockExclusive) {
+        Ok(()) => Ok(file),
+        Err(e) if e == Errno::AGAIN || e == Errno::ACCESS => {
+            error!("Db in use: {:?}", e);
+            Err(Box::new(ErrDbInUse::new(path)))
+        }
+        Err(e) => Err(Box::new(std::io::Error::from(e))),
+    }
+}
+
+// Restores the given backup, or the latest, replacing the db at the path.
+// The backup is restored next to the db and then moved in, all under the
+// lock of the db.  Restoring in place would delete the LOCK file, and the
+// lock with it.
+pub fn restore(info: &dyn DbInfo, dir: &str, id: Option<u32>) -> Result<(), Box<dyn Error>> {
+    check_not_open(info.path())?;
+    let _lock = lock(info.path())?;
+
+    let staging = format!("{}.restoring", info.path());
+    if Path::new(&staging).exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    let mut engine = engine(dir)?;
+    let options = RestoreOptions::default();
+    match id {
+        Some(id) => engine.restore_from_backup(&staging, &staging, &options, id)?,
+        None => engine.restore_from_latest_backup(&staging, &staging, &options)?,
+    }
+
+    // Directories, e.g. the logs of secondary instances, stay
+    for entry in fs::read_dir(info.path())? {
+        let entry = entry?;
+        if entry.file_name() != "LOCK" && entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    for entry in fs::read_dir(&staging)? {
+        let entry = entry?;
+        fs::rename(entry.path(), Path::new(info.path()).join(entry.file_name()))?;
+    }
+    fs::remove_dir(&staging)?;
+    info!("Restored {:?} from {:?}", info.path(), dir);
+    Ok(())
+}
+
+// Snapshots the db into dir, which must not exist.  Files are hard linked
+// where the filesystem allows.
+pub fn checkpoint(info: &dyn DbInfo, dir: &str) -> Result<(), Box<dyn Error>> {
+    let db = db::open_db(info, &All)?;
+    Checkpoint::new(&db)?.create_checkpoint(dir)?;
+    info!("Checkpoint of {:?} in {:?}", info.path(), dir);
+    Ok(())
+}
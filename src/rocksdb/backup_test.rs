@@ -0,0 +1,112 @@
+use std::error::Error;
+use tempfile::tempdir;
+
+use crate::rocksdb::backup;
+use crate::rocksdb::db::{self, Database, DbInfo, HasKey, OperationsBuilder};
+use crate::rocksdb::graph::Node;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+
+fn put_node(db: &Database, name: &str) -> Result<(), Box<dyn Error>> {
+    Node::operations(db).put(&mut Node {
+        name: name.into(),
+        ..Default::default()
+    })?;
+    Ok(())
+}
+
+fn has_node(db: &Database, id: u64) -> bool {
+    Node::operations(db)
+        .get(Node::id_from(id))
+        .unwrap()
+        .is_some()
+}
+
+#[test]
+fn test_backup_restore() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let dir = tempdir()?;
+    let dir = dir.path().to_str().unwrap();
+
+    {
+        let db = db::init(&db_info, &All)?;
+        put_node(&db, "api")?;
+    }
+    let first = backup::create(&db_info, dir)?;
+    {
+        let db = db::open_db(&db_info, &All)?;
+        put_node(&db, "db")?;
+    }
+    let second = backup::create(&db_info, dir)?;
+    assert!(second.id > first.id);
+    assert_eq!(2, backup::list(dir)?.len());
+    backup::verify(dir, first.id)?;
+    backup::verify(dir, second.id)?;
+
+    // Not while the db is open
+    {
+        let _db = db::open_db(&db_info, &All)?;
+        assert!(backup::restore(&db_info, dir, Some(first.id)).is_err());
+    }
+
+    backup::restore(&db_info, dir, Some(first.id))?;
+    {
+        let db = db::open_db(&db_info, &All)?;
+        assert!(has_node(&db, 1));
+        assert!(!has_node(&db, 2));
+    }
+
+    backup::restore(&db_info, dir, None)?;
+    {
+        let db = db::open_db(&db_info, &All)?;
+        assert!(has_node(&db, 2));
+    }
+
+    backup::purge(dir, 1)?;
+    assert_eq!(
+        vec![second.id],
+        backup::list(dir)?.iter().map(|b| b.id).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_checkpoint() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    {
+        let db = db::init(&db_info, &All)?;
+        put_node(&db, "api")?;
+    }
+    let dir = tempdir()?;
+    let path = dir.path().join("snapshot");
+    let snapshot = TestDbInfo::at(path.to_str().unwrap());
+    backup::checkpoint(&db_info, snapshot.path())?;
+
+    // Already exists
+    assert!(backup::checkpoint(&db_info, snapshot.path()).is_err());
+
+    let db = db::open_db(&snapshot, &All)?;
+    assert!(has_node(&db, 1));
+    Ok(())
+}
+
+#[test]
+fn test_restore_to_new_path() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let dir = tempdir()?;
+    let dir = dir.path().to_str().unwrap();
+    {
+        let db = db::init(&db_info, &All)?;
+        put_node(&db, "api")?;
+    }
+    backup::create(&db_info, dir)?;
+
+    let target = tempdir()?;
+    let path = target.path().join("restored");
+    let restored = TestDbInfo::at(path.to_str().unwrap());
+    backup::restore(&restored, dir, None)?;
+    assert!(!target.path().join("restored.restoring").exists());
+    let db = db::open_db(&restored, &All)?;
+    assert!(has_node(&db, 1));
+    Ok(())
+}
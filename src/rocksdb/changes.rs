@@ -0,0 +1,384 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::{self, AttrKey};
+use crate::rocksdb::db::{self, Database, Entity, KeyCodec, OpenMode};
+use crate::rocksdb::edge;
+use crate::rocksdb::error::{ErrBadWriteBatch, ErrWalGap};
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::value;
+
+use rocksdb::WriteBatch;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+// A put or delete of an entity, read back from the WAL.  All changes from
+// one write batch (i.e. one Operations::put or delete) share its sequence
+// number; resuming from that number skips the whole batch.
+//
+// Only the value indexes of nodes, edges and attributes are decoded; the
+// other column families hold data derived from them.  An attribute change
+// has the id of its node or edge and the attribute's name.  Bulk loads are
+// ingested as sst files and don't go through the WAL, so they don't show up
+// here.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub seq: u64,
+    pub op: Op,
+    pub entity: &'static str,
+    pub id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Put,
+    Delete,
+}
+
+// Record tags of the WriteBatch format (rocksdb/db/dbformat.h)
+const TYPE_DELETION: u8 = 0x0;
+const TYPE_VALUE: u8 = 0x1;
+const TYPE_MERGE: u8 = 0x2;
+const TYPE_LOG_DATA: u8 = 0x3;
+const TYPE_CF_DELETION: u8 = 0x4;
+const TYPE_CF_VALUE: u8 = 0x5;
+const TYPE_CF_MERGE: u8 = 0x6;
+const TYPE_SINGLE_DELETION: u8 = 0x7;
+const TYPE_CF_SINGLE_DELETION: u8 = 0x8;
+const TYPE_NOOP: u8 = 0xD;
+const TYPE_CF_RANGE_DELETION: u8 = 0xE;
+const TYPE_RANGE_DELETION: u8 = 0xF;
+
+const HEADER_SIZE: usize = 12; // sequence (8) + count (4)
+
+// One record of a write batch.  Value is None for deletions.
+#[derive(Debug, Clone, PartialEq)]
+struct Record {
+    cf_id: u32,
+    key: Vec<u8>,
+    value: Option<Vec<u8>>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, ErrBadWriteBatch> {
+        match self.data.get(self.pos) {
+            Some(b) => {
+                self.pos += 1;
+                Ok(*b)
+            }
+            None => Err(ErrBadWriteBatch::new(self.pos, "truncated")),
+        }
+    }
+
+    fn varint32(&mut self) -> Result<u32, ErrBadWriteBatch> {
+        let mut v = 0u32;
+        for shift in (0..35).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7f) as u32) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(ErrBadWriteBatch::new(self.pos, "bad varint"))
+    }
+
+    fn slice(&mut self) -> Result<Vec<u8>, ErrBadWriteBatch> {
+        let len = self.varint32()? as usize;
+        match self.data.get(self.pos..self.pos + len) {
+            Some(s) => {
+                self.pos += len;
+                Ok(s.to_vec())
+            }
+            None => Err(ErrBadWriteBatch::new(self.pos, "truncated")),
+        }
+    }
+}
+
+// Decodes the records of a write batch.  Merges and range deletions are
+// never written by this crate; they are returned as deletions of their
+// key so the sequence numbers stay in step.
+fn decode(data: &[u8]) -> Result<Vec<Record>, ErrBadWriteBatch> {
+    if data.len() < HEADER_SIZE {
+        return Err(ErrBadWriteBatch::new(0, "no header"));
+    }
+    let mut r = Reader {
+        data,
+        pos: HEADER_SIZE,
+    };
+    let mut records = vec![];
+    while r.pos < data.len() {
+        let tag = r.byte()?;
+        let cf_id = match tag {
+            TYPE_CF_DELETION
+            | TYPE_CF_VALUE
+            | TYPE_CF_MERGE
+            | TYPE_CF_SINGLE_DELETION
+            | TYPE_CF_RANGE_DELETION => r.varint32()?,
+            _ => 0,
+        };
+        match tag {
+            TYPE_VALUE | TYPE_CF_VALUE => {
+                let key = r.slice()?;
+                let value = r.slice()?;
+                records.push(Record {
+                    cf_id,
+                    key,
+                    value: Some(value),
+                });
+            }
+            TYPE_DELETION | TYPE_CF_DELETION | TYPE_SINGLE_DELETION | TYPE_CF_SINGLE_DELETION => {
+                let key = r.slice()?;
+                records.push(Record {
+                    cf_id,
+                    key,
+                    value: None,
+                });
+            }
+            TYPE_MERGE | TYPE_CF_MERGE | TYPE_RANGE_DELETION | TYPE_CF_RANGE_DELETION => {
+                let key = r.slice()?;
+                r.slice()?;
+                warn!("Unexpected record tag={:?} cf={:?}", tag, cf_id);
+                records.push(Record {
+                    cf_id,
+                    key,
+                    value: None,
+                });
+            }
+            TYPE_LOG_DATA => {
+                r.slice()?;
+            }
+            TYPE_NOOP => {}
+            _ => return Err(ErrBadWriteBatch::new(r.pos - 1, "unknown record tag")),
+        }
+    }
+    Ok(records)
+}
+
+// Column family ids aren't exposed, but a write batch records them; put
+// into a batch that is never written and read them back.
+fn cf_ids(db: &Database, cf_names: &[&str]) -> Result<HashMap<u32, String>, Box<dyn Error>> {
+    let mut ids = HashMap::new();
+    for cf_name in cf_names {
+        if let Some(cf) = db.cf_handle(cf_name) {
+            let mut batch = WriteBatch::default();
+            batch.put_cf(cf, b"", b"");
+            for record in decode(batch.data())? {
+                ids.insert(record.cf_id, cf_name.to_string());
+            }
+        }
+    }
+    Ok(ids)
+}
+
+fn ts_nano(bytes: &[u8]) -> Value {
+    match bytes.try_into() {
+        Ok(le) => json!(i128::from_le_bytes(le).to_string()),
+        Err(_) => Value::Null,
+    }
+}
+
+fn node_json(n: &Node) -> Value {
+    json!({
+        "id": n.id,
+        "type_code": n.type_code,
+        "type_name": n.type_name,
+        "name": n.name,
+        "ts_nano": ts_nano(&n.ts_nano),
+    })
+}
+
+fn edge_json(e: &Edge) -> Value {
+    json!({
+        "id": e.id,
+        "type_code": e.type_code,
+        "type_name": e.type_name,
+        "name": e.name,
+        "head": e.head,
+        "tail": e.tail,
+        "ts_nano": ts_nano(&e.ts_nano),
+    })
+}
+
+fn attr_json(a: &Attribute) -> Value {
+    let value = match a.value() {
+        Some(value::Value::Int(v)) => json!(v),
+        Some(value::Value::Float(v)) => json!(v),
+        Some(value::Value::Bool(v)) => json!(v),
+        Some(value::Value::String(v)) => json!(v),
+        Some(v) => json!(v.to_string()),
+        None => Value::Null,
+    };
+    json!({
+        "parent_id": a.parent_id,
+        "name": a.name,
+        "content_type": a.content_type,
+        "value": value,
+        "ts_nano": ts_nano(&a.ts_nano),
+    })
+}
+
+fn change(seq: u64, cf_name: &str, record: &Record) -> Result<Option<Change>, Box<dyn Error>> {
+    let (entity, id, name, value) = if cf_name == node::ById.cf_name() {
+        let value = match &record.value {
+            Some(v) => Some(node_json(&Node::from_bytes(&record.key, v)?)),
+            None => None,
+        };
+        (Node::TYPE, u64::decode_key(record.key.clone()), None, value)
+    } else if cf_name == edge::ById.cf_name() {
+        let value = match &record.value {
+            Some(v) => Some(edge_json(&Edge::from_bytes(&record.key, v)?)),
+            None => None,
+        };
+        (Edge::TYPE, u64::decode_key(record.key.clone()), None, value)
+    } else if cf_name == attribute::ById.cf_name() {
+        let key = AttrKey::decode_key(record.key.clone());
+        let value = match &record.value {
+            Some(v) => Some(attr_json(&Attribute::from_bytes(&record.key, v)?)),
+            None => None,
+        };
+        (Attribute::TYPE, key.parent_id, Some(key.name), value)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Change {
+        seq,
+        op: match value {
+            Some(_) => Op::Put,
+            None => Op::Delete,
+        },
+        entity,
+        id,
+        name,
+        value,
+    }))
+}
+
+// Receives the changes of one write batch.
+pub type BatchFn<'a> = dyn FnMut(&[Change]) -> Result<(), Box<dyn Error>> + 'a;
+
+// The WAL is kept for wal_ttl_seconds; resuming from a position older than
+// that would silently skip the changes in between.  Starting from 0 reads
+// whatever is still there.
+fn check_gap(db: &Database, since: u64) -> Result<(), Box<dyn Error>> {
+    if since == 0 {
+        return Ok(());
+    }
+    let oldest = match db.get_updates_since(0)?.next() {
+        Some(item) => item?.0,
+        None => db.latest_sequence_number() + 1,
+    };
+    if oldest > since + 1 {
+        return Err(Box::new(ErrWalGap::new(since, oldest)));
+    }
+    Ok(())
+}
+
+// Calls f with the changes of each write batch after since, in order.
+// Returns the last sequence number of the last batch read, or since if
+// none; a batch takes one sequence number per record.
+pub fn changes_since(db: &Database, since: u64, f: &mut BatchFn) -> Result<u64, Box<dyn Error>> {
+    check_gap(db, since)?;
+    let ids = cf_ids(
+        db,
+        &[
+            node::ById.cf_name(),
+            edge::ById.cf_name(),
+            attribute::ById.cf_name(),
+        ],
+    )?;
+    let mut last = since;
+    for item in db.get_updates_since(since)? {
+        let (seq, batch) = item?;
+        let mut changes = vec![];
+        for record in decode(batch.data())? {
+            if let Some(cf_name) = ids.get(&record.cf_id) {
+                if let Some(c) = change(seq, cf_name, &record)? {
+                    // A batch can write the same key more than once (delete
+                    // does); the last write is what the batch did.
+                    changes.retain(|p: &Change| {
+                        (p.entity, p.id, &p.name) != (c.entity, c.id, &c.name)
+                    });
+                    changes.push(c);
+                }
+            }
+        }
+        if !changes.is_empty() {
+            f(&changes)?;
+        }
+        last = seq + batch.len() as u64 - 1;
+    }
+    Ok(last)
+}
+
+// The cursor file holds the sequence number to resume from.
+pub fn read_cursor(path: &str) -> Result<Option<u64>, Box<dyn Error>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.trim().parse::<u64>()?))
+}
+
+fn write_cursor(path: &str, seq: u64) -> Result<(), Box<dyn Error>> {
+    // Write then rename so a crash doesn't leave a partial file
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, seq.to_string())?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// Writes the changes after since as JSON lines, saving the position in the
+// cursor file after each batch.  With follow, polls for new changes until
+// the output is closed; a secondary db catches up with the primary before
+// each poll.
+pub fn stream(
+    db: &Database,
+    mode: OpenMode,
+    since: u64,
+    follow: bool,
+    cursor: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<u64, Box<dyn Error>> {
+    let mut last = since;
+    loop {
+        last = changes_since(db, last, &mut |changes: &[Change]| {
+            for c in changes {
+                writeln!(out, "{}", serde_json::to_string(c)?)?;
+            }
+            out.flush()?;
+            match cursor {
+                Some(path) => write_cursor(path, changes[0].seq),
+                None => Ok(()),
+            }
+        })?;
+        if let Some(path) = cursor {
+            write_cursor(path, last)?;
+        }
+        if !follow {
+            return Ok(last);
+        }
+        thread::sleep(Duration::from_millis(500));
+        if mode == OpenMode::Secondary {
+            db::catch_up(db)?;
+        }
+    }
+}
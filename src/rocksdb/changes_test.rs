@@ -0,0 +1,211 @@
+use std::error::Error;
+use tempfile::tempdir;
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::changes::{self, Change, Op};
+use crate::rocksdb::db::{self, HasKey, OpenMode, OperationsBuilder};
+use crate::rocksdb::error::ErrWalGap;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+fn collect(db: &db::Database, since: u64) -> Result<(Vec<Change>, u64), Box<dyn Error>> {
+    let mut all = vec![];
+    let last = changes::changes_since(db, since, &mut |changes: &[Change]| {
+        all.extend_from_slice(changes);
+        Ok(())
+    })?;
+    Ok((all, last))
+}
+
+#[test]
+fn test_changes() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    let mut edge_ops = Edge::operations(&db);
+    for name in ["api", "db"] {
+        node_ops.put(&mut Node {
+            name: name.into(),
+            type_name: "service".into(),
+            ..Default::default()
+        })?;
+    }
+    edge_ops.put(&mut Edge {
+        head: 1,
+        tail: 2,
+        name: "depends-on".into(),
+        type_name: "depends-on".into(),
+        ..Default::default()
+    })?;
+
+    let (all, last) = collect(&db, 0)?;
+    assert_eq!(3, all.len());
+    assert_eq!((Op::Put, "Node", 1), (all[0].op, all[0].entity, all[0].id));
+    assert_eq!("api", all[0].value.as_ref().unwrap()["name"]);
+    assert_eq!((Op::Put, "Edge", 3), (all[2].op, all[2].entity, all[2].id));
+    assert_eq!(2, all[2].value.as_ref().unwrap()["tail"]);
+
+    // Resume after the last batch read
+    let node = node_ops.get(Node::id_from(1u64))?.unwrap();
+    node_ops.delete(&node)?;
+    let (more, _) = collect(&db, last)?;
+    assert_eq!(1, more.len());
+    assert_eq!(
+        (Op::Delete, "Node", 1, None),
+        (
+            more[0].op,
+            more[0].entity,
+            more[0].id,
+            more[0].value.clone()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_attribute_changes() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    Node::operations(&db).put(&mut Node {
+        name: "api".into(),
+        ..Default::default()
+    })?;
+    let (_, last) = collect(&db, 0)?;
+
+    let mut attr_ops = Attribute::operations(&db);
+    attr_ops.put(&mut Attribute::typed(1, "replicas", &Value::Int(3)))?;
+    let (all, last) = collect(&db, last)?;
+    assert_eq!(1, all.len());
+    assert_eq!(
+        (Op::Put, "Attribute", 1, Some("replicas".to_string())),
+        (all[0].op, all[0].entity, all[0].id, all[0].name.clone())
+    );
+    assert_eq!(3, all[0].value.as_ref().unwrap()["value"]);
+
+    let attr = attr_ops
+        .get(Attribute::id_from(AttrKey {
+            parent_id: 1,
+            name: "replicas".into(),
+        }))?
+        .unwrap();
+    attr_ops.delete(&attr)?;
+    let (more, _) = collect(&db, last)?;
+    assert_eq!(1, more.len());
+    assert_eq!(
+        (
+            Op::Delete,
+            "Attribute",
+            1,
+            Some("replicas".to_string()),
+            None
+        ),
+        (
+            more[0].op,
+            more[0].entity,
+            more[0].id,
+            more[0].name.clone(),
+            more[0].value.clone()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_stream_cursor() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    node_ops.put(&mut Node {
+        name: "api".into(),
+        ..Default::default()
+    })?;
+
+    let dir = tempdir()?;
+    let cursor = dir.path().join("cursor");
+    let cursor = cursor.to_str().unwrap();
+    assert_eq!(None, changes::read_cursor(cursor)?);
+
+    let mut out = Vec::<u8>::new();
+    changes::stream(&db, OpenMode::Primary, 0, false, Some(cursor), &mut out)?;
+    let lines: Vec<serde_json::Value> = String::from_utf8(out)?
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(1, lines.len());
+    assert_eq!("put", lines[0]["op"]);
+
+    // Nothing new from the saved position
+    let saved = changes::read_cursor(cursor)?.unwrap();
+    let mut out = Vec::<u8>::new();
+    changes::stream(&db, OpenMode::Primary, saved, false, Some(cursor), &mut out)?;
+    assert!(out.is_empty());
+
+    node_ops.put(&mut Node {
+        name: "db".into(),
+        ..Default::default()
+    })?;
+    let mut out = Vec::<u8>::new();
+    changes::stream(&db, OpenMode::Primary, saved, false, Some(cursor), &mut out)?;
+    assert_eq!(1, String::from_utf8(out)?.lines().count());
+    assert!(changes::read_cursor(cursor)?.unwrap() > saved);
+    Ok(())
+}
+
+#[test]
+fn test_stream_from_secondary() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let primary = db::init(&db_info, &All)?;
+    let mut node_ops = Node::operations(&primary);
+    node_ops.put(&mut Node {
+        name: "api".into(),
+        ..Default::default()
+    })?;
+
+    // The primary keeps the db open and writing
+    let dir = tempdir()?;
+    let secondary = db::open_secondary(&db_info, &All, dir.path().to_str().unwrap())?;
+    let (all, last) = collect(&secondary, 0)?;
+    assert_eq!(1, all.len());
+
+    node_ops.put(&mut Node {
+        name: "db".into(),
+        ..Default::default()
+    })?;
+    db::catch_up(&secondary)?;
+    let (more, _) = collect(&secondary, last)?;
+    assert_eq!(1, more.len());
+    assert_eq!("db", more[0].value.as_ref().unwrap()["name"]);
+    Ok(())
+}
+
+#[test]
+fn test_gap() -> Result<(), Box<dyn Error>> {
+    // Without a WAL TTL the flushed WAL is deleted
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    for name in ["api", "db"] {
+        node_ops.put(&mut Node {
+            name: name.into(),
+            ..Default::default()
+        })?;
+    }
+    let (_, first) = collect(&db, 0)?;
+    // Reopening flushes the recovered WAL and deletes it
+    drop(node_ops);
+    drop(db);
+    let db = db::open_db(&db_info, &All)?;
+    Node::operations(&db).put(&mut Node {
+        name: "cache".into(),
+        ..Default::default()
+    })?;
+
+    let err = collect(&db, 1).unwrap_err();
+    assert!(err.downcast_ref::<ErrWalGap>().is_some());
+    // Up to date before the flush, so nothing was missed
+    let (more, _) = collect(&db, first)?;
+    assert_eq!(1, more.len());
+    Ok(())
+}
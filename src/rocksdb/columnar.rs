@@ -0,0 +1,206 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{Entity, HasKey, OperationsBuilder, Visitor};
+use crate::rocksdb::export::Counts;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::store::Store;
+
+use arrow::array::{ArrayRef, StringArray, TimestampNanosecondArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+// Rows per record batch, unless given.
+pub static BATCH_ROWS: usize = 8192;
+
+// Names of the files written into the export directory.
+pub static NODES_FILE: &str = "nodes.arrow";
+pub static EDGES_FILE: &str = "edges.arrow";
+
+// An entity as a row of an Arrow table.  The ts column is null where
+// ts_nano is not set, or is past what nanoseconds in an i64 can hold.
+pub trait Table: Sized {
+    fn schema() -> SchemaRef;
+    fn batch(rows: &[Self]) -> Result<RecordBatch, Box<dyn Error>>;
+}
+
+fn id_field(name: &str) -> Field {
+    Field::new(name, DataType::UInt64, false)
+}
+
+fn ts_field() -> Field {
+    Field::new(
+        "ts",
+        DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        true,
+    )
+}
+
+fn ts(bytes: &[u8]) -> Option<i64> {
+    let le = bytes.try_into().ok()?;
+    i64::try_from(i128::from_le_bytes(le)).ok()
+}
+
+fn ts_column<'a>(ts_nanos: impl Iterator<Item = &'a [u8]>) -> ArrayRef {
+    Arc::new(TimestampNanosecondArray::from_iter(ts_nanos.map(ts)).with_timezone("UTC"))
+}
+
+impl Table for Node {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            id_field("id"),
+            id_field("type_code"),
+            Field::new("type_name", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            ts_field(),
+        ]))
+    }
+
+    fn batch(rows: &[Node]) -> Result<RecordBatch, Box<dyn Error>> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|n| n.id))),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|n| n.type_code),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|n| &n.type_name),
+            )),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|n| &n.name))),
+            ts_column(rows.iter().map(|n| n.ts_nano.as_slice())),
+        ];
+        Ok(RecordBatch::try_new(Node::schema(), columns)?)
+    }
+}
+
+impl Table for Edge {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            id_field("id"),
+            id_field("type_code"),
+            Field::new("type_name", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            id_field("head"),
+            id_field("tail"),
+            ts_field(),
+        ]))
+    }
+
+    fn batch(rows: &[Edge]) -> Result<RecordBatch, Box<dyn Error>> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|e| e.id))),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|e| e.type_code),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|e| &e.type_name),
+            )),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|e| &e.name))),
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|e| e.head))),
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|e| e.tail))),
+            ts_column(rows.iter().map(|e| e.ts_nano.as_slice())),
+        ];
+        Ok(RecordBatch::try_new(Edge::schema(), columns)?)
+    }
+}
+
+// Writes the nodes and edges, in key order, as two Arrow IPC files.  Ids
+// are little endian, so key order isn't id order; sort by the id column if
+// that matters.  Rows are read from the value indexes a batch at a time, so
+// the graph needn't fit in memory.  Attributes are not written.
+pub fn export_arrow(
+    db: &dyn Store,
+    nodes_out: &mut dyn Write,
+    edges_out: &mut dyn Write,
+    batch_rows: usize,
+) -> Result<Counts, Box<dyn Error>> {
+    Ok(Counts {
+        nodes: write_table::<Node>(db, nodes_out, batch_rows)?,
+        edges: write_table::<Edge>(db, edges_out, batch_rows)?,
+        ..Default::default()
+    })
+}
+
+// Writes the nodes and edges files into the directory, creating it if need
+// be.
+pub fn export_arrow_dir(
+    db: &dyn Store,
+    dir: &Path,
+    batch_rows: usize,
+) -> Result<Counts, Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let mut nodes = BufWriter::new(File::create(dir.join(NODES_FILE))?);
+    let mut edges = BufWriter::new(File::create(dir.join(EDGES_FILE))?);
+    let counts = export_arrow(db, &mut nodes, &mut edges, batch_rows)?;
+    nodes.flush()?;
+    edges.flush()?;
+    Ok(counts)
+}
+
+// Writes every E, returning how many.
+pub fn write_table<E>(
+    db: &dyn Store,
+    out: &mut dyn Write,
+    batch_rows: usize,
+) -> Result<u64, Box<dyn Error>>
+where
+    E: Table + Entity + HasKey<u64> + OperationsBuilder<E>,
+{
+    let mut batches = Batches {
+        writer: FileWriter::try_new(out, &E::schema())?,
+        rows: Vec::with_capacity(batch_rows),
+        batch_rows: batch_rows.max(1),
+        count: 0,
+        error: None,
+    };
+    E::operations(db).visit(E::id_from(0), Box::new(&mut batches))?;
+    if let Some(e) = batches.error.take() {
+        return Err(e);
+    }
+    batches.flush()?;
+    batches.writer.finish()?;
+    Ok(batches.count)
+}
+
+struct Batches<'a, E> {
+    writer: FileWriter<&'a mut dyn Write>,
+    rows: Vec<E>,
+    batch_rows: usize,
+    count: u64,
+    // Stops the visit; returned once it's done
+    error: Option<Box<dyn Error>>,
+}
+
+impl<E: Table> Batches<'_, E> {
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        self.writer.write(&E::batch(&self.rows)?)?;
+        self.count += self.rows.len() as u64;
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+impl<E: Table> Visitor<E> for &mut Batches<'_, E> {
+    fn visit(&mut self, e: E) -> bool {
+        self.rows.push(e);
+        if self.rows.len() < self.batch_rows {
+            return true;
+        }
+        match self.flush() {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, TimeUnit, TimestampNanosecondType, UInt64Type};
+use arrow::ipc::reader::FileReader;
+use arrow::record_batch::RecordBatch;
+use std::error::Error;
+use std::io::Cursor;
+use tempfile::tempdir;
+
+use crate::rocksdb::columnar::{self, Table};
+use crate::rocksdb::db::{self, OperationsBuilder};
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+
+// n nodes in a chain, the first with its timestamp given.
+fn build(db: &dyn Store, n: u64) -> Result<(), Box<dyn Error>> {
+    let mut ids = vec![];
+    for i in 0..n {
+        let mut node = Node {
+            name: format!("n{}", i),
+            type_name: ["service", "host"][i as usize % 2].into(),
+            ..Default::default()
+        };
+        if i == 0 {
+            node.ts_nano = 1_700_000_000_000_000_001i128.to_le_bytes().to_vec();
+        }
+        Node::operations(db).put(&mut node)?;
+        ids.push(node.id);
+    }
+    for pair in ids.windows(2) {
+        Edge::operations(db).put(&mut Edge {
+            name: "next".into(),
+            type_name: "link".into(),
+            head: pair[0],
+            tail: pair[1],
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+fn export(db: &dyn Store, batch_rows: usize) -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
+    let (mut nodes, mut edges) = (vec![], vec![]);
+    let counts = columnar::export_arrow(db, &mut nodes, &mut edges, batch_rows)?;
+    assert!(counts.nodes > 0 && counts.edges == counts.nodes - 1);
+    Ok((nodes, edges))
+}
+
+fn read(bytes: Vec<u8>) -> Result<Vec<RecordBatch>, Box<dyn Error>> {
+    let reader = FileReader::try_new(Cursor::new(bytes), None)?;
+    Ok(reader.collect::<Result<_, _>>()?)
+}
+
+fn u64s(batches: &[RecordBatch], column: &str) -> Vec<u64> {
+    batches
+        .iter()
+        .flat_map(|b| {
+            let c = b.column_by_name(column).unwrap();
+            c.as_primitive::<UInt64Type>().values().to_vec()
+        })
+        .collect()
+}
+
+#[test]
+fn test_export_arrow() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    build(&db, 3)?;
+    let (nodes, edges) = export(&db, 100)?;
+
+    let nodes = read(nodes)?;
+    assert_eq!(1, nodes.len());
+    assert_eq!(Node::schema(), nodes[0].schema());
+    assert_eq!(vec![1, 2, 3], u64s(&nodes, "id"));
+    let codes = u64s(&nodes, "type_code");
+    assert!(codes[0] == codes[2] && codes[0] != codes[1]);
+    let names = nodes[0].column_by_name("name").unwrap().as_string::<i32>();
+    assert_eq!(
+        vec!["n0", "n1", "n2"],
+        names.iter().flatten().collect::<Vec<_>>()
+    );
+    let types = nodes[0]
+        .column_by_name("type_name")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!("host", types.value(1));
+
+    let ts = nodes[0].column_by_name("ts").unwrap();
+    assert_eq!(
+        &DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        ts.data_type()
+    );
+    let ts = ts.as_primitive::<TimestampNanosecondType>();
+    assert_eq!(1_700_000_000_000_000_001, ts.value(0));
+    // Set by put to now
+    assert!(ts.value(1) > ts.value(0));
+    assert_eq!(0, ts.null_count());
+
+    let edges = read(edges)?;
+    assert_eq!(Edge::schema(), edges[0].schema());
+    assert_eq!(vec![4, 5], u64s(&edges, "id"));
+    assert_eq!(vec![1, 2], u64s(&edges, "head"));
+    assert_eq!(vec![2, 3], u64s(&edges, "tail"));
+    Ok(())
+}
+
+#[test]
+fn test_batches() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    build(&db, 10)?;
+    let (nodes, edges) = export(&db, 4)?;
+    let nodes = read(nodes)?;
+    assert_eq!(
+        vec![4, 4, 2],
+        nodes.iter().map(|b| b.num_rows()).collect::<Vec<_>>()
+    );
+    assert_eq!((1..=10).collect::<Vec<u64>>(), u64s(&nodes, "id"));
+    let edges = read(edges)?;
+    assert_eq!(
+        vec![4, 4, 1],
+        edges.iter().map(|b| b.num_rows()).collect::<Vec<_>>()
+    );
+
+    // An unset or out of range ts is null
+    let mut rows = vec![Node::default(), Node::default()];
+    rows[1].ts_nano = i128::MAX.to_le_bytes().to_vec();
+    let batch = Node::batch(&rows)?;
+    assert_eq!(2, batch.column_by_name("ts").unwrap().null_count());
+
+    // An empty graph is a file with the schema and no batches
+    let (mut nodes, mut edges) = (vec![], vec![]);
+    let empty = MemStore::new(&All);
+    let counts = columnar::export_arrow(&empty, &mut nodes, &mut edges, 4)?;
+    assert_eq!((0, 0), (counts.nodes, counts.edges));
+    let reader = FileReader::try_new(Cursor::new(nodes), None)?;
+    assert_eq!(Node::schema(), reader.schema());
+    assert_eq!(0, reader.count());
+    Ok(())
+}
+
+#[test]
+fn test_export_arrow_from_rocksdb() -> Result<(), Box<dyn Error>> {
+    let info = TestDbInfo::new();
+    let db = db::init(&info, &All)?;
+    build(&db, 5)?;
+    let tmp = tempdir()?;
+    let dir = tmp.path().join("out");
+    let counts = columnar::export_arrow_dir(&db, &dir, 2)?;
+    assert_eq!((5, 4), (counts.nodes, counts.edges));
+    let nodes = std::fs::read(dir.join(columnar::NODES_FILE))?;
+    assert_eq!((1..=5).collect::<Vec<u64>>(), u64s(&read(nodes)?, "id"));
+    let edges = std::fs::read(dir.join(columnar::EDGES_FILE))?;
+    assert_eq!(vec![1, 2, 3, 4], u64s(&read(edges)?, "head"));
+    Ok(())
+}
@@ -1,33 +1,68 @@
 #[allow(unused_imports)]
 use tracing::{debug, error, info, trace, warn};
 
-use crate::rocksdb::db::{self, HasKey, Visitor};
+use crate::rocksdb::attribute::{self, AttrKey, AttributePrinter};
+use crate::rocksdb::backfill;
+use crate::rocksdb::backup;
+use crate::rocksdb::changes;
+use crate::rocksdb::columnar;
+use crate::rocksdb::csv_import::{Columns, CsvImporter};
+use crate::rocksdb::db::{self, HasKey, OpenMode, Visitor};
+use crate::rocksdb::diagram;
+use crate::rocksdb::document::{self, Document, DocumentPrinter};
 use crate::rocksdb::edge::{self, EdgeCollector, EdgePrinter};
-use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::export;
+use crate::rocksdb::fsck;
+use crate::rocksdb::gc;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::graphql;
 use crate::rocksdb::index::Index;
+use crate::rocksdb::interchange::{self, XmlFormat};
+use crate::rocksdb::load;
+use crate::rocksdb::migrate;
 use crate::rocksdb::node;
 use crate::rocksdb::node::NodePrinter;
+use crate::rocksdb::query;
+use crate::rocksdb::rdf::{self, RdfFormat};
+use crate::rocksdb::registry;
+use crate::rocksdb::script;
+use crate::rocksdb::server;
+use crate::rocksdb::spec::{self, DbSpec};
+use crate::rocksdb::store::{Batch, Overlay, Store};
+use crate::rocksdb::value::Value;
 use crate::rocksdb::All;
 
 use crate::rocksdb::db::OperationsBuilder;
 
-use clap::{Args as clapArgs, Subcommand};
+use clap::{Args as clapArgs, Subcommand, ValueEnum};
 use rocksdb::Options;
 use std::default::Default;
+use std::rc::Rc;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, clapArgs, PartialEq, Eq)]
+#[derive(Debug, Clone, clapArgs, PartialEq)]
 pub struct DbArgs {
     /// The DB path
     path: String,
-}
 
-type Err = std::num::ParseIntError;
+    /// The db spec, when the path given is a YAML file
+    #[clap(skip)]
+    spec: Option<DbSpec>,
+}
 
 impl DbArgs {
-    fn from_str(s: &str) -> Result<Self, Err> {
+    fn from_str(s: &str) -> Result<Self, String> {
         // TODO - Check path for valid rocksdb directory
+        if s.ends_with(".yaml") || s.ends_with(".yml") {
+            let spec = DbSpec::load(s).map_err(|e| format!("Bad db spec {:?}: {}", s, e))?;
+            return Ok(DbArgs {
+                path: spec.path.clone(),
+                spec: Some(spec),
+            });
+        }
         Ok(DbArgs {
             path: s.to_string(),
+            spec: None,
         })
     }
 }
@@ -41,13 +76,20 @@ impl db::DbInfo for DbArgs {
         options.set_error_if_exists(false);
         options.create_if_missing(true);
         options.create_missing_column_families(true);
-        return options;
+        match &self.spec {
+            Some(spec) => spec.db_options(&mut options),
+            None => options.set_wal_ttl_seconds(spec::DEFAULT_WAL_TTL_SECONDS),
+        }
+        options
+    }
+    fn spec(&self) -> Option<&DbSpec> {
+        self.spec.as_ref()
     }
 }
 
 // Required for claps
 impl std::str::FromStr for DbArgs {
-    type Err = std::num::ParseIntError; // TODO - replace with better error
+    type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Self::from_str(s)
     }
@@ -56,7 +98,7 @@ impl std::str::FromStr for DbArgs {
 #[derive(Debug, clapArgs)]
 #[clap(args_conflicts_with_subcommands = false)]
 pub struct Command {
-    /// Path of the db file. TODO(future - full db spec with config params)
+    /// Path of the db, or of a YAML db spec with the path and options
     #[clap(long = "path", short = 'p')]
     pub db: DbArgs,
 
@@ -67,15 +109,304 @@ pub struct Command {
 #[derive(Debug, Subcommand)]
 pub enum Verb {
     Init(InitArgs),
+    Load(LoadArgs),
+    Fsck(FsckArgs),
+    Reindex(ReindexArgs),
+    Migrate(MigrateArgs),
+    Backup(BackupCommand),
+    Checkpoint(CheckpointArgs),
+    Changes(ChangesArgs),
     Counter(CounterArgs),
     Index(IndexCommand),
     Node(NodeCommand),
     Edge(EdgeCommand),
+    Doc(DocCommand),
+    Attr(AttrCommand),
+    Query(QueryArgs),
+    Script(ScriptArgs),
+    ServeGraphql(ServeGraphqlArgs),
+    Export(ExportArgs),
+    Import(ImportArgs),
+    ImportCsv(ImportCsvArgs),
 }
 
 #[derive(Debug, clapArgs)]
 pub struct InitArgs {}
 
+/// Bulk loads nodes and edges from a JSON lines file via sst ingestion
+#[derive(Debug, clapArgs)]
+pub struct LoadArgs {
+    /// Path of the JSON lines file
+    file: String,
+}
+
+/// Checks the secondary indexes and counters against the stored entities
+#[derive(Debug, clapArgs)]
+pub struct FsckArgs {}
+
+/// Rebuilds secondary indexes and counters from the stored entities
+#[derive(Debug, clapArgs)]
+pub struct ReindexArgs {
+    /// Name of the index column family to rebuild, e.g. index.node.name
+    #[clap(long)]
+    index: Option<String>,
+}
+
+/// Migrates the db to the schema version of this binary
+#[derive(Debug, clapArgs)]
+pub struct MigrateArgs {
+    /// Show the steps and changes without making them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Directory for the checkpoint taken first; defaults to <path>.v<version>.backup
+    #[clap(long)]
+    backup: Option<String>,
+}
+
+#[derive(Debug, clapArgs)]
+pub struct BackupCommand {
+    /// Backup directory; defaults to <path>.backups
+    #[clap(long)]
+    dir: Option<String>,
+
+    #[clap(subcommand)]
+    verb: BackupVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupVerb {
+    Create,
+    List,
+    Verify(BackupIdArgs),
+    Restore(BackupRestoreArgs),
+    Purge(BackupPurgeArgs),
+}
+
+#[derive(Debug, clapArgs)]
+pub struct BackupIdArgs {
+    /// The backup id
+    id: u32,
+}
+
+#[derive(Debug, clapArgs)]
+pub struct BackupRestoreArgs {
+    /// The backup id; the latest if not given
+    id: Option<u32>,
+}
+
+#[derive(Debug, clapArgs)]
+pub struct BackupPurgeArgs {
+    /// How many of the latest backups to keep
+    #[clap(long, default_value_t = 1)]
+    keep: usize,
+}
+
+/// Snapshots the db into a new directory with hard links
+#[derive(Debug, clapArgs)]
+pub struct CheckpointArgs {
+    /// The directory, which must not exist
+    dir: String,
+}
+
+/// Runs a graph query, e.g.
+/// MATCH (a:service)-[:depends-on*1..3]->(b) WHERE a.name = "api" RETURN b.name
+#[derive(Debug, clapArgs)]
+pub struct QueryArgs {
+    /// The query
+    text: String,
+
+    /// Print the plan and its estimates instead of running it
+    #[clap(long)]
+    explain: bool,
+}
+
+/// Runs a Rhai script with node and edge bindings; its writes are committed
+/// together when it finishes
+#[derive(Debug, clapArgs)]
+pub struct ScriptArgs {
+    /// Path of the .rhai file
+    file: String,
+
+    /// Print the writes instead of committing them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Operations the script may run before it is stopped
+    #[clap(long, default_value_t = script::DEFAULT_MAX_STEPS)]
+    max_steps: u64,
+}
+
+/// Serves a read only GraphQL schema of the nodes, edges and attributes over
+/// HTTP at /graphql, from a secondary instance that follows the primary
+#[derive(Debug, clapArgs)]
+pub struct ServeGraphqlArgs {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1:8000")]
+    addr: String,
+
+    /// Print the schema instead of serving it
+    #[clap(long)]
+    print_schema: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum ExportFormat {
+    /// One JSON object per symbol, node, edge and attribute; import reads it back
+    Jsonl,
+    /// GraphViz digraph, nodes colored by type
+    Dot,
+    /// Mermaid flowchart, nodes colored by type
+    Mermaid,
+    /// GraphML for yEd and other tools; import reads it back
+    Graphml,
+    /// GEXF for Gephi; import reads it back
+    Gexf,
+    /// RDF triples, one per line, with IRIs under --base
+    Ntriples,
+    /// RDF triples grouped by node, with the prefixes
+    Turtle,
+    /// Arrow IPC files of the nodes and of the edges, in the --out directory
+    Arrow,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum ImportFormat {
+    Jsonl,
+    Graphml,
+    Gexf,
+    Ntriples,
+    Turtle,
+}
+
+/// Writes the whole graph in a portable format, or draws a part of it
+#[derive(Debug, clapArgs)]
+pub struct ExportArgs {
+    #[clap(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+    format: ExportFormat,
+
+    /// Output file; defaults to stdout.  A directory for arrow
+    #[clap(long, required_if_eq("format", "arrow"))]
+    out: Option<String>,
+
+    /// Name of the node to draw out from, for dot and mermaid
+    #[clap(long)]
+    root: Option<String>,
+
+    /// Hops to follow from the root
+    #[clap(long, requires = "root")]
+    depth: Option<usize>,
+
+    /// Only draw edges of this type; repeat for more
+    #[clap(long = "edge-type")]
+    edge_types: Vec<String>,
+
+    /// Base of the IRIs of nodes, types, edges and attributes, for RDF
+    #[clap(long, default_value = rdf::DEFAULT_BASE)]
+    base: String,
+
+    /// YAML map of prefix to IRI, for RDF
+    #[clap(long)]
+    prefixes: Option<String>,
+}
+
+/// Reads back an export, or a graph from another tool, rebuilding the
+/// indexes
+#[derive(Debug, clapArgs)]
+pub struct ImportArgs {
+    /// Path of the export
+    file: String,
+
+    #[clap(long, value_enum, default_value_t = ImportFormat::Jsonl)]
+    format: ImportFormat,
+
+    /// Keep the ids in the file instead of taking new ones; none may be in use.
+    /// Not for RDF, where ids are always new
+    #[clap(long)]
+    preserve_ids: bool,
+
+    /// Base of the IRIs of nodes, types, edges and attributes, for RDF
+    #[clap(long, default_value = rdf::DEFAULT_BASE)]
+    base: String,
+
+    /// YAML map of prefix to IRI, naming the IRIs not under the base, for RDF
+    #[clap(long)]
+    prefixes: Option<String>,
+}
+
+/// Adds nodes and edges from CSV files with a header row.  Bad rows are
+/// reported and skipped; the rest is committed together
+#[derive(Debug, clapArgs)]
+pub struct ImportCsvArgs {
+    /// CSV of nodes, one per row
+    #[clap(long)]
+    nodes: Option<String>,
+
+    /// CSV of edges, one per row
+    #[clap(long)]
+    edges: Option<String>,
+
+    /// Column of the node names
+    #[clap(long, default_value = "name")]
+    name_column: String,
+
+    /// Column of the node types; nodes are entity if missing
+    #[clap(long, default_value = "type")]
+    type_column: String,
+
+    /// Column of a key the edges use for nodes, instead of the name
+    #[clap(long)]
+    key_column: Option<String>,
+
+    /// Column to add as a node attribute; repeat for more
+    #[clap(long = "attr")]
+    attrs: Vec<String>,
+
+    /// Column of the edge heads
+    #[clap(long, default_value = "head")]
+    head_column: String,
+
+    /// Column of the edge tails
+    #[clap(long, default_value = "tail")]
+    tail_column: String,
+
+    /// Column of the edge names
+    #[clap(long, default_value = "name")]
+    edge_name_column: String,
+
+    /// Column of the edge types; the name if missing
+    #[clap(long, default_value = "type")]
+    edge_type_column: String,
+
+    /// Column to add as an edge attribute; repeat for more
+    #[clap(long = "edge-attr")]
+    edge_attrs: Vec<String>,
+
+    /// Use the existing node with the same name instead of adding another
+    #[clap(long)]
+    dedup: bool,
+
+    /// Report what would be imported without committing
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// Streams node and edge changes from the WAL as JSON lines
+#[derive(Debug, clapArgs)]
+pub struct ChangesArgs {
+    /// Sequence number to start after; defaults to the cursor, else 0
+    #[clap(long)]
+    since: Option<u64>,
+
+    /// Keep waiting for new changes
+    #[clap(long)]
+    follow: bool,
+
+    /// File to save the sequence number in, for resuming
+    #[clap(long)]
+    cursor: Option<String>,
+}
+
 #[derive(Debug, clapArgs)]
 pub struct CounterArgs {
     /// The key
@@ -90,8 +421,11 @@ pub struct IndexCommand {
 
 #[derive(Debug, Subcommand)]
 pub enum IndexVerb {
+    /// Lists the indexes of each registered entity type
     All,
     Dump(IndexArgs),
+    Backfill(BackfillArgs),
+    Gc(GcArgs),
 }
 
 #[derive(Debug, clapArgs)]
@@ -100,6 +434,26 @@ pub struct IndexArgs {
     index: String,
 }
 
+/// Backfills indexes added to a db that already has data
+#[derive(Debug, clapArgs)]
+pub struct BackfillArgs {
+    /// Name of the index column family; all pending ones if not given
+    #[clap(long)]
+    index: Option<String>,
+
+    /// Only show the pending backfills and their progress
+    #[clap(long)]
+    status: bool,
+}
+
+/// Drops column families no longer declared by any index
+#[derive(Debug, clapArgs)]
+pub struct GcArgs {
+    /// Drop without asking for confirmation
+    #[clap(long)]
+    yes: bool,
+}
+
 #[derive(Debug, clapArgs)]
 pub struct NodeCommand {
     #[clap(subcommand)]
@@ -115,6 +469,7 @@ pub enum NodeVerb {
     List(NodeListArgs),
     Lookup(NodeLookupArgs),
     Exact(NodeExactArgs),
+    Where(NodeWhereArgs),
 }
 
 #[derive(Debug, clapArgs)]
@@ -178,6 +533,143 @@ pub struct NodeListArgs {
     n: usize,
 }
 
+/// Finds the nodes matching the conditions, using the index the planner
+/// picks
+#[derive(Debug, clapArgs)]
+pub struct NodeWhereArgs {
+    /// Conditions on the id, name, type or attributes joined by and, e.g.
+    /// 'type = "service" and size > 100'
+    conditions: String,
+
+    /// How many to return
+    #[clap(long, default_value_t = 100)]
+    n: usize,
+
+    /// Print the plan and its estimates instead of running it
+    #[clap(long)]
+    explain: bool,
+}
+
+/// Typed attributes of nodes and edges
+#[derive(Debug, clapArgs)]
+pub struct AttrCommand {
+    #[clap(subcommand)]
+    verb: AttrVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AttrVerb {
+    Set(AttrSetArgs),
+    Get(AttrGetArgs),
+    Delete(AttrGetArgs),
+    List(AttrListArgs),
+}
+
+impl AttrVerb {
+    fn is_read(&self) -> bool {
+        matches!(self, AttrVerb::Get(_) | AttrVerb::List(_))
+    }
+}
+
+/// Sets an attribute of a node or edge
+#[derive(Debug, clapArgs)]
+pub struct AttrSetArgs {
+    /// The id of the node or edge
+    parent_id: u64,
+
+    /// The name of the attribute
+    name: String,
+
+    /// The value
+    value: String,
+
+    /// int, float, bool, string or timestamp; guessed from the value if not
+    /// given
+    #[clap(long = "type")]
+    content_type: Option<String>,
+}
+
+#[derive(Debug, clapArgs)]
+pub struct AttrGetArgs {
+    /// The id of the node or edge
+    parent_id: u64,
+
+    /// The name of the attribute
+    name: String,
+}
+
+#[derive(Debug, clapArgs)]
+pub struct AttrListArgs {
+    /// The id of the node or edge
+    parent_id: u64,
+
+    /// How many to list
+    #[clap(long, default_value_t = 100)]
+    n: usize,
+}
+
+/// JSON documents, indexed by declared JSON paths
+#[derive(Debug, clapArgs)]
+pub struct DocCommand {
+    #[clap(subcommand)]
+    verb: DocVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DocVerb {
+    Put(DocPutArgs),
+    Get(DocGetArgs),
+    Delete(DocGetArgs),
+    Find(DocFindArgs),
+    Index(DocIndexArgs),
+}
+
+impl DocVerb {
+    fn is_read(&self) -> bool {
+        matches!(self, DocVerb::Get(_) | DocVerb::Find(_))
+    }
+}
+
+/// Stores a document, replacing the one with the same id
+#[derive(Debug, clapArgs)]
+pub struct DocPutArgs {
+    /// The document as JSON, or @file to read it from a file
+    json: String,
+
+    /// The id of the document; a new one if not given
+    #[clap(long = "id")]
+    id: Option<u64>,
+}
+
+#[derive(Debug, clapArgs)]
+pub struct DocGetArgs {
+    /// The id of the document
+    id: u64,
+}
+
+/// Finds the documents with a value at an indexed path
+#[derive(Debug, clapArgs)]
+pub struct DocFindArgs {
+    /// The JSON path, e.g. $.owner or $.tags[*]
+    #[clap(long)]
+    path: String,
+
+    /// The value as JSON; taken as a string if it doesn't parse
+    #[clap(long)]
+    equals: String,
+
+    /// How many to return
+    #[clap(long, default_value_t = 100)]
+    n: usize,
+}
+
+/// Declares a JSON path to index, or lists them
+#[derive(Debug, clapArgs)]
+pub struct DocIndexArgs {
+    /// The JSON path, e.g. $.owner or $.tags[*]
+    path: Option<String>,
+}
+
 #[derive(Debug, clapArgs)]
 pub struct EdgeCommand {
     #[clap(subcommand)]
@@ -278,11 +770,49 @@ struct BytesVisitor(u32);
 impl db::Visitor<(Box<[u8]>, Box<[u8]>)> for BytesVisitor {
     fn visit(&mut self, kv: (Box<[u8]>, Box<[u8]>)) -> bool {
         println!("[{:?}] | {:?}", kv.0, kv.1);
-        self.0 = self.0 - 1;
+        self.0 -= 1;
         self.0 > 0
     }
 }
 
+impl NodeVerb {
+    fn is_read(&self) -> bool {
+        !matches!(self, NodeVerb::Put(_) | NodeVerb::Delete(_))
+    }
+}
+
+impl EdgeVerb {
+    fn is_read(&self) -> bool {
+        !matches!(
+            self,
+            EdgeVerb::Put(_) | EdgeVerb::Delete(_) | EdgeVerb::Associate(_)
+        )
+    }
+}
+
+// Reads go to a secondary or read-only instance if another process has the
+// db open.
+fn open_for(info: &DbArgs, read: bool) -> db::Database {
+    if !read {
+        return db::open_db(info, &All).unwrap();
+    }
+    let (database, mode) = db::open_for_read(info, &All).unwrap();
+    trace!("Opened {:?}", mode);
+    database
+}
+
+// Asks on the terminal; anything but y or yes is a no.
+fn confirm(prompt: &str) -> bool {
+    use std::io::Write;
+    print!("{} [y/N] ", prompt);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
 pub fn go(cmd: &Command) {
     trace!("Running command: {:?}", cmd);
 
@@ -291,6 +821,122 @@ pub fn go(cmd: &Command) {
             trace!("Called start: {:?}", args);
             let result = db::init(&cmd.db, &All);
             trace!("Result: {:?}", result);
+            if let Ok(database) = result {
+                match backfill::backfill(&database, None) {
+                    Ok(done) => trace!("Backfilled: {:?}", done),
+                    Err(e) => error!("Error: {:?}", e),
+                }
+            }
+        }
+        Verb::Load(args) => {
+            trace!("Called load: {:?}", args);
+            match load::load_file(&cmd.db, &args.file) {
+                Ok(stats) => info!("Loaded: {:?}", stats),
+                Err(e) => error!("Error: {:?}", e),
+            }
+        }
+        Verb::Fsck(args) => {
+            trace!("Called fsck: {:?}", args);
+            let database = db::open_db(&cmd.db, &All).unwrap();
+            match fsck::fsck(&database) {
+                Ok(report) => {
+                    for p in report.problems.iter() {
+                        println!("{}", p);
+                    }
+                    info!(
+                        "Checked entities={:?}, entries={:?}, problems={:?}",
+                        report.entities,
+                        report.entries,
+                        report.problems.len()
+                    );
+                    if !report.is_clean() {
+                        warn!("Run reindex to rebuild the indexes and counters");
+                    }
+                }
+                Err(e) => error!("Error: {:?}", e),
+            }
+        }
+        Verb::Reindex(args) => {
+            trace!("Called reindex: {:?}", args);
+            let database = db::open_db(&cmd.db, &All).unwrap();
+            match fsck::reindex_all(&database, args.index.as_deref()) {
+                Ok(()) => info!("Reindexed"),
+                Err(e) => error!("Error: {:?}", e),
+            }
+        }
+        Verb::Migrate(args) => {
+            trace!("Called migrate: {:?}", args);
+            match migrate::migrate(&cmd.db, args.dry_run, args.backup.as_deref()) {
+                Ok(report) => {
+                    for step in report.steps.iter() {
+                        println!(
+                            "{:?} -> {:?}: {} ({} changes)",
+                            step.from,
+                            step.from + 1,
+                            step.description,
+                            step.changes
+                        );
+                    }
+                    info!("Migrated: {:?}", report);
+                }
+                Err(e) => error!("Error: {:?}", e),
+            }
+        }
+        Verb::Backup(bcmd) => {
+            trace!("Called backup: {:?}", bcmd);
+            let dir = backup::backup_dir(&cmd.db, bcmd.dir.as_deref());
+            let result = match &bcmd.verb {
+                BackupVerb::Create => backup::create(&cmd.db, &dir).map(|b| {
+                    info!("Created: {:?}", b);
+                }),
+                BackupVerb::List => backup::list(&dir).map(|l| {
+                    for b in l.iter() {
+                        println!(
+                            "{:?}: timestamp={:?}, size={:?}, files={:?}",
+                            b.id, b.timestamp, b.size, b.num_files
+                        );
+                    }
+                }),
+                BackupVerb::Verify(args) => backup::verify(&dir, args.id).map(|_| {
+                    info!("Verified: {:?}", args.id);
+                }),
+                BackupVerb::Restore(args) => backup::restore(&cmd.db, &dir, args.id),
+                BackupVerb::Purge(args) => backup::purge(&dir, args.keep),
+            };
+            if let Err(e) = result {
+                error!("Error: {:?}", e);
+            }
+        }
+        Verb::Checkpoint(args) => {
+            trace!("Called checkpoint: {:?}", args);
+            if let Err(e) = backup::checkpoint(&cmd.db, &args.dir) {
+                error!("Error: {:?}", e);
+            }
+        }
+        Verb::Changes(args) => {
+            trace!("Called changes: {:?}", args);
+            // As a secondary, so the primary can keep writing while this
+            // follows
+            let database = db::open_secondary(&cmd.db, &All, &db::secondary_path(&cmd.db)).unwrap();
+            let since = match (args.since, &args.cursor) {
+                (Some(seq), _) => Ok(seq),
+                (None, Some(path)) => changes::read_cursor(path).map(|c| c.unwrap_or(0)),
+                (None, None) => Ok(0),
+            };
+            let result = since.and_then(|since| {
+                changes::stream(
+                    &database,
+                    OpenMode::Secondary,
+                    since,
+                    args.follow,
+                    args.cursor.as_deref(),
+                    &mut std::io::stdout(),
+                )
+            });
+            match result {
+                Ok(last) => trace!("Last sequence: {:?}", last),
+                Err(e) => error!("Error: {:?}", e),
+            }
         }
         Verb::Counter(args) => {
             trace!("Called count: {:?}", args);
@@ -299,13 +945,13 @@ pub fn go(cmd: &Command) {
             let mut counter = counters.get(args.key.as_str()).unwrap();
             let result = counter.get();
             counter.inc();
-            let mut txn = db::Transaction::default();
+            let mut txn = Batch::default();
             let commit = match counters.update(&mut txn, &counter) {
                 Ok(()) => true,
                 Err(_) => false,
             };
             if commit {
-                match database.write(txn) {
+                match database.commit(txn) {
                     Ok(()) => trace!("committed"),
                     Err(e) => error!("Error: {:?}", e),
                 }
@@ -319,20 +965,77 @@ pub fn go(cmd: &Command) {
                 IndexVerb::All => {
                     let result = db::indexes(&cmd.db);
                     trace!("Result: {:?}", result);
-                    println!("{:?}", result);
+                    match result {
+                        Ok(mut on_disk) => {
+                            // Indexes by registered type, then whatever else is on disk
+                            for t in registry::global().types() {
+                                let cfs = t.cf_names();
+                                for c in cfs.iter().filter(|c| !on_disk.contains(c)) {
+                                    warn!("Index {:?} of {} is missing; run init", c, t.name());
+                                }
+                                on_disk.retain(|c| !cfs.contains(c));
+                                println!("{}: {:?}", t.name(), cfs);
+                            }
+                            println!("other: {:?}", on_disk);
+                        }
+                        Err(e) => error!("Error: {:?}", e),
+                    }
                 }
                 IndexVerb::Dump(args) => {
                     trace!("Dump index content: {:?}", args);
-                    let result =
-                        db::list_index(&cmd.db, &args.index, &mut BytesVisitor(u32::max_value()));
+                    let result = db::list_index(&cmd.db, &args.index, &mut BytesVisitor(u32::MAX));
                     trace!("Result: {:?}", result);
                 }
+                IndexVerb::Backfill(args) => {
+                    trace!("Backfill: {:?}", args);
+                    let database = db::open_db(&cmd.db, &All).unwrap();
+                    let result = match args.status {
+                        true => backfill::pending(&database),
+                        false => backfill::backfill(&database, args.index.as_deref()),
+                    };
+                    match result {
+                        Ok(l) => {
+                            for p in l.iter() {
+                                println!("{:?}: count={:?}, done={:?}", p.cf_name, p.count, p.done);
+                            }
+                        }
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                IndexVerb::Gc(args) => {
+                    trace!("Gc: {:?}", args);
+                    let orphans = match gc::orphans(&cmd.db, &All) {
+                        Ok(l) => l,
+                        Err(e) => {
+                            error!("Error: {:?}", e);
+                            return;
+                        }
+                    };
+                    if orphans.is_empty() {
+                        info!("No orphaned column families");
+                        return;
+                    }
+                    for o in orphans.iter() {
+                        println!(
+                            "{:?}: sst_bytes={:?}, keys~={:?}",
+                            o.cf_name, o.sst_bytes, o.keys
+                        );
+                    }
+                    if !args.yes && !confirm(&format!("Drop {} column families?", orphans.len())) {
+                        return;
+                    }
+                    let names: Vec<String> = orphans.into_iter().map(|o| o.cf_name).collect();
+                    match gc::drop_orphans(&cmd.db, &All, &names) {
+                        Ok(dropped) => info!("Dropped: {:?}", dropped),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
             }
         }
 
         Verb::Node(ncmd) => {
             trace!("Called node: {:?}", ncmd);
-            let database = db::open_db(&cmd.db, &All).unwrap();
+            let database = open_for(&cmd.db, ncmd.verb.is_read());
 
             match &ncmd.verb {
                 NodeVerb::Hash(args) => {
@@ -358,10 +1061,7 @@ pub fn go(cmd: &Command) {
                     }
                 }
                 NodeVerb::Put(args) => {
-                    let id: u64 = match args.id {
-                        Some(v) => v,
-                        None => 0,
-                    };
+                    let id: u64 = args.id.unwrap_or_default();
                     let mut node = Node {
                         id,
                         type_name: match &args.type_name {
@@ -471,21 +1171,456 @@ pub fn go(cmd: &Command) {
                         Err(e) => error!("Error: {:?}", e),
                     }
                 }
+                NodeVerb::Where(args) => {
+                    trace!("Where: {:?}", args);
+                    let q = match attribute::parse_where(&args.conditions) {
+                        Ok(c) => query::node_query(c, args.n),
+                        Err(e) => {
+                            error!("Error: {}", e);
+                            return;
+                        }
+                    };
+                    let result = query::explain(&database, &q).and_then(|plan| {
+                        if args.explain {
+                            print!("{}", plan);
+                            return Ok(());
+                        }
+                        let mut p = NodePrinter(args.n);
+                        for row in query::execute(&database, &q, &plan)?.rows {
+                            if let Some(query::Cell::Node(node)) = row.into_iter().next() {
+                                p.visit(node);
+                            }
+                        }
+                        Ok(())
+                    });
+                    if let Err(e) = result {
+                        error!("Error: {:?}", e);
+                    }
+                }
+            }
+        }
+        Verb::Query(args) => {
+            trace!("Called query: {:?}", args);
+            let database = open_for(&cmd.db, true);
+            if args.explain {
+                match query::parse(&args.text)
+                    .map_err(|e| e.into())
+                    .and_then(|q| query::explain(&database, &q))
+                {
+                    Ok(plan) => print!("{}", plan),
+                    Err(e) => error!("Error: {}", e),
+                }
+                return;
+            }
+            match query::run(&database, &args.text) {
+                Ok(results) => {
+                    println!("{}", results.columns.join("\t"));
+                    for row in results.rows.iter() {
+                        let cells: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+                        println!("{}", cells.join("\t"));
+                    }
+                    info!("{} rows", results.rows.len());
+                }
+                Err(e) => error!("Error: {}", e),
+            }
+        }
+        Verb::Script(args) => {
+            trace!("Called script: {:?}", args);
+            let text = match std::fs::read_to_string(&args.file) {
+                Ok(text) => text,
+                Err(e) => {
+                    error!("Error reading {}: {:?}", args.file, e);
+                    return;
+                }
+            };
+            let database: Rc<dyn Store> = Rc::new(open_for(&cmd.db, args.dry_run));
+            match script::run(database.clone(), &text, args.max_steps) {
+                Ok(batch) if args.dry_run => {
+                    for op in batch.ops() {
+                        println!("{}", op);
+                    }
+                    info!("Dry run, {} writes not committed", batch.len());
+                }
+                Ok(batch) => {
+                    let n = batch.len();
+                    match database.commit(batch) {
+                        Ok(()) => info!("Committed {} writes", n),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                Err(e) => error!("Error: {}", e),
+            }
+        }
+        Verb::ServeGraphql(args) => {
+            trace!("Called serve-graphql: {:?}", args);
+            // A secondary never takes the lock, so writers can keep the
+            // primary open; the server catches it up before each request.
+            let database =
+                Arc::new(db::open_secondary(&cmd.db, &All, &db::secondary_path(&cmd.db)).unwrap());
+            if args.print_schema {
+                print!("{}", graphql::schema(database).sdl());
+                return;
+            }
+            if let Err(e) = server::serve_graphql(&args.addr, database) {
+                error!("Error: {:?}", e);
+            }
+        }
+        Verb::Export(args) => {
+            trace!("Called export: {:?}", args);
+            let subgraph = diagram::Subgraph {
+                root: args.root.clone(),
+                depth: args.depth,
+                edge_types: args.edge_types.clone(),
+            };
+            let drawing = matches!(args.format, ExportFormat::Dot | ExportFormat::Mermaid);
+            if !drawing && subgraph != Default::default() {
+                error!("--root, --depth and --edge-type are for dot and mermaid");
+                return;
+            }
+            let vocabulary = match &args.prefixes {
+                Some(path) => rdf::Vocabulary::load(&args.base, path),
+                None => Ok(rdf::Vocabulary::new(&args.base, Default::default())),
+            };
+            let vocabulary = match vocabulary {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Error: {}", e);
+                    return;
+                }
+            };
+            let database = open_for(&cmd.db, true);
+            if args.format == ExportFormat::Arrow {
+                let dir = std::path::Path::new(args.out.as_deref().unwrap_or("."));
+                let result = columnar::export_arrow_dir(&database, dir, columnar::BATCH_ROWS);
+                match result {
+                    Ok(counts) => info!("Exported: {:?}", counts),
+                    Err(e) => error!("Error: {:?}", e),
+                }
+                return;
+            }
+            let mut out: Box<dyn std::io::Write> = match &args.out {
+                Some(path) => match std::fs::File::create(path) {
+                    Ok(f) => Box::new(std::io::BufWriter::new(f)),
+                    Err(e) => {
+                        error!("Error creating {}: {:?}", path, e);
+                        return;
+                    }
+                },
+                None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+            };
+            let result = match args.format {
+                ExportFormat::Jsonl => export::export_jsonl(&database, &mut out),
+                ExportFormat::Dot => {
+                    diagram::export_diagram(&database, &subgraph, diagram::Style::Dot, &mut out)
+                }
+                ExportFormat::Mermaid => {
+                    diagram::export_diagram(&database, &subgraph, diagram::Style::Mermaid, &mut out)
+                }
+                ExportFormat::Graphml => {
+                    interchange::export_xml(&database, XmlFormat::Graphml, &mut out)
+                }
+                ExportFormat::Gexf => interchange::export_xml(&database, XmlFormat::Gexf, &mut out),
+                ExportFormat::Ntriples => {
+                    rdf::export_rdf(&database, &vocabulary, RdfFormat::NTriples, &mut out)
+                }
+                ExportFormat::Turtle => {
+                    rdf::export_rdf(&database, &vocabulary, RdfFormat::Turtle, &mut out)
+                }
+                ExportFormat::Arrow => unreachable!("arrow is written above"),
+            };
+            let result = result.and_then(|counts| {
+                out.flush()?;
+                Ok(counts)
+            });
+            match result {
+                Ok(counts) => info!("Exported: {:?}", counts),
+                Err(e) => error!("Error: {:?}", e),
+            }
+        }
+        Verb::Import(args) => {
+            trace!("Called import: {:?}", args);
+            let rdf = matches!(args.format, ImportFormat::Ntriples | ImportFormat::Turtle);
+            if rdf && args.preserve_ids {
+                error!("--preserve-ids is not for RDF");
+                return;
+            }
+            let vocabulary = match &args.prefixes {
+                Some(path) => rdf::Vocabulary::load(&args.base, path),
+                None => Ok(rdf::Vocabulary::new(&args.base, Default::default())),
+            };
+            let vocabulary = match vocabulary {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Error: {}", e);
+                    return;
+                }
+            };
+            // Written over an overlay and committed once, so a bad file
+            // leaves the db as it was.  RDF files can be too big for that
+            // and commit their own batches as they go.
+            let database: Rc<dyn Store> = Rc::new(open_for(&cmd.db, false));
+            let overlay = Overlay::new(database.clone());
+            let ids = match args.preserve_ids {
+                true => export::Ids::Preserve,
+                false => export::Ids::Remap,
+            };
+            let result = std::fs::File::open(&args.file)
+                .map_err(|e| e.into())
+                .and_then(|f| {
+                    let mut input = std::io::BufReader::new(f);
+                    match args.format {
+                        ImportFormat::Jsonl => export::import_jsonl(&overlay, &mut input, ids),
+                        ImportFormat::Graphml => {
+                            interchange::import_xml(&overlay, XmlFormat::Graphml, &mut input, ids)
+                        }
+                        ImportFormat::Gexf => {
+                            interchange::import_xml(&overlay, XmlFormat::Gexf, &mut input, ids)
+                        }
+                        ImportFormat::Ntriples | ImportFormat::Turtle => rdf::import_rdf(
+                            database.clone(),
+                            &vocabulary,
+                            &mut input,
+                            rdf::IMPORT_BATCH,
+                        ),
+                    }
+                });
+            let counts = match result {
+                Ok(counts) => counts,
+                Err(e) => {
+                    error!("Error: {}", e);
+                    return;
+                }
+            };
+            match database.commit(overlay.batch()) {
+                Ok(()) => info!("Imported: {:?}", counts),
+                Err(e) => error!("Error: {:?}", e),
+            }
+        }
+        Verb::ImportCsv(args) => {
+            trace!("Called import-csv: {:?}", args);
+            let columns = Columns {
+                name: args.name_column.clone(),
+                type_name: args.type_column.clone(),
+                key: args.key_column.clone(),
+                attrs: args.attrs.clone(),
+                head: args.head_column.clone(),
+                tail: args.tail_column.clone(),
+                edge_name: args.edge_name_column.clone(),
+                edge_type: args.edge_type_column.clone(),
+                edge_attrs: args.edge_attrs.clone(),
+            };
+            // Written over an overlay, so nothing is committed on a dry run
+            // or a bad file
+            let database: Rc<dyn Store> = Rc::new(open_for(&cmd.db, args.dry_run));
+            let overlay = Overlay::new(database.clone());
+            let mut importer = CsvImporter::new(&overlay, columns, args.dedup);
+            let files = [(&args.nodes, true), (&args.edges, false)];
+            for (file, nodes) in files {
+                let Some(file) = file else { continue };
+                let result = std::fs::File::open(file)
+                    .map_err(|e| e.into())
+                    .and_then(|mut f| match nodes {
+                        true => importer.nodes(file, &mut f),
+                        false => importer.edges(file, &mut f),
+                    });
+                if let Err(e) = result {
+                    error!("Error: {}", e);
+                    return;
+                }
+            }
+            let report = importer.report();
+            for e in report.errors.iter() {
+                println!("{}", e);
+            }
+            info!(
+                "Nodes: {} added, {} reused; edges: {}; attributes: {}; bad rows: {}",
+                report.nodes,
+                report.reused,
+                report.edges,
+                report.attributes,
+                report.errors.len()
+            );
+            if args.dry_run {
+                info!("Dry run, nothing committed");
+                return;
+            }
+            if let Err(e) = database.commit(overlay.batch()) {
+                error!("Error: {:?}", e);
+            }
+        }
+        Verb::Attr(acmd) => {
+            trace!("Called attr: {:?}", acmd);
+            let database = open_for(&cmd.db, acmd.verb.is_read());
+            match &acmd.verb {
+                AttrVerb::Set(args) => {
+                    let value = match &args.content_type {
+                        Some(t) => match Value::parse(t, &args.value) {
+                            Ok(v) => v,
+                            Err(e) => {
+                                error!("Error: {}", e);
+                                return;
+                            }
+                        },
+                        None => Value::infer(&args.value),
+                    };
+                    let found = Node::operations(&database)
+                        .get(Node::id_from(args.parent_id))
+                        .map(|n| n.is_some())
+                        .and_then(|found| match found {
+                            true => Ok(true),
+                            false => Edge::operations(&database)
+                                .get(Edge::id_from(args.parent_id))
+                                .map(|e| e.is_some()),
+                        });
+                    match found {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            error!("No node or edge with id {}", args.parent_id);
+                            return;
+                        }
+                        Err(e) => {
+                            error!("Error: {:?}", e);
+                            return;
+                        }
+                    }
+                    let mut attr = Attribute::typed(args.parent_id, &args.name, &value);
+                    let result = Attribute::operations(&database).put(&mut attr);
+                    info!("Result: {:?}", result.map(|_| value));
+                }
+                AttrVerb::Get(args) => {
+                    let ops = Attribute::operations(&database);
+                    match ops.get(Attribute::id_from(AttrKey {
+                        parent_id: args.parent_id,
+                        name: args.name.clone(),
+                    })) {
+                        Ok(Some(attr)) => {
+                            let mut p = AttributePrinter(1);
+                            p.visit(attr);
+                        }
+                        Ok(None) => info!("not found"),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                AttrVerb::Delete(args) => {
+                    let mut ops = Attribute::operations(&database);
+                    let id = Attribute::id_from(AttrKey {
+                        parent_id: args.parent_id,
+                        name: args.name.clone(),
+                    });
+                    match ops.get(id) {
+                        Ok(Some(attr)) => match ops.delete(&attr) {
+                            Ok(deleted) => info!("Deleted {:?}: {:?}", args.name, deleted),
+                            Err(e) => error!("Error: {:?}", e),
+                        },
+                        Ok(None) => info!("not found"),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                AttrVerb::List(args) => {
+                    if let Err(e) = attribute::visit_parent(
+                        &database,
+                        args.parent_id,
+                        Box::new(AttributePrinter(args.n)),
+                    ) {
+                        error!("Error: {:?}", e);
+                    }
+                }
+            }
+        }
+        Verb::Doc(dcmd) => {
+            trace!("Called doc: {:?}", dcmd);
+            let database = open_for(&cmd.db, dcmd.verb.is_read());
+            match &dcmd.verb {
+                DocVerb::Put(args) => {
+                    let json = match args.json.strip_prefix('@') {
+                        Some(file) => match std::fs::read_to_string(file) {
+                            Ok(json) => json,
+                            Err(e) => {
+                                error!("Error reading {:?}: {:?}", file, e);
+                                return;
+                            }
+                        },
+                        None => args.json.clone(),
+                    };
+                    let body = match serde_json::from_str(&json) {
+                        Ok(body) => body,
+                        Err(e) => {
+                            error!("Error: {:?}", e);
+                            return;
+                        }
+                    };
+                    let mut doc = Document {
+                        id: args.id.unwrap_or(0),
+                        paths: vec![],
+                        body,
+                    };
+                    let mut ops = Document::operations(&database);
+                    match ops.put(&mut doc) {
+                        Ok(_) => println!("{}", doc.id),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                DocVerb::Get(args) => {
+                    let ops = Document::operations(&database);
+                    match ops.get(Document::id_from(args.id)) {
+                        Ok(Some(doc)) => {
+                            let mut p = DocumentPrinter(1);
+                            p.visit(doc);
+                        }
+                        Ok(None) => info!("not found"),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                DocVerb::Delete(args) => {
+                    let mut ops = Document::operations(&database);
+                    match ops.get(Document::id_from(args.id)) {
+                        Ok(Some(doc)) => match ops.delete(&doc) {
+                            Ok(deleted) => info!("Deleted {}: {:?}", args.id, deleted),
+                            Err(e) => error!("Error: {:?}", e),
+                        },
+                        Ok(None) => info!("not found"),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                DocVerb::Find(args) => {
+                    let value = serde_json::from_str(&args.equals)
+                        .unwrap_or_else(|_| serde_json::Value::String(args.equals.clone()));
+                    match document::find(
+                        &database,
+                        &args.path,
+                        &value,
+                        Box::new(DocumentPrinter(args.n)),
+                    ) {
+                        Ok(()) => trace!("Done."),
+                        Err(e) => error!("Error: {:?}", e),
+                    }
+                }
+                DocVerb::Index(args) => match &args.path {
+                    Some(path) => match document::declare_path(&database, path) {
+                        Ok(path) => info!("Indexed {}", path),
+                        Err(e) => error!("Error: {:?}", e),
+                    },
+                    None => match document::declared_paths(&database) {
+                        Ok(paths) => {
+                            for p in paths.iter() {
+                                println!("{}", p);
+                            }
+                        }
+                        Err(e) => error!("Error: {:?}", e),
+                    },
+                },
             }
         }
         Verb::Edge(ncmd) => {
             trace!("Called edge: {:?}", cmd);
-            let database = db::open_db(&cmd.db, &All).unwrap();
+            let database = open_for(&cmd.db, ncmd.verb.is_read());
             match &ncmd.verb {
                 EdgeVerb::Associate(args) => {
                     // Look up the head and tail by name
                     let node_ops = Node::operations(&database);
-                    match node_ops.first(&node::ByName.cf_name().to_string(), args.head.as_bytes())
-                    {
+                    match node_ops.first(node::ByName.cf_name(), args.head.as_bytes()) {
                         Ok(Some(head)) => {
-                            match node_ops
-                                .first(&node::ByName.cf_name().to_string(), args.tail.as_bytes())
-                            {
+                            match node_ops.first(node::ByName.cf_name(), args.tail.as_bytes()) {
                                 Ok(Some(tail)) => {
                                     trace!("{:?} --{:?}-> {:?}", head, args.name, tail);
                                     let mut edge = Edge {
@@ -513,10 +1648,7 @@ pub fn go(cmd: &Command) {
                     }
                 }
                 EdgeVerb::Put(args) => {
-                    let id: u64 = match args.id {
-                        Some(v) => v,
-                        None => 0,
-                    };
+                    let id: u64 = args.id.unwrap_or_default();
                     let mut edge = Edge {
                         id,
                         head: args.head,
@@ -567,15 +1699,14 @@ pub fn go(cmd: &Command) {
                 EdgeVerb::From(args) => {
                     trace!("Edges from {:?}", args);
                     let mut buffer: Vec<Edge> = vec![];
-                    let visitor = EdgeCollector::new(&mut buffer, usize::max_value());
+                    let visitor = EdgeCollector::new(&mut buffer, usize::MAX);
                     // Look up the head and tail by name
                     let node_ops = Node::operations(&database);
-                    match node_ops.first(&node::ByName.cf_name().to_string(), args.name.as_bytes())
-                    {
+                    match node_ops.first(node::ByName.cf_name(), args.name.as_bytes()) {
                         Ok(Some(head)) => {
                             let edge_ops = Edge::operations(&database);
                             match edge_ops.scan(
-                                &edge::ByHeadTail.cf_name().to_string(),
+                                edge::ByHeadTail.cf_name(),
                                 head.id.to_le_bytes().to_vec(),
                                 Box::new(visitor),
                             ) {
@@ -632,16 +1763,15 @@ pub fn go(cmd: &Command) {
                 EdgeVerb::To(args) => {
                     trace!("Edges to {:?}", args);
                     let mut buffer: Vec<Edge> = vec![];
-                    let visitor = EdgeCollector::new(&mut buffer, usize::max_value());
+                    let visitor = EdgeCollector::new(&mut buffer, usize::MAX);
 
                     // Look up the head and tail by name
                     let node_ops = Node::operations(&database);
-                    match node_ops.first(&node::ByName.cf_name().to_string(), args.name.as_bytes())
-                    {
+                    match node_ops.first(node::ByName.cf_name(), args.name.as_bytes()) {
                         Ok(Some(tail)) => {
                             let edge_ops = Edge::operations(&database);
                             match edge_ops.scan(
-                                &edge::ByTailHead.cf_name().to_string(),
+                                edge::ByTailHead.cf_name(),
                                 tail.id.to_le_bytes().to_vec(),
                                 Box::new(visitor),
                             ) {
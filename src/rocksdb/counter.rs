@@ -4,6 +4,7 @@ use tracing::{debug, error, info, trace, warn};
 use crate::rocksdb::db;
 use crate::rocksdb::db::{Entity, HasKey, KeyCodec};
 use crate::rocksdb::error::ErrNoCounters;
+use crate::rocksdb::store::{Batch, Store};
 
 use std::convert::TryInto;
 use std::error::Error;
@@ -23,7 +24,7 @@ impl Counter {
     }
 
     pub fn inc(&mut self) {
-        self.value = self.value + 1;
+        self.value += 1;
     }
 
     pub fn set(&mut self, v: u64) {
@@ -58,19 +59,19 @@ impl db::Entity for Counter {
         let be = bytes.try_into().unwrap();
         let val = u64::from_le_bytes(be);
         Ok(Counter {
-            key: String::from_utf8(key.to_vec()).unwrap().into(),
+            key: String::from_utf8(key.to_vec()).unwrap(),
             value: val,
         })
     }
 }
 
 pub(crate) struct Counters<'a> {
-    db: &'a db::Database,
+    db: &'a dyn Store,
     column_family: String,
 }
 
 impl Counters<'_> {
-    pub fn new<'a>(db: &'a db::Database, cf: &'a str) -> Counters<'a> {
+    pub fn new<'a>(db: &'a dyn Store, cf: &'a str) -> Counters<'a> {
         Counters {
             db,
             column_family: cf.to_string(),
@@ -79,27 +80,26 @@ impl Counters<'_> {
 
     // Creates a new counter by key if not found.
     pub fn get(&self, key: &str) -> Result<Counter, Box<dyn Error>> {
-        let cf = self.db.cf_handle(self.column_family.as_str()).unwrap();
-        match self.db.get_cf(cf, key) {
-            Ok(Some(bytes)) => Counter::from_bytes(key.as_bytes(), &bytes),
-            Ok(None) => Ok(Counter::new(key)),
-            Err(e) => Err(Box::new(e)),
+        match self
+            .db
+            .get_value(self.column_family.as_str(), key.as_bytes())?
+        {
+            Some(bytes) => Counter::from_bytes(key.as_bytes(), &bytes),
+            None => Ok(Counter::new(key)),
         }
     }
 
     // Update the counter in db
-    pub fn update(
-        &mut self,
-        txn: &mut db::Transaction,
-        counter: &Counter,
-    ) -> Result<(), Box<dyn Error>> {
-        match self.db.cf_handle(self.column_family.as_str()) {
-            Some(cf) => {
-                txn.put_cf(cf, counter.id().as_bytes(), counter.as_bytes());
-                Ok(())
-            }
-            None => Err(Box::new(ErrNoCounters::new(self.column_family.to_string()))),
+    pub fn update(&mut self, txn: &mut Batch, counter: &Counter) -> Result<(), Box<dyn Error>> {
+        if !self.db.has_cf(self.column_family.as_str()) {
+            return Err(Box::new(ErrNoCounters::new(self.column_family.to_string())));
         }
+        txn.put(
+            self.column_family.as_str(),
+            counter.id().as_bytes(),
+            counter.as_bytes(),
+        );
+        Ok(())
     }
 }
 
@@ -0,0 +1,307 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::OperationsBuilder;
+use crate::rocksdb::error::ErrBadImport;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::store::Store;
+use crate::rocksdb::value::Value;
+
+use csv::{ReaderBuilder, StringRecord};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+
+// Which columns hold what.  The type columns are optional; other columns
+// are ignored unless listed as attributes, whose values are inferred like
+// attr set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns {
+    pub name: String,
+    pub type_name: String,
+    // A key for the edges to refer to nodes by, instead of the name
+    pub key: Option<String>,
+    pub attrs: Vec<String>,
+    pub head: String,
+    pub tail: String,
+    pub edge_name: String,
+    pub edge_type: String,
+    pub edge_attrs: Vec<String>,
+}
+
+impl Default for Columns {
+    fn default() -> Columns {
+        Columns {
+            name: "name".into(),
+            type_name: "type".into(),
+            key: None,
+            attrs: vec![],
+            head: "head".into(),
+            tail: "tail".into(),
+            edge_name: "name".into(),
+            edge_type: "type".into(),
+            edge_attrs: vec![],
+        }
+    }
+}
+
+// A row that was skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub file: String,
+    pub line: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.reason)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CsvReport {
+    pub nodes: u64,
+    // Rows matched to a node with the same name, when deduping
+    pub reused: u64,
+    pub edges: u64,
+    pub attributes: u64,
+    pub errors: Vec<RowError>,
+}
+
+// Column positions in a file, from its header.
+struct Positions {
+    name: usize,
+    type_name: Option<usize>,
+    key: Option<usize>,
+    attrs: Vec<(String, usize)>,
+}
+
+// Imports a nodes file, then an edges file.  A bad row is recorded in the
+// report and skipped; only a bad header or an unreadable file fails the
+// import.
+pub struct CsvImporter<'a> {
+    db: &'a dyn Store,
+    columns: Columns,
+    // Reuse the node with the same name instead of adding another
+    dedup: bool,
+    // Nodes of this import
+    by_name: HashMap<String, u64>,
+    by_key: HashMap<String, u64>,
+    report: CsvReport,
+}
+
+impl<'a> CsvImporter<'a> {
+    pub fn new(db: &'a dyn Store, columns: Columns, dedup: bool) -> CsvImporter<'a> {
+        CsvImporter {
+            db,
+            columns,
+            dedup,
+            by_name: HashMap::new(),
+            by_key: HashMap::new(),
+            report: CsvReport::default(),
+        }
+    }
+
+    pub fn nodes(&mut self, file: &str, input: &mut dyn Read) -> Result<(), Box<dyn Error>> {
+        let c = &self.columns;
+        let mut reader = ReaderBuilder::new().from_reader(input);
+        let headers = reader.headers()?.clone();
+        let p = positions(
+            file,
+            &headers,
+            &c.name,
+            &c.type_name,
+            c.key.as_deref(),
+            &c.attrs,
+        )?;
+        for record in reader.records() {
+            let (line, result) = match record {
+                Ok(row) => (
+                    row.position().map_or(0, |p| p.line()),
+                    self.node_row(&p, &row),
+                ),
+                Err(e) => (e.position().map_or(0, |p| p.line()), Err(e.into())),
+            };
+            self.record(file, line, result);
+        }
+        Ok(())
+    }
+
+    pub fn edges(&mut self, file: &str, input: &mut dyn Read) -> Result<(), Box<dyn Error>> {
+        let c = &self.columns;
+        let mut reader = ReaderBuilder::new().from_reader(input);
+        let headers = reader.headers()?.clone();
+        let head = column(file, &headers, &c.head)?;
+        let tail = column(file, &headers, &c.tail)?;
+        let p = positions(
+            file,
+            &headers,
+            &c.edge_name,
+            &c.edge_type,
+            None,
+            &c.edge_attrs,
+        )?;
+        for record in reader.records() {
+            let (line, result) = match record {
+                Ok(row) => (
+                    row.position().map_or(0, |p| p.line()),
+                    self.edge_row(&p, head, tail, &row),
+                ),
+                Err(e) => (e.position().map_or(0, |p| p.line()), Err(e.into())),
+            };
+            self.record(file, line, result);
+        }
+        Ok(())
+    }
+
+    pub fn report(self) -> CsvReport {
+        self.report
+    }
+
+    fn record(&mut self, file: &str, line: u64, result: Result<(), Box<dyn Error>>) {
+        if let Err(e) = result {
+            trace!("{}:{}: {}", file, line, e);
+            self.report.errors.push(RowError {
+                file: file.to_string(),
+                line,
+                reason: e.to_string(),
+            });
+        }
+    }
+
+    fn node_row(&mut self, p: &Positions, row: &StringRecord) -> Result<(), Box<dyn Error>> {
+        let name = cell(row, Some(p.name)).ok_or("no name")?;
+        let key = match p.key {
+            None => None,
+            Some(i) => match cell(row, Some(i)) {
+                None => return Err("no key".into()),
+                Some(k) if self.by_key.contains_key(k) => {
+                    return Err(format!("key {:?} is not unique", k).into())
+                }
+                Some(k) => Some(k),
+            },
+        };
+        let found = match self.dedup {
+            true => self.node_named(name)?,
+            false => None,
+        };
+        let id = match found {
+            Some(id) => {
+                self.report.reused += 1;
+                id
+            }
+            None => {
+                let mut node = Node {
+                    name: name.to_string(),
+                    type_name: cell(row, p.type_name).unwrap_or("entity").to_string(),
+                    ..Default::default()
+                };
+                Node::operations(self.db).put(&mut node)?;
+                self.report.nodes += 1;
+                node.id
+            }
+        };
+        self.by_name.insert(name.to_string(), id);
+        if let Some(k) = key {
+            self.by_key.insert(k.to_string(), id);
+        }
+        self.attributes(id, p, row)
+    }
+
+    fn edge_row(
+        &mut self,
+        p: &Positions,
+        head: usize,
+        tail: usize,
+        row: &StringRecord,
+    ) -> Result<(), Box<dyn Error>> {
+        let head = self.node_ref(cell(row, Some(head)).ok_or("no head")?)?;
+        let tail = self.node_ref(cell(row, Some(tail)).ok_or("no tail")?)?;
+        let name = cell(row, Some(p.name)).ok_or("no name")?;
+        let mut edge = Edge {
+            name: name.to_string(),
+            type_name: cell(row, p.type_name).unwrap_or(name).to_string(),
+            head,
+            tail,
+            ..Default::default()
+        };
+        Edge::operations(self.db).put(&mut edge)?;
+        self.report.edges += 1;
+        self.attributes(edge.id, p, row)
+    }
+
+    fn attributes(
+        &mut self,
+        parent_id: u64,
+        p: &Positions,
+        row: &StringRecord,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut ops = Attribute::operations(self.db);
+        for (name, i) in p.attrs.iter() {
+            if let Some(v) = cell(row, Some(*i)) {
+                ops.put(&mut Attribute::typed(parent_id, name, &Value::infer(v)))?;
+                self.report.attributes += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // This import's node with the name, else the db's.
+    fn node_named(&self, name: &str) -> Result<Option<u64>, Box<dyn Error>> {
+        if let Some(id) = self.by_name.get(name) {
+            return Ok(Some(*id));
+        }
+        let found = Node::operations(self.db).first(node::ByName.cf_name(), name.as_bytes())?;
+        Ok(found.map(|n| n.id))
+    }
+
+    // By key if nodes have keys, else by name.
+    fn node_ref(&self, s: &str) -> Result<u64, Box<dyn Error>> {
+        let found = match self.columns.key {
+            Some(_) => self.by_key.get(s).copied(),
+            None => self.node_named(s)?,
+        };
+        found.ok_or_else(|| format!("no node {:?}", s).into())
+    }
+}
+
+// The trimmed value, if not empty.
+fn cell(row: &StringRecord, i: Option<usize>) -> Option<&str> {
+    i.and_then(|i| row.get(i))
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+}
+
+fn column(file: &str, headers: &StringRecord, name: &str) -> Result<usize, Box<dyn Error>> {
+    headers
+        .iter()
+        .position(|h| h.trim() == name)
+        .ok_or_else(|| {
+            let reason = format!("{} has no column {:?}", file, name);
+            Box::new(ErrBadImport::new(1, &reason)) as Box<dyn Error>
+        })
+}
+
+fn positions(
+    file: &str,
+    headers: &StringRecord,
+    name: &str,
+    type_name: &str,
+    key: Option<&str>,
+    attrs: &[String],
+) -> Result<Positions, Box<dyn Error>> {
+    Ok(Positions {
+        name: column(file, headers, name)?,
+        type_name: column(file, headers, type_name).ok(),
+        key: key.map(|k| column(file, headers, k)).transpose()?,
+        attrs: attrs
+            .iter()
+            .map(|a| column(file, headers, a).map(|i| (a.clone(), i)))
+            .collect::<Result<_, _>>()?,
+    })
+}
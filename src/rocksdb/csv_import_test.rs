@@ -0,0 +1,195 @@
+use std::error::Error;
+use std::rc::Rc;
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::csv_import::{Columns, CsvImporter, CsvReport};
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::store::{MemStore, Overlay, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+static NODES: &str = "\
+id,label,kind,port
+a1,api,service,8080
+d1,db,database,
+c1,cache,,6379
+a2,api,team,
+";
+
+static EDGES: &str = "\
+from,to,rel,weight
+a1,d1,depends-on,0.5
+a1,c1,depends-on,
+c1,d1,reads,1
+";
+
+fn columns() -> Columns {
+    Columns {
+        name: "label".into(),
+        type_name: "kind".into(),
+        key: Some("id".into()),
+        attrs: vec!["port".into()],
+        head: "from".into(),
+        tail: "to".into(),
+        edge_name: "rel".into(),
+        edge_attrs: vec!["weight".into()],
+        ..Default::default()
+    }
+}
+
+fn import(
+    db: &dyn Store,
+    columns: Columns,
+    dedup: bool,
+    nodes: &str,
+    edges: &str,
+) -> Result<CsvReport, Box<dyn Error>> {
+    let mut importer = CsvImporter::new(db, columns, dedup);
+    importer.nodes("nodes.csv", &mut nodes.as_bytes())?;
+    importer.edges("edges.csv", &mut edges.as_bytes())?;
+    Ok(importer.report())
+}
+
+fn attribute(db: &dyn Store, parent_id: u64, name: &str) -> Option<Value> {
+    Attribute::operations(db)
+        .get(Attribute::id_from(AttrKey {
+            parent_id,
+            name: name.into(),
+        }))
+        .unwrap()
+        .and_then(|a| a.value())
+}
+
+#[test]
+fn test_import_by_key() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let report = import(&db, columns(), false, NODES, EDGES)?;
+    assert_eq!(
+        CsvReport {
+            nodes: 4,
+            reused: 0,
+            edges: 3,
+            attributes: 4,
+            errors: vec![],
+        },
+        report
+    );
+    let cache = Node::operations(&db).get(Node::id_from(3))?.unwrap();
+    assert_eq!(
+        ("cache", "entity"),
+        (cache.name.as_str(), cache.type_name.as_str())
+    );
+    assert_eq!(Some(Value::Int(6379)), attribute(&db, 3, "port"));
+
+    // Keys tell the two apis apart
+    let edge = Edge::operations(&db).get(Edge::id_from(5))?.unwrap();
+    assert_eq!(
+        (1, 2, "depends-on"),
+        (edge.head, edge.tail, edge.type_name.as_str())
+    );
+    assert_eq!(Some(Value::Float(0.5)), attribute(&db, 5, "weight"));
+    assert_eq!(None, attribute(&db, 6, "weight"));
+    Ok(())
+}
+
+#[test]
+fn test_dedup_by_name() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let mut n = Node {
+        name: "db".into(),
+        type_name: "database".into(),
+        ..Default::default()
+    };
+    Node::operations(&db).put(&mut n)?;
+
+    let c = Columns {
+        key: None,
+        ..columns()
+    };
+    let edges = "from,to,rel,weight\napi,db,depends-on,\n";
+    let report = import(&db, c.clone(), true, NODES, edges)?;
+    // db is the one already there, and the second api the first
+    assert_eq!((2, 2, 1), (report.nodes, report.reused, report.edges));
+    let edge = Edge::operations(&db).get(Edge::id_from(4))?.unwrap();
+    assert_eq!((2, 1), (edge.head, edge.tail));
+
+    // Without dedup every row is a node; edges take the last of a name
+    let db = MemStore::new(&All);
+    let report = import(&db, c, false, NODES, edges)?;
+    assert_eq!((4, 0), (report.nodes, report.reused));
+    let edge = Edge::operations(&db).get(Edge::id_from(5))?.unwrap();
+    assert_eq!((4, 2), (edge.head, edge.tail));
+    Ok(())
+}
+
+#[test]
+fn test_row_errors() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let nodes = "id,label,kind,port\na1,api,,\n,nokey,,\na1,again,,\nb1,,,\nc1,\"cut\n";
+    let edges = "from,to,rel,weight\na1,x9,calls,\na1,a1,,\na1,a1,self,\n";
+    let report = import(&db, columns(), false, nodes, edges)?;
+    assert_eq!((1, 1), (report.nodes, report.edges));
+    let mut errors: Vec<String> = report.errors.iter().map(|e| e.to_string()).collect();
+    // The unterminated quote is a short record
+    assert!(
+        errors[3].starts_with("nodes.csv:6: CSV error"),
+        "{:?}",
+        errors
+    );
+    errors.remove(3);
+    assert_eq!(
+        vec![
+            "nodes.csv:3: no key",
+            "nodes.csv:4: key \"a1\" is not unique",
+            "nodes.csv:5: no name",
+            "edges.csv:2: no node \"x9\"",
+            "edges.csv:3: no name",
+        ],
+        errors
+    );
+
+    // A missing column fails the file
+    let mut importer = CsvImporter::new(&db, Columns::default(), false);
+    let err = importer
+        .nodes("x.csv", &mut "label\napi\n".as_bytes())
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("x.csv has no column \"name\""),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let info = TestDbInfo::new();
+    let database: Rc<dyn Store> = Rc::new(db::init(&info, &All)?);
+    let overlay = Overlay::new(database.clone());
+    let report = import(&overlay, columns(), false, NODES, EDGES)?;
+    assert_eq!(4, report.nodes);
+    let by_name = node::ByName.cf_name().to_string();
+    assert!(Node::operations(database.as_ref())
+        .first(&by_name, b"api")?
+        .is_none());
+
+    database.commit(overlay.batch())?;
+    let api = Node::operations(database.as_ref()).first(&by_name, b"api")?;
+    assert!(api.is_some());
+    assert_eq!(7, db::last_id(database.as_ref())?);
+    Ok(())
+}
+
+#[test]
+fn test_import_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let info = TestDbInfo::new();
+    let database = db::init(&info, &All)?;
+    import(&database, columns(), true, NODES, EDGES)?;
+    assert!(fsck::fsck(&database)?.is_clean());
+    Ok(())
+}
@@ -3,12 +3,15 @@
 use tracing::{debug, error, info, trace, warn};
 
 use crate::rocksdb::counter;
-use crate::rocksdb::error::{ErrBadDbPath, ErrBadIndex};
+use crate::rocksdb::error::{ErrBadDbPath, ErrBadIndex, ErrIndexNotReady, ErrSchemaVersion};
 use crate::rocksdb::index::Index;
+use crate::rocksdb::spec::DbSpec;
+use crate::rocksdb::store::{Batch, Store};
 use crate::rocksdb::All;
 
 use rocksdb::{
-    DBWithThreadMode, Direction, IteratorMode, SingleThreaded, WriteBatchWithTransaction, DB,
+    ColumnFamilyDescriptor, DBWithThreadMode, IteratorMode, Options, ReadOptions, SingleThreaded,
+    WriteBatchWithTransaction, DB,
 };
 
 use std::error::Error;
@@ -19,6 +22,10 @@ use std::path::Path;
 pub trait DbInfo {
     fn path(&self) -> &str;
     fn options(&self) -> rocksdb::Options;
+    // Per column family options on top of options()
+    fn spec(&self) -> Option<&DbSpec> {
+        None
+    }
 }
 
 pub type Database = DBWithThreadMode<SingleThreaded>;
@@ -92,7 +99,7 @@ impl KeyCodec for u64 {
 // trait as a builder for getting an Operations trait implementation
 // which has CRUD methods.
 pub trait OperationsBuilder<E: Entity> {
-    fn operations(db: &Database) -> Box<dyn Operations<E> + '_>;
+    fn operations(db: &dyn Store) -> Box<dyn Operations<E> + '_>;
 }
 
 pub trait Operations<E: Entity> {
@@ -104,37 +111,95 @@ pub trait Operations<E: Entity> {
         start_id: Id<E>,
         visitor: Box<dyn Visitor<E> + '_>,
     ) -> Result<(), Box<dyn Error>>;
-    fn first(&self, index: &String, match_bytes: &[u8]) -> Result<Option<E>, Box<dyn Error>>;
+    fn first(&self, index: &str, match_bytes: &[u8]) -> Result<Option<E>, Box<dyn Error>>;
     fn scan(
         &self,
-        index: &String,
+        index: &str,
         match_start: Vec<u8>, //&[u8],
         visitor: Box<dyn Visitor<E> + '_>,
     ) -> Result<(), Box<dyn Error>>;
+    // Hooks are for code embedding the db; none of the commands register
+    // one yet.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn add_before_commit(&mut self, hook: Box<dyn BeforeCommit<E>>);
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn add_after_commit(&mut self, hook: Box<dyn AfterCommit<E>>);
+}
+
+// Called by put and delete with the batch about to be written, after the
+// indexes are updated.  A hook can add to the batch, or veto the write by
+// returning an error.  For a put, old is None if the entity is new; for a
+// delete, new is None.
+pub trait BeforeCommit<E: Entity> {
+    fn before_commit(
+        &self,
+        db: &dyn Store,
+        txn: &mut Batch,
+        old: Option<&E>,
+        new: Option<&E>,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+// Called by put and delete once the batch is written.
+pub trait AfterCommit<E: Entity> {
+    fn after_commit(&self, old: Option<&E>, new: Option<&E>);
+}
+
+impl<E, F> BeforeCommit<E> for F
+where
+    E: Entity,
+    F: Fn(&dyn Store, &mut Batch, Option<&E>, Option<&E>) -> Result<(), Box<dyn Error>>,
+{
+    fn before_commit(
+        &self,
+        db: &dyn Store,
+        txn: &mut Batch,
+        old: Option<&E>,
+        new: Option<&E>,
+    ) -> Result<(), Box<dyn Error>> {
+        self(db, txn, old, new)
+    }
+}
+
+impl<E, F> AfterCommit<E> for F
+where
+    E: Entity,
+    F: Fn(Option<&E>, Option<&E>),
+{
+    fn after_commit(&self, old: Option<&E>, new: Option<&E>) {
+        self(old, new)
+    }
 }
 
 pub(crate) trait IndexHelper<K: KeyCodec, E: Entity + HasKey<K>> {
     fn value_index(&self) -> &dyn Index<E>;
     fn indexes(&self) -> Vec<Box<dyn Index<E>>>;
-    fn before_put(&self, db: &Database, e: &mut E) -> Result<(), Box<dyn Error>>;
+    // Sets the fields the db assigns, such as the id and type code.  New
+    // ids and type codes are staged in txn, the batch of the put.
+    fn before_put(&self, db: &dyn Store, txn: &mut Batch, e: &mut E) -> Result<(), Box<dyn Error>>;
+    #[allow(clippy::wrong_self_convention)]
     fn from_bytes(&self, buff: &[u8]) -> Result<E, Box<dyn Error>>;
 }
 
 pub(crate) fn entity_operations<K: KeyCodec + 'static, E: Entity + HasKey<K> + 'static>(
-    db: &Database,
+    db: &dyn Store,
     ops: Box<dyn IndexHelper<K, E>>,
 ) -> Box<dyn Operations<E> + '_> {
     Box::new(OperationsImpl::<K, E> {
         db,
         custom: ops,
         counters: default_counters(db),
+        before_commit: vec![],
+        after_commit: vec![],
     })
 }
 
 struct OperationsImpl<'a, K: KeyCodec, E: Entity + HasKey<K>> {
-    db: &'a Database,
+    db: &'a dyn Store,
     custom: Box<dyn IndexHelper<K, E> + 'a>,
     counters: counter::Counters<'a>,
+    before_commit: Vec<Box<dyn BeforeCommit<E>>>,
+    after_commit: Vec<Box<dyn AfterCommit<E>>>,
 }
 
 #[test]
@@ -150,20 +215,19 @@ fn test_timestamp() {
     assert_eq!(now2, now);
 }
 
-impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_, K, E> {
+impl<K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_, K, E> {
     fn get(&self, id: Id<E>) -> Result<Option<E>, Box<dyn Error>> {
-        let cf = self
+        match self
             .db
-            .cf_handle(self.custom.value_index().cf_name())
-            .unwrap();
-        match self.db.get_cf(cf, id.key) {
-            Ok(Some(bytes)) => Ok(Some(self.custom.from_bytes(&bytes[..])?)),
-            Ok(None) => Ok(None),
-            Err(e) => Err(Box::new(e)),
+            .get_value(self.custom.value_index().cf_name(), &id.key)?
+        {
+            Some(bytes) => Ok(Some(self.custom.from_bytes(&bytes[..])?)),
+            None => Ok(None),
         }
     }
     fn put(&mut self, o: &mut E) -> Result<Id<E>, Box<dyn Error>> {
-        self.custom.before_put(self.db, o)?;
+        let mut txn = Batch::default();
+        self.custom.before_put(self.db, &mut txn, o)?;
 
         // Index keys can change based on the fields changed.
         // If we had index on obj.foo and obj.bar and now we have
@@ -175,26 +239,25 @@ impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_
         // remove the index entry at (value.foo, value.bar) and the
         // add the index entry at (value.foo', value.bar').
 
-        let mut txn = Transaction::default();
+        let mut is_new = true;
 
-        match self.get(o.id()) {
-            Ok(Some(found)) => {
+        let old = self.get(o.id())?;
+        match &old {
+            Some(found) => {
+                is_new = false;
                 let _: Vec<_> = self
                     .custom
                     .indexes()
                     .iter()
                     .map(|index| {
-                        let _ = index.delete_entry(self.db, &mut txn, &found);
+                        let _ = index.delete_entry(self.db, &mut txn, found);
                         trace!("Scheduled deletion old={:?} new={:?}", found, o);
                     })
                     .collect();
             }
-            Ok(None) => {
+            None => {
                 trace!("No value read with id={:?}", o.id());
             }
-            Err(e) => {
-                return Err(e);
-            }
         }
 
         // Index the new value
@@ -202,14 +265,22 @@ impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_
             .custom
             .indexes()
             .iter()
-            .map(|index| index.update_entry(self.db, &mut txn, &o))
+            .map(|index| index.update_entry(self.db, &mut txn, o))
             .collect();
 
-        // update a counter for the type
-        let mut counter = self.counters.get(E::TYPE)?;
-        counter.set(counter.get() + 1);
-        self.counters.update(&mut txn, &counter)?;
-        self.db.write(txn)?;
+        // update a counter for the type; updates don't change the count
+        if is_new {
+            let mut counter = self.counters.get(E::TYPE)?;
+            counter.set(counter.get() + 1);
+            self.counters.update(&mut txn, &counter)?;
+        }
+        for hook in self.before_commit.iter() {
+            hook.before_commit(self.db, &mut txn, old.as_ref(), Some(o))?;
+        }
+        self.db.commit(txn)?;
+        for hook in self.after_commit.iter() {
+            hook.after_commit(old.as_ref(), Some(o));
+        }
 
         Ok(o.id())
     }
@@ -218,7 +289,7 @@ impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_
         // Check if the entity exists first
         match self.get(o.id())? {
             Some(found) => {
-                let mut txn = Transaction::default();
+                let mut txn = Batch::default();
 
                 // Delete all index entries for this entity
                 let _: Vec<_> = self
@@ -232,11 +303,7 @@ impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_
                     .collect();
 
                 // Delete the actual entity from the value index
-                let cf = self
-                    .db
-                    .cf_handle(self.custom.value_index().cf_name())
-                    .unwrap();
-                txn.delete_cf(cf, o.id().as_bytes());
+                txn.delete(self.custom.value_index().cf_name(), o.id().as_bytes());
 
                 // Update counter for the type (decrement)
                 let mut counter = self.counters.get(E::TYPE)?;
@@ -245,8 +312,15 @@ impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_
                     self.counters.update(&mut txn, &counter)?;
                 }
 
+                for hook in self.before_commit.iter() {
+                    hook.before_commit(self.db, &mut txn, Some(&found), None)?;
+                }
+
                 // Commit the transaction
-                self.db.write(txn)?;
+                self.db.commit(txn)?;
+                for hook in self.after_commit.iter() {
+                    hook.after_commit(Some(&found), None);
+                }
 
                 Ok(true)
             }
@@ -263,73 +337,76 @@ impl<'a, K: KeyCodec, E: Entity + HasKey<K>> Operations<E> for OperationsImpl<'_
         mut visitor: Box<dyn Visitor<E> + '_>,
     ) -> Result<(), Box<dyn Error>> {
         trace!("visit from {:?}", start_id);
-        let cf = self
-            .db
-            .cf_handle(self.custom.value_index().cf_name())
-            .unwrap();
-        let iter = self.db.iterator_cf(
-            cf,
-            IteratorMode::From(start_id.as_bytes().as_slice(), Direction::Forward),
-        );
-        for item in iter {
-            let (k, v) = item.unwrap();
-            let entity = E::from_bytes(&k, &v)?;
-            if !visitor.visit(entity) {
-                break;
-            }
-        }
-        Ok(())
+        let mut result: Result<(), Box<dyn Error>> = Ok(());
+        self.db.scan_from(
+            self.custom.value_index().cf_name(),
+            start_id.as_bytes().as_slice(),
+            &mut |k, v| match E::from_bytes(k, v) {
+                Ok(entity) => visitor.visit(entity),
+                Err(e) => {
+                    result = Err(e);
+                    false
+                }
+            },
+        )?;
+        result
     }
 
-    fn first(&self, index: &String, match_bytes: &[u8]) -> Result<Option<E>, Box<dyn Error>> {
-        let cf = self.db.cf_handle(index.as_str()).unwrap();
-        match self.db.get_cf(cf, match_bytes) {
-            Ok(Some(bytes)) => {
+    fn first(&self, index: &str, match_bytes: &[u8]) -> Result<Option<E>, Box<dyn Error>> {
+        check_ready(self.db, index)?;
+        match self.db.get_value(index, match_bytes)? {
+            Some(bytes) => {
                 let id = E::id_from(KeyCodec::decode_key(bytes));
                 self.get(id)
             }
-            Ok(None) => Ok(None),
-            Err(e) => Err(Box::new(e)),
+            None => Ok(None),
         }
     }
 
     fn scan(
         &self,
-        index: &String,
+        index: &str,
         match_start: Vec<u8>, //&[u8],
         mut visitor: Box<dyn Visitor<E> + '_>,
     ) -> Result<(), Box<dyn Error>> {
-        let cf = self.db.cf_handle(index).unwrap();
+        check_ready(self.db, index)?;
         trace!("Found cf {:?} with match={:?}", index, match_start);
-        let iter = self.db.iterator_cf(
-            cf,
-            IteratorMode::From(match_start.as_slice(), Direction::Forward),
-        );
-        for item in iter {
-            let (k, v) = item.unwrap();
-            // The first bytes must match
-            if k.len() < match_start.len() || match_start.to_owned() != k[0..match_start.len()] {
-                break;
-            }
-            trace!("For match={:?}, (k,v)={:?} | {:?}", match_start, k, v);
-            if v.len() == 0 {
-                warn!("Bad value: index={:?}, k={:?}", index, k);
-                break;
-            }
+        let mut result: Result<(), Box<dyn Error>> = Ok(());
+        self.db
+            .scan_from(index, match_start.as_slice(), &mut |k, v| {
+                // The first bytes must match
+                if !k.starts_with(&match_start) {
+                    return false;
+                }
+                trace!("For match={:?}, (k,v)={:?} | {:?}", match_start, k, v);
+                if v.is_empty() {
+                    warn!("Bad value: index={:?}, k={:?}", index, k);
+                    return false;
+                }
 
-            let id = E::id_from(KeyCodec::decode_key(v.to_vec()));
-            let stop: Result<bool, Box<dyn Error>> = match self.get(id)? {
-                Some(obj) => Ok(!visitor.visit(obj)),
-                None => {
-                    error!("Bad index!!! {:?}", ErrBadIndex::new(index, &v));
-                    Err(Box::new(ErrBadIndex::new(index, &v)))
+                let id = E::id_from(KeyCodec::decode_key(v.to_vec()));
+                match self.get(id) {
+                    Ok(Some(obj)) => visitor.visit(obj),
+                    Ok(None) => {
+                        error!("Bad index!!! {:?}", ErrBadIndex::new(index, v));
+                        result = Err(Box::new(ErrBadIndex::new(index, v)));
+                        false
+                    }
+                    Err(e) => {
+                        result = Err(e);
+                        false
+                    }
                 }
-            };
-            if stop? {
-                break;
-            }
-        }
-        Ok(())
+            })?;
+        result
+    }
+
+    fn add_before_commit(&mut self, hook: Box<dyn BeforeCommit<E>>) {
+        self.before_commit.push(hook);
+    }
+
+    fn add_after_commit(&mut self, hook: Box<dyn AfterCommit<E>>) {
+        self.after_commit.push(hook);
     }
 }
 
@@ -337,6 +414,16 @@ pub trait Visitor<E: Sized> {
     fn visit(&mut self, entity: E) -> bool;
 }
 
+// Collects everything visited.
+pub(crate) struct Collect<'a, E>(pub &'a mut Vec<E>);
+
+impl<E> Visitor<E> for Collect<'_, E> {
+    fn visit(&mut self, e: E) -> bool {
+        self.0.push(e);
+        true
+    }
+}
+
 pub trait IndexBuilder {
     fn cf_names(&self) -> Vec<String>;
 }
@@ -344,8 +431,8 @@ pub trait IndexBuilder {
 fn check_path(path: &str) -> Result<&Path, Box<dyn Error>> {
     let p = Path::new(path);
     match p.try_exists() {
-        Err(e) => return Err(Box::new(e)),
-        Ok(false) => return Ok(p),
+        Err(e) => Err(Box::new(e)),
+        Ok(false) => Ok(p),
         Ok(true) => {
             if p.is_file() {
                 error!("Path is a file: {}", path);
@@ -362,34 +449,90 @@ fn check_path(path: &str) -> Result<&Path, Box<dyn Error>> {
 
 #[test]
 fn test_check_path() {
-    assert_eq!(check_path("/bin").unwrap(), Path::new("/bin"));
+    // A temp dir rather than /bin, which is a symlink on merged /usr systems
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    assert_eq!(check_path(path).unwrap(), Path::new(path));
 
     // expect error -- this is a path to a file or symlink
-    check_path("/bin/bash").unwrap_err();
+    let file = dir.path().join("file");
+    std::fs::write(&file, b"").unwrap();
+    check_path(file.to_str().unwrap()).unwrap_err();
+    let link = dir.path().join("link");
+    std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+    check_path(link.to_str().unwrap()).unwrap_err();
 
     // Non-existent file path is ok.
     check_path("/i/dont/exist").unwrap();
 }
 
-static CF_SYSTEM: &str = "cf.system";
-static SEQ_KEY: &str = "sequence";
-static CF_COUNTERS: &str = "cf.system.counters";
+pub(crate) static CF_SYSTEM: &str = "cf.system";
+pub(crate) static SEQ_KEY: &str = "sequence";
+pub(crate) static CF_COUNTERS: &str = "cf.system.counters";
 
 // CF for storing type information.
-static CF_SYSTEM_TYPES: &str = "cf.system.types";
-static COUNT_TYPES: &str = "counter.types";
+pub(crate) static CF_SYSTEM_TYPES: &str = "cf.system.types";
+pub(crate) static COUNT_TYPES: &str = "counter.types";
+
+// Keys in cf.system for indexes added to an existing db, present until
+// their entries for the existing entities are backfilled.
+pub(crate) static BACKFILL_PREFIX: &str = "backfill.";
+
+pub(crate) fn backfill_key(cf_name: &str) -> Vec<u8> {
+    format!("{}{}", BACKFILL_PREFIX, cf_name).into_bytes()
+}
+
+// Returns an error if the index is still being backfilled.
+pub fn check_ready(db: &dyn Store, cf_name: &str) -> Result<(), Box<dyn Error>> {
+    match db.get_value(CF_SYSTEM, &backfill_key(cf_name))? {
+        Some(_) => Err(Box::new(ErrIndexNotReady::new(cf_name))),
+        None => Ok(()),
+    }
+}
 
-pub fn default_counters(db: &Database) -> counter::Counters {
+pub fn default_counters(db: &dyn Store) -> counter::Counters<'_> {
     counter::Counters::new(db, CF_COUNTERS)
 }
 
-fn all_column_families(builder: &dyn IndexBuilder) -> Vec<String> {
+fn system_column_families() -> Vec<String> {
+    vec![
+        CF_SYSTEM.to_string(),
+        CF_SYSTEM_TYPES.to_string(),
+        CF_COUNTERS.to_string(),
+    ]
+}
+
+pub(crate) fn all_column_families(builder: &dyn IndexBuilder) -> Vec<String> {
     let mut indexes = builder.cf_names();
-    indexes.push(CF_SYSTEM.to_string());
-    indexes.push(CF_SYSTEM_TYPES.to_string());
-    indexes.push(CF_COUNTERS.to_string());
+    indexes.extend(system_column_families());
     trace!("all_column_families: {:?}", indexes);
-    return indexes;
+    indexes
+}
+
+pub(crate) fn cf_options(info: &dyn DbInfo, base: &Options, cf_name: &str) -> Options {
+    match info.spec() {
+        Some(spec) => spec.cf_options(base, cf_name),
+        None => base.clone(),
+    }
+}
+
+// Descriptors for opening the column families with the options of the db
+// spec, after validating it.
+pub(crate) fn cf_descriptors(
+    info: &dyn DbInfo,
+    cf_names: Vec<String>,
+) -> Result<Vec<ColumnFamilyDescriptor>, Box<dyn Error>> {
+    if let Some(spec) = info.spec() {
+        spec.validate(&cf_names)?;
+    }
+    let options = info.options();
+    Ok(cf_names
+        .into_iter()
+        .map(|c| {
+            let cf_options = cf_options(info, &options, &c);
+            ColumnFamilyDescriptor::new(c, cf_options)
+        })
+        .collect())
 }
 
 pub fn init(info: &dyn DbInfo, builder: &dyn IndexBuilder) -> Result<Database, Box<dyn Error>> {
@@ -408,8 +551,7 @@ pub fn init(info: &dyn DbInfo, builder: &dyn IndexBuilder) -> Result<Database, B
         if found.iter().find(|cf| cf.as_str() == c).is_none() {
             // create a new ColumnFamily
             info!("Creating column family {:?}", c);
-            let create_cf = db.create_cf(c, &options)?;
-            info!("Creating column family {:?}, result = {:?}", c, create_cf);
+            db.create_cf(c, &cf_options(info, &options, c))?;
         } else {
             info!("Found column family {:?}", c);
         }
@@ -421,99 +563,292 @@ pub fn init(info: &dyn DbInfo, builder: &dyn IndexBuilder) -> Result<Database, B
 // TODO - Optimize this a bit more so that opening the database simply
 // opens all the column families, without creating them (do that in "init").
 pub fn open_db(info: &dyn DbInfo, builder: &dyn IndexBuilder) -> Result<Database, Box<dyn Error>> {
+    let db = open_cfs(info, builder)?;
+    check_schema(&db)?;
+    Ok(db)
+}
+
+// Opens the db without checking its schema version.  Only migrations
+// should work against a db of another version.
+pub(crate) fn open_cfs(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+) -> Result<Database, Box<dyn Error>> {
     trace!("open_db path={}", info.path());
     let options = info.options();
-    match DB::open_cf(
+    let found = DB::list_cf(&options, info.path()).unwrap_or(vec![]);
+    let db = match DB::open_cf_descriptors(
         &options,
         check_path(info.path())?,
-        all_column_families(builder),
+        cf_descriptors(info, all_column_families(builder))?,
     ) {
-        Ok(db) => Ok(db),
+        Ok(db) => db,
         Err(e) => {
             error!("Error opening db: {:?}", e);
-            Err(Box::new(e))
+            return Err(Box::new(e));
+        }
+    };
+
+    // Indexes created on a db that already has data start out empty; mark
+    // them for backfill, up to the last id handed out.  A new db has nothing
+    // to backfill.
+    if !found.is_empty() {
+        let cf = db.cf_handle(CF_SYSTEM).unwrap();
+        let until = last_id(&db)?;
+        let mut txn = Transaction::default();
+        for c in builder.cf_names() {
+            if !found.contains(&c) {
+                info!("New index {:?} needs backfill up to id {:?}", c, until);
+                txn.put_cf(cf, backfill_key(&c), until.to_le_bytes());
+            }
         }
+        db.write(txn)?;
+    } else {
+        set_schema_version(&db, SCHEMA_VERSION)?;
     }
+    Ok(db)
+}
+
+// How a db handle was opened.  Only one process can open a db as the
+// primary; others can read it as a secondary instance, which follows the
+// primary's writes when asked to catch up, or as a read-only snapshot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenMode {
+    Primary,
+    Secondary,
+    ReadOnly,
+}
+
+// Column families both declared and on disk; the read modes can't create
+// the missing ones.
+fn existing_column_families(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+) -> Result<Vec<ColumnFamilyDescriptor>, Box<dyn Error>> {
+    let found = DB::list_cf(&info.options(), info.path())?;
+    let names = all_column_families(builder)
+        .into_iter()
+        .filter(|c| found.contains(c))
+        .collect();
+    cf_descriptors(info, names)
+}
+
+pub fn open_read_only(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+) -> Result<Database, Box<dyn Error>> {
+    trace!("open_read_only path={}", info.path());
+    let db = DB::open_cf_descriptors_read_only(
+        &info.options(),
+        info.path(),
+        existing_column_families(info, builder)?,
+        false,
+    )?;
+    check_schema(&db)?;
+    Ok(db)
+}
+
+// Opens a secondary instance, keeping its own logs in secondary_path, and
+// catches it up with the primary.
+pub fn open_secondary(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+    secondary_path: &str,
+) -> Result<Database, Box<dyn Error>> {
+    trace!(
+        "open_secondary path={} secondary={}",
+        info.path(),
+        secondary_path
+    );
+    let mut options = info.options();
+    options.set_max_open_files(-1); // required for secondary instances
+    let db = DB::open_cf_descriptors_as_secondary(
+        &options,
+        info.path(),
+        secondary_path,
+        existing_column_families(info, builder)?,
+    )?;
+    catch_up(&db)?;
+    check_schema(&db)?;
+    Ok(db)
+}
+
+// Applies the primary's latest writes to a secondary instance.
+pub fn catch_up(db: &Database) -> Result<(), Box<dyn Error>> {
+    Ok(db.try_catch_up_with_primary()?)
+}
+
+// Did opening the db at path fail because RocksDB couldn't take its LOCK
+// file, i.e. because another handle has it open?
+pub(crate) fn is_lock_error(e: &(dyn Error + 'static), path: &str) -> bool {
+    match e.downcast_ref::<rocksdb::Error>() {
+        Some(e) => {
+            e.kind() == rocksdb::ErrorKind::IOError
+                && e.as_ref().contains(&format!("{}/LOCK", path))
+        }
+        None => false,
+    }
+}
+
+// Directory under the db where secondary instances keep their logs.
+pub(crate) static SECONDARY_DIR: &str = "secondary";
+
+pub(crate) fn secondary_path(info: &dyn DbInfo) -> String {
+    Path::new(info.path())
+        .join(SECONDARY_DIR)
+        .to_string_lossy()
+        .to_string()
+}
+
+// Opens the db for reading: as the primary when nobody holds the lock,
+// else as a secondary instance, else read-only.
+pub fn open_for_read(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+) -> Result<(Database, OpenMode), Box<dyn Error>> {
+    match open_db(info, builder) {
+        Ok(db) => return Ok((db, OpenMode::Primary)),
+        Err(e) if is_lock_error(e.as_ref(), info.path()) => {
+            info!("Db is locked; opening as secondary")
+        }
+        Err(e) => return Err(e),
+    }
+    match open_secondary(info, builder, &secondary_path(info)) {
+        Ok(db) => Ok((db, OpenMode::Secondary)),
+        Err(e) => {
+            warn!("Opening as secondary failed: {:?}; opening read-only", e);
+            Ok((open_read_only(info, builder)?, OpenMode::ReadOnly))
+        }
+    }
+}
+
+// Version of the on-disk layout written by this binary.  Bump it with each
+// new step in migrate::migrations().
+pub const SCHEMA_VERSION: u64 = 2;
+
+// Key in cf.system for the schema version.  Dbs created before versioning
+// don't have it and are at version 1.
+pub(crate) static SCHEMA_KEY: &str = "schema.version";
+
+pub fn schema_version(db: &dyn Store) -> Result<u64, Box<dyn Error>> {
+    match db.get_value(CF_SYSTEM, SCHEMA_KEY.as_bytes())? {
+        Some(v) => {
+            let le = v.try_into().unwrap_or_else(|v: Vec<u8>| {
+                panic!("Expected a Vec of length {} but it was {}", 8, v.len())
+            });
+            Ok(u64::from_le_bytes(le))
+        }
+        None => Ok(1),
+    }
+}
+
+pub(crate) fn set_schema_version(db: &dyn Store, version: u64) -> Result<(), Box<dyn Error>> {
+    db.put_value(CF_SYSTEM, SCHEMA_KEY.as_bytes(), &version.to_le_bytes())
+}
+
+// Refuses dbs written by a newer binary; older ones open with a warning
+// until migrated.
+fn check_schema(db: &Database) -> Result<(), Box<dyn Error>> {
+    let version = schema_version(db)?;
+    if version > SCHEMA_VERSION {
+        error!(
+            "Db schema version {} is newer than {}",
+            version, SCHEMA_VERSION
+        );
+        return Err(Box::new(ErrSchemaVersion::new(version, SCHEMA_VERSION)));
+    }
+    if version < SCHEMA_VERSION {
+        warn!(
+            "Db schema version {} is older than {}; run migrate",
+            version, SCHEMA_VERSION
+        );
+    }
+    Ok(())
 }
 
 // Returns the type code by checking a global lookup table of names;
 // creates new entry if name is not found.
-pub fn type_code(db: &Database, name: &String) -> Result<u64, Box<dyn Error>> {
-    let type_code: u64;
-    let cf = db.cf_handle(CF_SYSTEM_TYPES).unwrap();
-    match db.get_cf(cf, name.as_bytes()) {
+pub fn type_code(db: &dyn Store, name: &String) -> Result<u64, Box<dyn Error>> {
+    // Note starting a separate txn from the put of the object.
+    // This only updates two cf's: the counters and type/symbol tables.
+    let mut txn = Batch::default();
+    let type_code = stage_type_code(db, &mut txn, name)?;
+    if !txn.is_empty() {
+        if let Err(e) = db.commit(txn) {
+            error!("Error updating type codes {:?}", e);
+            return Err(e);
+        }
+    }
+    Ok(type_code)
+}
+
+// Returns the type code of the name, or None if there is none yet.
+pub fn find_type_code(db: &dyn Store, name: &str) -> Result<Option<u64>, Box<dyn Error>> {
+    match db.get_value(CF_SYSTEM_TYPES, name.as_bytes()) {
         Err(e) => {
             error!("Error retrieving value for {}: {}", name, e);
-            return Err(Box::new(e));
+            Err(e)
         }
-
         Ok(Some(v)) => {
             let le = v.try_into().unwrap_or_else(|v: Vec<u8>| {
                 panic!("Expected a Vec of length {} but it was {}", 8, v.len())
             });
-            type_code = u64::from_le_bytes(le);
+            let type_code = u64::from_le_bytes(le);
             trace!("type_code read: {}", type_code);
-            Ok(type_code)
+            Ok(Some(type_code))
         }
+        Ok(None) => Ok(None),
+    }
+}
 
-        Ok(None) => {
+// Like type_code, but a new entry is added to txn rather than written, so
+// that it is only kept if txn is committed.
+pub(crate) fn stage_type_code(
+    db: &dyn Store,
+    txn: &mut Batch,
+    name: &String,
+) -> Result<u64, Box<dyn Error>> {
+    match find_type_code(db, name)? {
+        Some(type_code) => Ok(type_code),
+        None => {
             // The type code is simply the count of types + 1 (> 0)
             let mut counters = default_counters(db);
             let mut counter = counters.get(COUNT_TYPES)?;
-            type_code = counter.get() + 1;
-
-            // Note starting a separate txn from the put of the object.
-            // This only updates two cf's: the counters and type/symbol tables.
-            let mut txn = Transaction::default();
+            let type_code = counter.get() + 1;
 
             // Update the number of rows in the types table/cf.
             counter.set(type_code);
-            counters.update(&mut txn, &counter)?;
-            txn.put_cf(cf, name.as_bytes(), type_code.to_le_bytes().to_vec());
-            match db.write(txn) {
-                Ok(()) => Ok(type_code),
-                Err(e) => {
-                    error!("Error updating type codes {:?}", e);
-                    Err(Box::new(e))
-                }
-            }
+            counters.update(txn, &counter)?;
+            txn.put(CF_SYSTEM_TYPES, name.as_bytes(), type_code.to_le_bytes());
+            Ok(type_code)
         }
     }
 }
 
-pub fn next_id(db: &Database) -> Result<u64, Box<dyn Error>> {
-    trace!("DB = {:?}", db);
-
-    let id: u64;
-
-    // TODO inefficient - refactor later
-    let cf = db.cf_handle(CF_SYSTEM).unwrap();
-    match db.get_cf(cf, SEQ_KEY.as_bytes()) {
+// Returns the last id committed by next_id, or 0 if there is none.
+pub fn last_id(db: &dyn Store) -> Result<u64, Box<dyn Error>> {
+    match db.get_value(CF_SYSTEM, SEQ_KEY.as_bytes()) {
         Ok(Some(v)) => {
-            let be = v.try_into().unwrap_or_else(|v: Vec<u8>| {
+            let le = v.try_into().unwrap_or_else(|v: Vec<u8>| {
                 panic!("Expected a Vec of length {} but it was {}", 8, v.len())
             });
-
-            id = u64::from_le_bytes(be) + 1;
-            trace!("id read: {}", id);
-        }
-        Ok(None) => {
-            id = 1;
+            Ok(u64::from_le_bytes(le))
         }
+        Ok(None) => Ok(0),
         Err(e) => {
             error!("Error retrieving value for {}: {}", SEQ_KEY, e);
-            return Err(Box::new(e));
+            Err(e)
         }
     }
+}
 
-    // update the id before returning the value
-    match db.put_cf(cf, SEQ_KEY.as_bytes(), id.to_le_bytes().to_vec()) {
-        Ok(()) => Ok(id),
-        Err(e) => {
-            error!("Error updating sequence {}", SEQ_KEY);
-            Err(Box::new(e))
-        }
-    }
+// Returns a new id.  The sequence is updated in txn, so the id is only used
+// up if txn is committed.
+pub fn next_id(db: &dyn Store, txn: &mut Batch) -> Result<u64, Box<dyn Error>> {
+    let id = last_id(db)? + 1;
+    trace!("id read: {}", id);
+    txn.put(CF_SYSTEM, SEQ_KEY.as_bytes(), id.to_le_bytes());
+    Ok(id)
 }
 
 pub fn indexes(
@@ -538,11 +873,13 @@ pub fn list_index(
     visitor: &mut dyn Visitor<(Box<[u8]>, Box<[u8]>)>,
 ) -> Result<(), Box<dyn Error>> {
     trace!("List path={}, key={}", info.path(), index);
-    let db = open_db(info, &All)?;
+    let (db, _) = open_for_read(info, &All)?;
     trace!("DB = {:?}", db);
 
     let cf = db.cf_handle(index).unwrap();
-    let iter = db.iterator_cf(cf, IteratorMode::From("".as_bytes(), Direction::Forward));
+    let mut options = ReadOptions::default();
+    options.set_total_order_seek(true);
+    let iter = db.iterator_cf_opt(cf, options, IteratorMode::Start);
     for item in iter {
         if !visitor.visit(item.unwrap()) {
             break;
@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::graph::Node;
+use crate::rocksdb::store::{Batch, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+
+type Seen = Rc<RefCell<Vec<(Option<String>, Option<String>)>>>;
+
+fn name(n: Option<&Node>) -> Option<String> {
+    n.map(|n| n.name.clone())
+}
+
+#[test]
+fn test_after_commit() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    let mut ops = Node::operations(&db);
+
+    let seen: Seen = Rc::new(RefCell::new(vec![]));
+    let s = seen.clone();
+    ops.add_after_commit(Box::new(move |old: Option<&Node>, new: Option<&Node>| {
+        s.borrow_mut().push((name(old), name(new)));
+    }));
+
+    let mut node = Node {
+        name: "api".into(),
+        ..Default::default()
+    };
+    ops.put(&mut node)?;
+    node.name = "gateway".into();
+    ops.put(&mut node)?;
+    ops.delete(&node)?;
+
+    assert_eq!(
+        vec![
+            (None, Some("api".to_string())),
+            (Some("api".to_string()), Some("gateway".to_string())),
+            (Some("gateway".to_string()), None),
+        ],
+        *seen.borrow()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_before_commit() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    let mut ops = Node::operations(&db);
+
+    // Veto some names, and audit the others in the same batch
+    ops.add_before_commit(Box::new(
+        |_: &dyn Store, txn: &mut Batch, _: Option<&Node>, new: Option<&Node>| {
+            let new = match new {
+                Some(n) => n,
+                None => return Ok(()),
+            };
+            if new.name.starts_with("tmp") {
+                return Err("no temporary nodes".into());
+            }
+            txn.put(
+                "cf.system",
+                format!("audit.{}", new.id),
+                new.name.as_bytes(),
+            );
+            Ok(())
+        },
+    ));
+
+    let mut node = Node {
+        name: "tmp1".into(),
+        type_name: "scratch".into(),
+        ..Default::default()
+    };
+    assert!(ops.put(&mut node).is_err());
+    assert!(ops.get(Node::id_from(node.id))?.is_none());
+    assert_eq!(0, db::default_counters(&db).get("Node")?.get());
+
+    // A vetoed put uses up neither an id nor a type code
+    assert_eq!(0, db::last_id(&db)?);
+    assert_eq!(None, db.get_value(db::CF_SYSTEM_TYPES, b"scratch")?);
+
+    let mut node = Node {
+        name: "api".into(),
+        ..Default::default()
+    };
+    ops.put(&mut node)?;
+    let cf = db.cf_handle("cf.system").unwrap();
+    let audit = db.get_cf(cf, format!("audit.{}", node.id))?;
+    assert_eq!(Some(b"api".to_vec()), audit);
+    Ok(())
+}
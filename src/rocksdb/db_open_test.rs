@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::rocksdb::db::{self, HasKey, OpenMode, OperationsBuilder};
+use crate::rocksdb::graph::Node;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+
+#[test]
+fn test_open_for_read_unlocked() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    db::init(&db_info, &All)?;
+    let (_, mode) = db::open_for_read(&db_info, &All)?;
+    assert_eq!(OpenMode::Primary, mode);
+    Ok(())
+}
+
+#[test]
+fn test_open_for_read_locked() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let primary = db::init(&db_info, &All)?;
+    let mut ops = Node::operations(&primary);
+    ops.put(&mut Node {
+        name: "api".into(),
+        ..Default::default()
+    })?;
+
+    let err = db::open_db(&db_info, &All).unwrap_err();
+    assert!(db::is_lock_error(err.as_ref(), &db_info.path));
+    let (secondary, mode) = db::open_for_read(&db_info, &All)?;
+    assert_eq!(OpenMode::Secondary, mode);
+    assert!(Path::new(&db_info.path).join("secondary").is_dir());
+    let reader = Node::operations(&secondary);
+    assert!(reader.get(Node::id_from(1u64))?.is_some());
+    assert!(reader.first("index.node.name", b"api")?.is_some());
+
+    // Sees new writes after catching up
+    ops.put(&mut Node {
+        name: "db".into(),
+        ..Default::default()
+    })?;
+    db::catch_up(&secondary)?;
+    assert!(reader.get(Node::id_from(2u64))?.is_some());
+
+    // Writes are refused
+    assert!(Node::operations(&secondary)
+        .put(&mut Node {
+            name: "cache".into(),
+            ..Default::default()
+        })
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_open_read_only() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let primary = db::init(&db_info, &All)?;
+    Node::operations(&primary).put(&mut Node {
+        name: "api".into(),
+        ..Default::default()
+    })?;
+
+    let db = db::open_read_only(&db_info, &All)?;
+    assert!(Node::operations(&db).get(Node::id_from(1u64))?.is_some());
+    Ok(())
+}
+
+#[test]
+fn test_other_errors_not_lock_errors() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join("file");
+    std::fs::write(&file, b"")?;
+    let db_info = TestDbInfo::at(file.to_str().unwrap());
+    let err = db::open_db(&db_info, &All).unwrap_err();
+    assert!(!db::is_lock_error(err.as_ref(), &db_info.path));
+    Ok(())
+}
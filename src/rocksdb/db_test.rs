@@ -1,41 +1,13 @@
 use std::error::Error;
 use std::path::Path;
-use tempfile::tempdir;
 
 use crate::rocksdb::db::{
     Database, DbInfo, Entity, HasKey, Id, KeyCodec, Operations, OperationsBuilder,
 };
 use crate::rocksdb::graph::{Edge, Node};
 use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::testing::TestDbInfo;
 use crate::rocksdb::All;
-use rocksdb::Options;
-
-// A helper struct to create a temporary database for testing
-struct TestDbInfo {
-    path: String,
-}
-
-impl TestDbInfo {
-    fn new() -> Self {
-        let dir = tempdir().unwrap();
-        Self {
-            path: dir.path().to_str().unwrap().to_string(),
-        }
-    }
-}
-
-impl DbInfo for TestDbInfo {
-    fn path(&self) -> &str {
-        &self.path
-    }
-
-    fn options(&self) -> Options {
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-        opts.create_missing_column_families(true);
-        opts
-    }
-}
 
 #[test]
 fn test_operations_delete_node() -> Result<(), Box<dyn Error>> {
@@ -83,6 +55,29 @@ fn test_operations_delete_node() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+fn test_operations_counter_counts_entities() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = crate::rocksdb::db::init(&db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    let mut node = Node {
+        type_name: "TestNode".into(),
+        name: "TestNodeName".into(),
+        ..Default::default()
+    };
+    node_ops.put(&mut node)?;
+
+    // Putting it again updates it and doesn't count it again
+    node.name = "Renamed".into();
+    node_ops.put(&mut node)?;
+    let counters = crate::rocksdb::db::default_counters(&db);
+    assert_eq!(1, counters.get("Node")?.get());
+
+    node_ops.delete(&node)?;
+    assert_eq!(0, counters.get("Node")?.get());
+    Ok(())
+}
+
 #[test]
 fn test_operations_delete_edge() -> Result<(), Box<dyn Error>> {
     // Setup a temporary database
@@ -0,0 +1,133 @@
+use std::error::Error;
+
+use playrs_derive::GraphEntity;
+
+use crate::rocksdb::db::{self, Entity, HasKey, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::registry::Registry;
+use crate::rocksdb::store::MemStore;
+use crate::rocksdb::testing::TestDbInfo;
+
+#[derive(Clone, PartialEq, ::prost::Message, GraphEntity)]
+#[graph(timestamp = ts_nano, type_name = type_name, type_code = type_code)]
+#[index(name = "index.task.id", value)]
+#[index(name = "index.task.name", fields = (name), unique)]
+#[index(name = "index.task.owner", fields = (owner, type_code))]
+#[index(name = "index.task.name-hash", fields = (name_hash()))]
+pub struct Task {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(string, tag = "2")]
+    pub type_name: String,
+    #[prost(uint64, tag = "3")]
+    pub type_code: u64,
+    #[prost(string, tag = "4")]
+    pub name: String,
+    #[prost(uint64, tag = "5")]
+    pub owner: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub ts_nano: Vec<u8>,
+}
+
+impl Task {
+    fn name_hash(&self) -> String {
+        crate::rocksdb::hash::compute_sha256_hash(&self.name)
+    }
+}
+
+fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register::<u64, Task>();
+    registry
+}
+
+#[test]
+fn test_derived_indexes() {
+    let task = Task {
+        id: 7,
+        type_code: 2,
+        name: "build".into(),
+        owner: 3,
+        ..Default::default()
+    };
+    assert_eq!("Task", Task::TYPE);
+    assert_eq!(Some(7), task.key());
+    assert_eq!(
+        vec![
+            "index.task.id",
+            "index.task.name",
+            "index.task.owner",
+            "index.task.name-hash"
+        ],
+        Task::indexes()
+            .iter()
+            .map(|i| i.cf_name())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!("index.task.id", Task::value_index().cf_name());
+
+    assert_eq!(
+        (task.id().as_bytes(), task.as_bytes()),
+        TaskById.key_value(&task)
+    );
+    assert_eq!(
+        (b"build".to_vec(), 7u64.to_le_bytes().to_vec()),
+        TaskByName.key_value(&task)
+    );
+    let mut owner_key = 3u64.to_le_bytes().to_vec();
+    owner_key.extend(2u64.to_le_bytes());
+    assert_eq!(owner_key, TaskByOwner.key_value(&task).0);
+    assert!(!TaskByName.append_if_same_key());
+    assert!(TaskByNameHash.append_if_same_key());
+    assert_eq!(
+        task.name_hash().into_bytes(),
+        TaskByNameHash.key_value(&task).0
+    );
+
+    assert_eq!(task, Task::from_bytes(&[], &task.as_bytes()).unwrap());
+}
+
+#[test]
+fn test_derived_operations() -> Result<(), Box<dyn Error>> {
+    let store = MemStore::new(&registry());
+    let mut ops = Task::operations(&store);
+    let mut task = Task {
+        type_name: "job".into(),
+        name: "build".into(),
+        owner: 3,
+        ..Default::default()
+    };
+    ops.put(&mut task)?;
+
+    // The id, timestamp and type code are filled in
+    assert_eq!(1, task.id);
+    assert_eq!(16, task.ts_nano.len());
+    assert_eq!(1, task.type_code);
+
+    let found = ops.first("index.task.name", b"build")?;
+    assert_eq!(Some(&task), found.as_ref());
+    assert_eq!(1, db::default_counters(&store).get("Task")?.get());
+
+    assert!(ops.delete(&task)?);
+    assert!(ops.get(Task::id_from(1u64))?.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_derived_fsck() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &registry())?;
+    let mut ops = Task::operations(&db);
+    for name in ["build", "test", "build"] {
+        ops.put(&mut Task {
+            type_name: "job".into(),
+            name: name.into(),
+            ..Default::default()
+        })?;
+    }
+    let report = fsck::fsck_with(&db, &registry())?;
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(3, report.entities);
+    Ok(())
+}
@@ -0,0 +1,201 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{Collect, HasKey, OperationsBuilder};
+use crate::rocksdb::edge;
+use crate::rocksdb::error::ErrNoSuchNode;
+use crate::rocksdb::export::Counts;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::store::Store;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Dot,
+    Mermaid,
+}
+
+// Which part of the graph to draw.  Without a root it's every node, and the
+// edges between them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Subgraph {
+    // Name of the node to walk out from
+    pub root: Option<String>,
+    // Hops from the root; unlimited if not set
+    pub depth: Option<usize>,
+    // Only edges of these types, if any
+    pub edge_types: Vec<String>,
+}
+
+// Fill colors, given out to the node types in name order.
+static PALETTE: [&str; 8] = [
+    "#8dd3c7", "#ffffb3", "#bebada", "#fb8072", "#80b1d3", "#fdb462", "#b3de69", "#fccde5",
+];
+
+pub fn export_diagram(
+    db: &dyn Store,
+    subgraph: &Subgraph,
+    style: Style,
+    out: &mut dyn Write,
+) -> Result<Counts, Box<dyn Error>> {
+    let (nodes, edges) = collect(db, subgraph)?;
+    match style {
+        Style::Dot => write_dot(&nodes, &edges, out)?,
+        Style::Mermaid => write_mermaid(&nodes, &edges, out)?,
+    }
+    Ok(Counts {
+        nodes: nodes.len() as u64,
+        edges: edges.len() as u64,
+        ..Default::default()
+    })
+}
+
+// The nodes and edges of the subgraph, in id order.
+pub fn collect(
+    db: &dyn Store,
+    subgraph: &Subgraph,
+) -> Result<(Vec<Node>, Vec<Edge>), Box<dyn Error>> {
+    let wanted =
+        |e: &Edge| subgraph.edge_types.is_empty() || subgraph.edge_types.contains(&e.type_name);
+    let mut nodes = BTreeMap::new();
+    let mut edges = BTreeMap::new();
+    let node_ops = Node::operations(db);
+    let edge_ops = Edge::operations(db);
+
+    let root = match &subgraph.root {
+        None => {
+            let mut all = vec![];
+            node_ops.visit(Node::id_from(0), Box::new(Collect(&mut all)))?;
+            nodes.extend(all.into_iter().map(|n| (n.id, n)));
+            let mut all = vec![];
+            edge_ops.visit(Edge::id_from(0), Box::new(Collect(&mut all)))?;
+            edges.extend(
+                all.into_iter()
+                    .filter(|e| wanted(e))
+                    .filter(|e| nodes.contains_key(&e.head) && nodes.contains_key(&e.tail))
+                    .map(|e| (e.id, e)),
+            );
+            return Ok((nodes.into_values().collect(), edges.into_values().collect()));
+        }
+        Some(name) => node_ops
+            .first(node::ByName.cf_name(), name.as_bytes())?
+            .ok_or_else(|| Box::new(ErrNoSuchNode::new(name.clone())))?,
+    };
+
+    // Breadth first over the head-tail index, so each node is at its
+    // shortest distance from the root
+    let out_index = edge::ByHeadTail.cf_name().to_string();
+    let mut queue = VecDeque::from([(root.id, 0)]);
+    nodes.insert(root.id, root);
+    let mut seen = BTreeSet::new();
+    while let Some((id, hops)) = queue.pop_front() {
+        if subgraph.depth.is_some_and(|d| hops >= d) || !seen.insert(id) {
+            continue;
+        }
+        let mut out = vec![];
+        edge_ops.scan(
+            &out_index,
+            id.to_le_bytes().to_vec(),
+            Box::new(Collect(&mut out)),
+        )?;
+        for e in out.into_iter().filter(|e| wanted(e)) {
+            if !nodes.contains_key(&e.tail) {
+                // Dangling edges are left out
+                let Some(tail) = node_ops.get(Node::id_from(e.tail))? else {
+                    continue;
+                };
+                nodes.insert(tail.id, tail);
+            }
+            queue.push_back((e.tail, hops + 1));
+            edges.insert(e.id, e);
+        }
+    }
+    Ok((nodes.into_values().collect(), edges.into_values().collect()))
+}
+
+pub fn write_dot(
+    nodes: &[Node],
+    edges: &[Edge],
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let colors = colors(nodes);
+    writeln!(out, "digraph G {{")?;
+    writeln!(out, "  node [style=filled];")?;
+    for n in nodes {
+        writeln!(
+            out,
+            "  n{} [label={}, fillcolor=\"{}\", tooltip={}];",
+            n.id,
+            dot_quote(&n.name),
+            colors[n.type_name.as_str()],
+            dot_quote(&n.type_name)
+        )?;
+    }
+    for e in edges {
+        writeln!(
+            out,
+            "  n{} -> n{} [label={}];",
+            e.head,
+            e.tail,
+            dot_quote(&e.name)
+        )?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+pub fn write_mermaid(
+    nodes: &[Node],
+    edges: &[Edge],
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let colors = colors(nodes);
+    writeln!(out, "flowchart LR")?;
+    for n in nodes {
+        writeln!(out, "  n{}[{}]", n.id, mermaid_quote(&n.name))?;
+    }
+    for e in edges {
+        writeln!(
+            out,
+            "  n{} -->|{}| n{}",
+            e.head,
+            mermaid_quote(&e.name),
+            e.tail
+        )?;
+    }
+    // A class per type, named by position as type names needn't be ids
+    for (i, (type_name, color)) in colors.iter().enumerate() {
+        let ids: Vec<String> = nodes
+            .iter()
+            .filter(|n| n.type_name == *type_name)
+            .map(|n| format!("n{}", n.id))
+            .collect();
+        writeln!(out, "  %% t{}: {}", i, type_name)?;
+        writeln!(out, "  classDef t{} fill:{}", i, color)?;
+        writeln!(out, "  class {} t{}", ids.join(","), i)?;
+    }
+    Ok(())
+}
+
+// Type name to fill color.
+fn colors(nodes: &[Node]) -> BTreeMap<&str, &'static str> {
+    let types: BTreeSet<&str> = nodes.iter().map(|n| n.type_name.as_str()).collect();
+    types
+        .into_iter()
+        .enumerate()
+        .map(|(i, t)| (t, PALETTE[i % PALETTE.len()]))
+        .collect()
+}
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "#quot;"))
+}
@@ -0,0 +1,139 @@
+use std::error::Error;
+
+use crate::rocksdb::db::OperationsBuilder;
+use crate::rocksdb::diagram::{self, Style, Subgraph};
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::All;
+
+// api -> db, api -> cache, cache -> db, db -> disk, and a lone node.
+fn build(db: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut ids = vec![];
+    for (name, type_name) in [
+        ("api", "service"),
+        ("db", "database"),
+        ("cache", "service"),
+        ("disk", "volume"),
+        ("lone \"wolf\"", "service"),
+    ] {
+        let mut n = Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        };
+        Node::operations(db).put(&mut n)?;
+        ids.push(n.id);
+    }
+    for (name, head, tail) in [
+        ("depends-on", 0, 1),
+        ("depends-on", 0, 2),
+        ("reads", 2, 1),
+        ("runs-on", 1, 3),
+    ] {
+        Edge::operations(db).put(&mut Edge {
+            name: name.into(),
+            type_name: name.into(),
+            head: ids[head],
+            tail: ids[tail],
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+fn names(db: &dyn Store, subgraph: Subgraph) -> Result<(Vec<String>, usize), Box<dyn Error>> {
+    let (nodes, edges) = diagram::collect(db, &subgraph)?;
+    Ok((nodes.into_iter().map(|n| n.name).collect(), edges.len()))
+}
+
+#[test]
+fn test_collect() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    build(&db)?;
+    let (nodes, edges) = names(&db, Subgraph::default())?;
+    assert_eq!((5, 4), (nodes.len(), edges));
+
+    let root = |name: &str, depth, edge_types: &[&str]| Subgraph {
+        root: Some(name.into()),
+        depth,
+        edge_types: edge_types.iter().map(|t| t.to_string()).collect(),
+    };
+    assert_eq!(
+        (vec!["api".into(), "db".into(), "cache".into()], 2),
+        names(&db, root("api", Some(1), &[]))?
+    );
+    // cache -> db is found from either end of the first hop
+    assert_eq!(4, names(&db, root("api", Some(2), &[]))?.1);
+    assert_eq!(4, names(&db, root("api", None, &[]))?.0.len());
+    assert_eq!(
+        (vec!["db".into(), "cache".into()], 1),
+        names(&db, root("cache", None, &["reads"]))?
+    );
+    assert_eq!(
+        (vec!["db".into()], 0),
+        names(&db, root("db", Some(0), &[]))?
+    );
+
+    let err = names(&db, root("nope", None, &[])).unwrap_err();
+    assert_eq!("No such node: \"nope\"", err.to_string());
+    Ok(())
+}
+
+#[test]
+fn test_dot() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    build(&db)?;
+    let subgraph = Subgraph {
+        edge_types: vec!["reads".into()],
+        ..Default::default()
+    };
+    let mut out = vec![];
+    let counts = diagram::export_diagram(&db, &subgraph, Style::Dot, &mut out)?;
+    assert_eq!((5, 1), (counts.nodes, counts.edges));
+    assert_eq!(
+        r##"digraph G {
+  node [style=filled];
+  n1 [label="api", fillcolor="#ffffb3", tooltip="service"];
+  n2 [label="db", fillcolor="#8dd3c7", tooltip="database"];
+  n3 [label="cache", fillcolor="#ffffb3", tooltip="service"];
+  n4 [label="disk", fillcolor="#bebada", tooltip="volume"];
+  n5 [label="lone \"wolf\"", fillcolor="#ffffb3", tooltip="service"];
+  n3 -> n2 [label="reads"];
+}
+"##,
+        String::from_utf8(out)?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mermaid() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    build(&db)?;
+    let subgraph = Subgraph {
+        root: Some("cache".into()),
+        ..Default::default()
+    };
+    let mut out = vec![];
+    diagram::export_diagram(&db, &subgraph, Style::Mermaid, &mut out)?;
+    assert_eq!(
+        r#"flowchart LR
+  n2["db"]
+  n3["cache"]
+  n4["disk"]
+  n3 -->|"reads"| n2
+  n2 -->|"runs-on"| n4
+  %% t0: database
+  classDef t0 fill:#8dd3c7
+  class n2 t0
+  %% t1: service
+  classDef t1 fill:#ffffb3
+  class n3 t1
+  %% t2: volume
+  classDef t2 fill:#bebada
+  class n4 t2
+"#,
+        String::from_utf8(out)?
+    );
+    Ok(())
+}
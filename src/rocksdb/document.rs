@@ -0,0 +1,344 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Entity, HasKey};
+use crate::rocksdb::error::ErrBadPath;
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::store::{Batch, Store};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::error::Error;
+use std::fmt;
+
+// Key in cf.system for the JSON array of the declared paths.
+pub(crate) static DOC_PATHS_KEY: &str = "doc.paths";
+
+// Documents read at a time when reindexing for a new path.
+pub(crate) const REINDEX_BATCH_SIZE: usize = 1_000;
+
+// A schemaless JSON document.  Paths are the JSON paths it is indexed by:
+// those declared for the db when it was put, plus any set by the caller.
+// They are stored with the document so its index entries can be found again
+// after the declarations change.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    #[serde(default)]
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Element(usize),
+    Elements,
+}
+
+// A JSON path like $.owner, $.tags[*] or $.items[0].sku.  Only child fields,
+// array elements and [*] are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, ErrBadPath> {
+        let rest = match path.strip_prefix('$') {
+            Some(r) => r,
+            None => return Err(ErrBadPath::new(path, "must start with $")),
+        };
+        let mut steps = vec![];
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    let mut name = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c == '.' || c == '[' {
+                            break;
+                        }
+                        name.push(c);
+                        chars.next();
+                    }
+                    if name.is_empty() {
+                        return Err(ErrBadPath::new(path, "empty field name"));
+                    }
+                    steps.push(Step::Field(name));
+                }
+                '[' => {
+                    let mut inner = String::new();
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                        inner.push(c);
+                    }
+                    if inner == "*" {
+                        steps.push(Step::Elements);
+                    } else {
+                        match inner.parse::<usize>() {
+                            Ok(n) => steps.push(Step::Element(n)),
+                            Err(_) => {
+                                return Err(ErrBadPath::new(path, "expected [n] or [*]"));
+                            }
+                        }
+                    }
+                }
+                _ => return Err(ErrBadPath::new(path, "expected . or [")),
+            }
+        }
+        Ok(JsonPath { steps })
+    }
+
+    // The values at the path; [*] gives one per element.
+    pub fn select<'a>(&self, v: &'a Value) -> Vec<&'a Value> {
+        let mut found = vec![v];
+        for step in self.steps.iter() {
+            found = found
+                .into_iter()
+                .flat_map(|v| match (step, v) {
+                    (Step::Field(name), Value::Object(m)) => m.get(name).into_iter().collect(),
+                    (Step::Element(n), Value::Array(a)) => a.get(*n).into_iter().collect(),
+                    (Step::Elements, Value::Array(a)) => a.iter().collect(),
+                    _ => vec![],
+                })
+                .collect();
+        }
+        found
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$")?;
+        for step in self.steps.iter() {
+            match step {
+                Step::Field(name) => write!(f, ".{}", name)?,
+                Step::Element(n) => write!(f, "[{}]", n)?,
+                Step::Elements => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Prefix of the entries in index.doc.path for the path and scalar value:
+// the path, 0, the value as JSON, 0.  The id (u64 le) follows.
+pub fn path_key(path: &JsonPath, value: &Value) -> Vec<u8> {
+    let mut key = path.to_string().into_bytes();
+    key.push(0);
+    key.extend(value.to_string().into_bytes());
+    key.push(0);
+    key
+}
+
+impl HasKey<u64> for Document {
+    fn key(&self) -> Option<u64> {
+        if self.id > 0 {
+            Some(self.id)
+        } else {
+            None
+        }
+    }
+}
+
+impl Entity for Document {
+    const TYPE: &'static str = "Document";
+    fn as_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+    fn from_bytes(_key: &[u8], bytes: &[u8]) -> Result<Document, Box<dyn Error>> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+impl db::OperationsBuilder<Document> for Document {
+    fn operations(db: &dyn Store) -> Box<dyn db::Operations<Document> + '_> {
+        db::entity_operations::<u64, Document>(db, Box::new(IndexHelper {}))
+    }
+}
+
+struct IndexHelper {}
+
+impl db::IndexHelper<u64, Document> for IndexHelper {
+    fn value_index(&self) -> &dyn Index<Document> {
+        &ById
+    }
+    fn indexes(&self) -> Vec<Box<dyn Index<Document>>> {
+        Document::indexes()
+    }
+    fn before_put(
+        &self,
+        db: &dyn Store,
+        txn: &mut Batch,
+        doc: &mut Document,
+    ) -> Result<(), Box<dyn Error>> {
+        if doc.id == 0 {
+            doc.id = db::next_id(db, txn)?;
+        }
+        for path in declared_paths(db)? {
+            if !doc.paths.contains(&path) {
+                doc.paths.push(path);
+            }
+        }
+        Ok(())
+    }
+    fn from_bytes(&self, buff: &[u8]) -> Result<Document, Box<dyn Error>> {
+        Document::from_bytes(&[], buff)
+    }
+}
+
+impl Indexes<Document> for Document {
+    fn indexes() -> Vec<Box<dyn Index<Document>>> {
+        vec![
+            // By Id
+            Box::new(ById),
+            // By the values at the document's paths
+            Box::new(ByPath),
+        ]
+    }
+    fn value_index() -> Box<dyn Index<Document>> {
+        Box::new(ById)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ById;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ByPath;
+
+impl Index<Document> for ById {
+    fn cf_name(&self) -> &'static str {
+        "index.doc.id"
+    }
+    fn key_value(&self, d: &Document) -> (Vec<u8>, Vec<u8>) {
+        (d.id().as_bytes(), d.as_bytes())
+    }
+}
+
+impl Index<Document> for ByPath {
+    fn cf_name(&self) -> &'static str {
+        "index.doc.path"
+    }
+    fn key_value(&self, d: &Document) -> (Vec<u8>, Vec<u8>) {
+        self.key_values(d).into_iter().next().unwrap_or_default()
+    }
+    // One entry per scalar at each path; objects and arrays are skipped.
+    fn key_values(&self, d: &Document) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let id = d.id.to_le_bytes().to_vec();
+        let mut kvs = vec![];
+        for p in d.paths.iter() {
+            let path = match JsonPath::parse(p) {
+                Ok(path) => path,
+                Err(e) => {
+                    warn!("Not indexing document {}: {}", d.id, e);
+                    continue;
+                }
+            };
+            for v in path.select(&d.body) {
+                if v.is_object() || v.is_array() {
+                    continue;
+                }
+                let mut key = path_key(&path, v);
+                key.extend(id.iter());
+                if !kvs.iter().any(|kv: &(Vec<u8>, Vec<u8>)| kv.0 == key) {
+                    kvs.push((key, id.clone()));
+                }
+            }
+        }
+        kvs
+    }
+}
+
+// The paths indexed for new documents.
+pub fn declared_paths(db: &dyn Store) -> Result<Vec<String>, Box<dyn Error>> {
+    match db.get_value(db::CF_SYSTEM, DOC_PATHS_KEY.as_bytes())? {
+        Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        None => Ok(vec![]),
+    }
+}
+
+// Declares the path for indexing and reindexes the documents stored before,
+// REINDEX_BATCH_SIZE at a time.  Returns the path as stored, e.g. $.tags[*]
+// for $.tags[ * ].
+pub fn declare_path(db: &dyn Store, path: &str) -> Result<String, Box<dyn Error>> {
+    use crate::rocksdb::db::OperationsBuilder;
+
+    let path = JsonPath::parse(path)?.to_string();
+    let mut paths = declared_paths(db)?;
+    if paths.contains(&path) {
+        return Ok(path);
+    }
+    paths.push(path.clone());
+    db.put_value(
+        db::CF_SYSTEM,
+        DOC_PATHS_KEY.as_bytes(),
+        &serde_json::to_vec(&paths)?,
+    )?;
+
+    // Each batch starts at the key of the last document of the one before,
+    // in key order, which the put doesn't change.
+    let mut ops = Document::operations(db);
+    let mut last: Option<Vec<u8>> = None;
+    loop {
+        let mut docs = Vec::<Document>::with_capacity(REINDEX_BATCH_SIZE);
+        let mut result: Result<(), Box<dyn Error>> = Ok(());
+        let start = last.clone().unwrap_or_default();
+        db.scan_from(ById.cf_name(), &start, &mut |k, v| {
+            if last.as_deref() == Some(k) {
+                return true;
+            }
+            match Document::from_bytes(k, v) {
+                Ok(doc) => docs.push(doc),
+                Err(e) => {
+                    result = Err(e);
+                    return false;
+                }
+            }
+            docs.len() < REINDEX_BATCH_SIZE
+        })?;
+        result?;
+
+        let more = docs.len() == REINDEX_BATCH_SIZE;
+        last = docs.last().map(|d| d.id().as_bytes());
+        for mut doc in docs {
+            ops.put(&mut doc)?;
+        }
+        if !more {
+            break;
+        }
+    }
+    info!("Declared {:?}; paths are {:?}", path, paths);
+    Ok(path)
+}
+
+// Visits the documents with the value at the path, in key order.
+pub fn find(
+    db: &dyn Store,
+    path: &str,
+    value: &Value,
+    visitor: Box<dyn db::Visitor<Document> + '_>,
+) -> Result<(), Box<dyn Error>> {
+    use crate::rocksdb::db::OperationsBuilder;
+
+    let path = JsonPath::parse(path)?;
+    Document::operations(db).scan(ByPath.cf_name(), path_key(&path, value), visitor)
+}
+
+pub struct DocumentPrinter(pub usize);
+
+impl db::Visitor<Document> for DocumentPrinter {
+    fn visit(&mut self, doc: Document) -> bool {
+        match serde_json::to_string(&doc) {
+            Ok(s) => println!("{}", s),
+            Err(e) => error!("Error: {:?}", e),
+        }
+        self.0 -= 1;
+        self.0 > 0
+    }
+}
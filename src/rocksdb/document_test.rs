@@ -0,0 +1,120 @@
+use serde_json::json;
+use std::error::Error;
+
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::document::{self, Document, JsonPath};
+use crate::rocksdb::fsck;
+use crate::rocksdb::registry;
+use crate::rocksdb::store::MemStore;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+
+fn ids(db: &dyn crate::rocksdb::store::Store, path: &str, value: serde_json::Value) -> Vec<u64> {
+    let mut found = Vec::<Document>::new();
+    document::find(db, path, &value, Box::new(db::Collect(&mut found))).unwrap();
+    found.iter().map(|d| d.id).collect()
+}
+
+#[test]
+fn test_json_path() {
+    let v = json!({"owner": "ann", "tags": ["a", "b"], "items": [{"sku": 1}, {"sku": 2}]});
+
+    let p = JsonPath::parse("$.owner").unwrap();
+    assert_eq!(vec![&json!("ann")], p.select(&v));
+
+    let p = JsonPath::parse("$.tags[*]").unwrap();
+    assert_eq!(vec![&json!("a"), &json!("b")], p.select(&v));
+
+    let p = JsonPath::parse("$.items[1].sku").unwrap();
+    assert_eq!(vec![&json!(2)], p.select(&v));
+    assert_eq!("$.items[1].sku", p.to_string());
+
+    assert!(JsonPath::parse("$.nope").unwrap().select(&v).is_empty());
+    assert!(JsonPath::parse("owner").is_err());
+    assert!(JsonPath::parse("$.").is_err());
+    assert!(JsonPath::parse("$[x]").is_err());
+}
+
+#[test]
+fn test_put_and_find() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    document::declare_path(&db, "$.owner")?;
+    document::declare_path(&db, "$.tags[*]")?;
+    assert_eq!(vec!["$.owner", "$.tags[*]"], document::declared_paths(&db)?);
+
+    let mut ops = Document::operations(&db);
+    let mut a = Document {
+        body: json!({"owner": "ann", "tags": ["red", "blue"]}),
+        ..Default::default()
+    };
+    let mut b = Document {
+        body: json!({"owner": "bob", "tags": ["red"], "n": 1}),
+        ..Default::default()
+    };
+    ops.put(&mut a)?;
+    ops.put(&mut b)?;
+    assert!(a.id > 0 && b.id > a.id);
+    assert_eq!(Some(a.clone()), ops.get(Document::id_from(a.id))?);
+
+    assert_eq!(vec![a.id], ids(&db, "$.owner", json!("ann")));
+    assert_eq!(vec![a.id, b.id], ids(&db, "$.tags[*]", json!("red")));
+    assert_eq!(vec![a.id], ids(&db, "$.tags[*]", json!("blue")));
+    assert!(ids(&db, "$.owner", json!("carl")).is_empty());
+
+    // Changing a value moves the entry
+    b.body["owner"] = json!("ann");
+    ops.put(&mut b)?;
+    assert_eq!(vec![a.id, b.id], ids(&db, "$.owner", json!("ann")));
+    assert!(ids(&db, "$.owner", json!("bob")).is_empty());
+
+    // Deleting removes all of them
+    ops.delete(&a)?;
+    assert_eq!(vec![b.id], ids(&db, "$.owner", json!("ann")));
+    assert_eq!(vec![b.id], ids(&db, "$.tags[*]", json!("red")));
+    assert!(ids(&db, "$.tags[*]", json!("blue")).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_declare_after_put() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+
+    let mut ops = Document::operations(&db);
+    let mut doc = Document {
+        body: json!({"status": "open", "points": 3}),
+        ..Default::default()
+    };
+    ops.put(&mut doc)?;
+    assert!(ids(&db, "$.status", json!("open")).is_empty());
+
+    // Declaring indexes what is already there
+    assert_eq!("$.points", document::declare_path(&db, "$.points")?);
+    document::declare_path(&db, "$.status")?;
+    assert_eq!(vec![doc.id], ids(&db, "$.points", json!(3)));
+    assert_eq!(vec![doc.id], ids(&db, "$.status", json!("open")));
+    assert!(ids(&db, "$.points", json!("3")).is_empty());
+
+    let report = fsck::fsck_with(&db, &registry::global())?;
+    assert!(report.is_clean(), "{:?}", report);
+    Ok(())
+}
+
+#[test]
+fn test_declare_path_in_batches() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let mut ops = Document::operations(&db);
+    let n = 2 * document::REINDEX_BATCH_SIZE + 1;
+    for i in 0..n {
+        ops.put(&mut Document {
+            body: json!({"kind": "part", "n": i}),
+            ..Default::default()
+        })?;
+    }
+
+    document::declare_path(&db, "$.kind")?;
+    assert_eq!(n, ids(&db, "$.kind", json!("part")).len());
+    let report = fsck::fsck_with(&db, &registry::global())?;
+    assert!(report.is_clean(), "{:?}", report);
+    Ok(())
+}
@@ -1,37 +1,9 @@
 use std::error::Error;
-use tempfile::tempdir;
 
-use crate::rocksdb::db::{DbInfo, OperationsBuilder};
+use crate::rocksdb::db::OperationsBuilder;
 use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::testing::TestDbInfo;
 use crate::rocksdb::All;
-use rocksdb::Options;
-
-// A helper struct to create a temporary database for testing
-struct TestDbInfo {
-    path: String,
-}
-
-impl TestDbInfo {
-    fn new() -> Self {
-        let dir = tempdir().unwrap();
-        Self {
-            path: dir.path().to_str().unwrap().to_string(),
-        }
-    }
-}
-
-impl DbInfo for TestDbInfo {
-    fn path(&self) -> &str {
-        &self.path
-    }
-
-    fn options(&self) -> Options {
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-        opts.create_missing_column_families(true);
-        opts
-    }
-}
 
 #[cfg(test)]
 mod tests {
@@ -53,6 +53,186 @@ impl fmt::Display for ErrMissingIndex {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct ErrNoSuchIndex {
+    name: String,
+}
+
+impl Error for ErrNoSuchIndex {}
+
+impl ErrNoSuchIndex {
+    pub fn new(name: &str) -> ErrNoSuchIndex {
+        ErrNoSuchIndex {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrNoSuchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No such secondary index: {:?}", self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrIndexNotReady {
+    cf_name: String,
+}
+
+impl Error for ErrIndexNotReady {}
+
+impl ErrIndexNotReady {
+    pub fn new(cf_name: &str) -> ErrIndexNotReady {
+        ErrIndexNotReady {
+            cf_name: cf_name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrIndexNotReady {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Index {:?} is not ready; backfill is pending",
+            self.cf_name
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrSchemaVersion {
+    found: u64,
+    supported: u64,
+}
+
+impl Error for ErrSchemaVersion {}
+
+impl ErrSchemaVersion {
+    pub fn new(found: u64, supported: u64) -> ErrSchemaVersion {
+        ErrSchemaVersion { found, supported }
+    }
+}
+
+impl fmt::Display for ErrSchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Db schema version {:?} is newer than the supported {:?}",
+            self.found, self.supported
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrNoMigration {
+    from: u64,
+}
+
+impl Error for ErrNoMigration {}
+
+impl ErrNoMigration {
+    pub fn new(from: u64) -> ErrNoMigration {
+        ErrNoMigration { from }
+    }
+}
+
+impl fmt::Display for ErrNoMigration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No migration from schema version {:?}", self.from)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrDbInUse {
+    path: String,
+}
+
+impl Error for ErrDbInUse {}
+
+impl ErrDbInUse {
+    pub fn new(path: &str) -> ErrDbInUse {
+        ErrDbInUse {
+            path: path.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrDbInUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Db {:?} is open by another process", self.path)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrBadSpec {
+    cf_name: String,
+    reason: String,
+}
+
+impl Error for ErrBadSpec {}
+
+impl ErrBadSpec {
+    pub fn new(cf_name: &str, reason: &str) -> ErrBadSpec {
+        ErrBadSpec {
+            cf_name: cf_name.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad spec for {:?}: {}", self.cf_name, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrBadWriteBatch {
+    offset: usize,
+    reason: String,
+}
+
+impl Error for ErrBadWriteBatch {}
+
+impl ErrBadWriteBatch {
+    pub fn new(offset: usize, reason: &str) -> ErrBadWriteBatch {
+        ErrBadWriteBatch {
+            offset,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadWriteBatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad write batch at {:?}: {}", self.offset, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrWalGap {
+    since: u64,
+    oldest: u64,
+}
+
+impl Error for ErrWalGap {}
+
+impl ErrWalGap {
+    pub fn new(since: u64, oldest: u64) -> ErrWalGap {
+        ErrWalGap { since, oldest }
+    }
+}
+
+impl fmt::Display for ErrWalGap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Changes after {:?} are gone from the WAL, which starts at {:?}",
+            self.since, self.oldest
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ErrNoCounters {
     cf_name: String,
@@ -78,9 +258,9 @@ pub struct ErrBadIndex {
 }
 
 impl ErrBadIndex {
-    pub fn new(cf_name: &String, key: &[u8]) -> ErrBadIndex {
+    pub fn new(cf_name: &str, key: &[u8]) -> ErrBadIndex {
         ErrBadIndex {
-            cf_name: cf_name.clone(),
+            cf_name: cf_name.to_string(),
             key: key.to_vec(),
         }
     }
@@ -112,3 +292,118 @@ impl std::fmt::Display for ErrNoSuchNode {
         write!(f, "No such node: {:?}", self.name)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct ErrBadPath {
+    path: String,
+    reason: String,
+}
+
+impl Error for ErrBadPath {}
+
+impl ErrBadPath {
+    pub fn new(path: &str, reason: &str) -> ErrBadPath {
+        ErrBadPath {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad JSON path {:?}: {}", self.path, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrBadValue {
+    content_type: String,
+    reason: String,
+}
+
+impl Error for ErrBadValue {}
+
+impl ErrBadValue {
+    pub fn new(content_type: &str, reason: &str) -> ErrBadValue {
+        ErrBadValue {
+            content_type: content_type.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad {:?} value: {}", self.content_type, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrBadQuery {
+    query: String,
+    reason: String,
+}
+
+impl Error for ErrBadQuery {}
+
+impl ErrBadQuery {
+    pub fn new(query: &str, reason: &str) -> ErrBadQuery {
+        ErrBadQuery {
+            query: query.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad query {:?}: {}", self.query, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrBadImport {
+    line: usize,
+    reason: String,
+}
+
+impl Error for ErrBadImport {}
+
+impl ErrBadImport {
+    pub fn new(line: usize, reason: &str) -> ErrBadImport {
+        ErrBadImport {
+            line,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadImport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad import at line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrBadXml {
+    offset: u64,
+    reason: String,
+}
+
+impl Error for ErrBadXml {}
+
+impl ErrBadXml {
+    pub fn new(offset: u64, reason: &str) -> ErrBadXml {
+        ErrBadXml {
+            offset,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrBadXml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bad XML at byte {}: {}", self.offset, self.reason)
+    }
+}
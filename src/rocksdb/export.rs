@@ -0,0 +1,252 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder, Visitor};
+use crate::rocksdb::error::ErrBadImport;
+use crate::rocksdb::graph::{Attribute, Edge, Node, Symbol};
+use crate::rocksdb::store::Store;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+// One line of a JSON lines export: the prost type tagged with its kind, e.g.
+//
+// {"symbol":{"id":1,"name":"service","name_hash":"a5c1...","doc":"","ts_nano":""}}
+// {"node":{"id":1,"type_code":1,"type_name":"service","name":"api","ts_nano":"80d3..."}}
+// {"edge":{"id":3,"type_code":2,"type_name":"depends-on","name":"depends-on","head":1,"tail":2,"ts_nano":"..."}}
+// {"attribute":{"parent_id":1,"name":"port","content":"901f...","content_type":"int",...}}
+//
+// Symbols are the type names, with their type codes as ids.  Bytes are hex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Line {
+    Symbol(Symbol),
+    Node(Node),
+    Edge(Edge),
+    Attribute(Attribute),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Counts {
+    pub symbols: u64,
+    pub nodes: u64,
+    pub edges: u64,
+    pub attributes: u64,
+}
+
+// What import does with the ids in the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ids {
+    // New ids from the db sequence; heads, tails and parents are mapped to
+    // them, so they must be in the file before they are referred to.
+    Remap,
+    // The ids as given, which must not be in use.  The sequence moves past
+    // the largest.
+    Preserve,
+}
+
+// Serde of bytes as lowercase hex, for the bytes fields of the prost types.
+pub mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(
+            &bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        if s.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+// Writes the symbols in type code order, then the nodes, edges and
+// attributes in key order, so the same db always exports the same lines.
+// Ids are little endian, so key order is stable but isn't id order.
+pub fn export_jsonl(db: &dyn Store, out: &mut dyn Write) -> Result<Counts, Box<dyn Error>> {
+    let mut counts = Counts::default();
+
+    let mut types = vec![];
+    db.scan_from(db::CF_SYSTEM_TYPES, b"", &mut |k, v| {
+        if let Ok(le) = v.try_into() {
+            types.push((
+                u64::from_le_bytes(le),
+                String::from_utf8_lossy(k).to_string(),
+            ));
+        }
+        true
+    })?;
+    types.sort();
+    for (id, name) in types {
+        let line = Line::Symbol(Symbol {
+            id,
+            name_hash: Sha256::digest(name.as_bytes()).to_vec(),
+            name,
+            ..Default::default()
+        });
+        writeln!(out, "{}", serde_json::to_string(&line)?)?;
+        counts.symbols += 1;
+    }
+
+    let mut lines = Lines::new(out, Line::Node);
+    Node::operations(db).visit(Node::id_from(0), Box::new(&mut lines))?;
+    counts.nodes = lines.done()?;
+    let mut lines = Lines::new(out, Line::Edge);
+    Edge::operations(db).visit(Edge::id_from(0), Box::new(&mut lines))?;
+    counts.edges = lines.done()?;
+    let mut lines = Lines::new(out, Line::Attribute);
+    Attribute::operations(db).visit(
+        Attribute::id_from(AttrKey {
+            parent_id: 0,
+            name: String::new(),
+        }),
+        Box::new(&mut lines),
+    )?;
+    counts.attributes = lines.done()?;
+    Ok(counts)
+}
+
+// Writes each entity visited as a line; stops at the first error.
+struct Lines<'a, E> {
+    out: &'a mut dyn Write,
+    line: fn(E) -> Line,
+    count: u64,
+    error: Option<Box<dyn Error>>,
+}
+
+impl<'a, E> Lines<'a, E> {
+    fn new(out: &'a mut dyn Write, line: fn(E) -> Line) -> Self {
+        Lines {
+            out,
+            line,
+            count: 0,
+            error: None,
+        }
+    }
+
+    fn done(self) -> Result<u64, Box<dyn Error>> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.count),
+        }
+    }
+}
+
+impl<E> Visitor<E> for &mut Lines<'_, E> {
+    fn visit(&mut self, e: E) -> bool {
+        let written = serde_json::to_string(&(self.line)(e))
+            .map_err(|e| e.into())
+            .and_then(|s| writeln!(self.out, "{}", s).map_err(|e| e.into()));
+        match written {
+            Ok(()) => {
+                self.count += 1;
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+// Reads an export back.  Type codes and the indexes are rebuilt by the puts;
+// timestamps are kept.
+pub fn import_jsonl(
+    db: &dyn Store,
+    input: &mut dyn BufRead,
+    ids: Ids,
+) -> Result<Counts, Box<dyn Error>> {
+    let mut counts = Counts::default();
+    // Old id to new, for nodes and edges
+    let mut remap: HashMap<u64, u64> = HashMap::new();
+    let mut nodes = Node::operations(db);
+    let mut edges = Edge::operations(db);
+    let mut attributes = Attribute::operations(db);
+
+    for (i, text) in input.lines().enumerate() {
+        let n = i + 1;
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let bad = |reason: &str| Box::new(ErrBadImport::new(n, reason));
+        let line: Line = serde_json::from_str(&text).map_err(|e| bad(&e.to_string()))?;
+        let resolve = |old: u64| match (ids, remap.get(&old)) {
+            (_, Some(id)) => Ok(*id),
+            (Ids::Preserve, None) => Ok(old),
+            (Ids::Remap, None) => Err(bad(&format!("id {} is not earlier in the file", old))),
+        };
+        let check_unused = |id: u64| -> Result<(), Box<dyn Error>> {
+            if nodes.get(Node::id_from(id))?.is_some() || edges.get(Edge::id_from(id))?.is_some() {
+                return Err(bad(&format!("id {} is in use", id)));
+            }
+            Ok(())
+        };
+        match line {
+            Line::Symbol(s) => {
+                db::type_code(db, &s.name)?;
+                counts.symbols += 1;
+            }
+            Line::Node(mut node) => {
+                let old = node.id;
+                match ids {
+                    Ids::Remap => node.id = 0,
+                    Ids::Preserve => {
+                        check_unused(old)?;
+                        bump_sequence(db, old)?;
+                    }
+                }
+                nodes.put(&mut node)?;
+                remap.insert(old, node.id);
+                counts.nodes += 1;
+            }
+            Line::Edge(mut edge) => {
+                let old = edge.id;
+                edge.head = resolve(edge.head)?;
+                edge.tail = resolve(edge.tail)?;
+                match ids {
+                    Ids::Remap => edge.id = 0,
+                    Ids::Preserve => {
+                        check_unused(old)?;
+                        bump_sequence(db, old)?;
+                    }
+                }
+                edges.put(&mut edge)?;
+                remap.insert(old, edge.id);
+                counts.edges += 1;
+            }
+            Line::Attribute(mut attribute) => {
+                attribute.parent_id = resolve(attribute.parent_id)?;
+                attributes.put(&mut attribute)?;
+                counts.attributes += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+// Moves the id sequence past an id about to be put as given, so that no id
+// handed out after it, e.g. to a type, can be the same.
+pub fn bump_sequence(db: &dyn Store, id: u64) -> Result<(), Box<dyn Error>> {
+    if id > db::last_id(db)? {
+        db.put_value(db::CF_SYSTEM, db::SEQ_KEY.as_bytes(), &id.to_le_bytes())?;
+    }
+    Ok(())
+}
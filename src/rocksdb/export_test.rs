@@ -0,0 +1,151 @@
+use std::error::Error;
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::export::{self, Counts, Ids, Line};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+// api -> db, with attributes on both ends and the edge.
+fn build(db: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut ids = vec![];
+    for (name, type_name) in [("api", "service"), ("db", "database")] {
+        let mut n = Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        };
+        Node::operations(db).put(&mut n)?;
+        ids.push(n.id);
+    }
+    let mut e = Edge {
+        name: "depends-on".into(),
+        type_name: "depends-on".into(),
+        head: ids[0],
+        tail: ids[1],
+        ..Default::default()
+    };
+    Edge::operations(db).put(&mut e)?;
+    ids.push(e.id);
+    for (parent_id, name, v) in [
+        (ids[0], "port", Value::Int(8080)),
+        (ids[1], "engine", Value::String("postgres".into())),
+        (ids[2], "weight", Value::Float(0.5)),
+    ] {
+        Attribute::operations(db).put(&mut Attribute {
+            parent_id,
+            name: name.into(),
+            content: v.content(),
+            content_type: v.content_type().into(),
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+fn export(db: &dyn Store) -> Result<(String, Counts), Box<dyn Error>> {
+    let mut out = vec![];
+    let counts = export::export_jsonl(db, &mut out)?;
+    Ok((String::from_utf8(out)?, counts))
+}
+
+#[test]
+fn test_round_trip() -> Result<(), Box<dyn Error>> {
+    let from = MemStore::new(&All);
+    build(&from)?;
+    let (text, counts) = export(&from)?;
+    assert_eq!(
+        Counts {
+            symbols: 3,
+            nodes: 2,
+            edges: 1,
+            attributes: 3,
+        },
+        counts
+    );
+    assert_eq!(9, text.lines().count());
+    // Stable, and each line is a tagged prost type
+    assert_eq!(text, export(&from)?.0);
+    let lines: Vec<Line> = text
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert!(matches!(&lines[0], Line::Symbol(s) if s.id == 1 && s.name == "service"));
+    assert!(matches!(&lines[3], Line::Node(n) if n.name == "api"));
+
+    // Preserved ids give back the same export
+    let to = MemStore::new(&All);
+    let imported = export::import_jsonl(&to, &mut text.as_bytes(), Ids::Preserve)?;
+    assert_eq!(counts, imported);
+    assert_eq!(text, export(&to)?.0);
+    assert_eq!(3, db::last_id(&to)?);
+
+    // but not twice
+    let err = export::import_jsonl(&to, &mut text.as_bytes(), Ids::Preserve).unwrap_err();
+    assert!(
+        err.to_string().contains("line 4: id 1 is in use"),
+        "{}",
+        err
+    );
+
+    // Remapped ids come after the ones in use
+    let to = MemStore::new(&All);
+    build(&to)?;
+    export::import_jsonl(&to, &mut text.as_bytes(), Ids::Remap)?;
+    let node = Node::operations(&to).get(Node::id_from(4))?.unwrap();
+    assert_eq!("api", node.name);
+    let edge = Edge::operations(&to).get(Edge::id_from(6))?.unwrap();
+    assert_eq!((4, 5), (edge.head, edge.tail));
+    let attribute = Attribute::operations(&to).get(Attribute::id_from(AttrKey {
+        parent_id: 6,
+        name: "weight".into(),
+    }))?;
+    assert_eq!(Some(Value::Float(0.5)), attribute.and_then(|a| a.value()));
+    Ok(())
+}
+
+#[test]
+fn test_import_errors() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let import = |text: &str| export::import_jsonl(&db, &mut text.as_bytes(), Ids::Remap);
+
+    let err = import(r#"{"edge": {"id": 9, "name": "e", "head": 1, "tail": 2}}"#).unwrap_err();
+    assert!(
+        err.to_string().contains("line 1: id 1 is not earlier"),
+        "{}",
+        err
+    );
+    let err = import("\n{\"vertex\": {}}").unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{}", err);
+    let err = import(r#"{"node": {"name": "x", "ts_nano": "abc"}}"#).unwrap_err();
+    assert!(err.to_string().contains("hex"), "{}", err);
+
+    // Missing fields default
+    let counts = import(r#"{"node": {"id": 7, "name": "x", "type_name": "t"}}"#)?;
+    assert_eq!(1, counts.nodes);
+    Ok(())
+}
+
+#[test]
+fn test_round_trip_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let from_info = TestDbInfo::new();
+    let from = db::init(&from_info, &All)?;
+    build(&from)?;
+    let (text, _) = export(&from)?;
+
+    let to_info = TestDbInfo::new();
+    let to = db::init(&to_info, &All)?;
+    export::import_jsonl(&to, &mut text.as_bytes(), Ids::Preserve)?;
+    assert_eq!(text, export(&to)?.0);
+    assert!(fsck::fsck(&to)?.is_clean());
+
+    // Remapped over a graph with the same types
+    export::import_jsonl(&to, &mut text.as_bytes(), Ids::Remap)?;
+    assert!(fsck::fsck(&to)?.is_clean());
+    assert_eq!(4, Node::operations(&to).get(Node::id_from(4))?.unwrap().id);
+    Ok(())
+}
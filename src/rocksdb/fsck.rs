@@ -0,0 +1,384 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Entity};
+use crate::rocksdb::error::{ErrMissingIndex, ErrNoSuchIndex};
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::registry::{self, Registry};
+use crate::rocksdb::store::{Batch, Store};
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+// Number of index writes per write batch when reindexing.
+const REINDEX_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    // Index entry pointing at an id that is not in the value index.
+    Orphan {
+        index: String,
+        key: Vec<u8>,
+        id: Vec<u8>,
+    },
+    // Index entry whose entity no longer maps to that key.
+    Stale {
+        index: String,
+        key: Vec<u8>,
+        id: Vec<u8>,
+    },
+    // Entity without its entry in the index.
+    Missing {
+        index: String,
+        id: Vec<u8>,
+    },
+    // Counter that doesn't match what's stored.
+    Counter {
+        key: String,
+        counter: u64,
+        found: u64,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Orphan { index, key, id } => {
+                write!(f, "orphan: index={:?}, key={:?}, id={:?}", index, key, id)
+            }
+            Problem::Stale { index, key, id } => {
+                write!(f, "stale: index={:?}, key={:?}, id={:?}", index, key, id)
+            }
+            Problem::Missing { index, id } => {
+                write!(f, "missing: index={:?}, id={:?}", index, id)
+            }
+            Problem::Counter {
+                key,
+                counter,
+                found,
+            } => write!(
+                f,
+                "counter mismatch: key={:?}, counter={:?}, found={:?}",
+                key, counter, found
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Report {
+    pub entities: u64,
+    pub entries: u64,
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// Does the index have the entry for e?  Keys like names are not unique and
+// the last put takes the key, so a key held by another entity that maps to
+// it is fine.
+pub(crate) fn has_entry<E: Entity>(
+    db: &dyn Store,
+    index: &dyn Index<E>,
+    value_cf: &str,
+    e: &E,
+) -> Result<bool, Box<dyn Error>> {
+    if index.append_if_same_key() {
+        let value = index.key_value(e).1;
+        let prefix = index.append_prefix(e);
+        let mut found = false;
+        db.scan_from(index.cf_name(), &prefix, &mut |k, v| {
+            if !k.starts_with(&prefix) {
+                return false;
+            }
+            found = *v == *value;
+            !found
+        })?;
+        return Ok(found);
+    }
+    for (key, value) in index.key_values(e) {
+        let found = match db.get_value(index.cf_name(), &key)? {
+            None => false,
+            Some(v) if v == value => true,
+            Some(v) => match db.get_value(value_cf, &v)? {
+                Some(bytes) => maps_to(index, &key, &E::from_bytes(&v, &bytes)?),
+                None => false,
+            },
+        };
+        if !found {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// Does the entry at key belong to e?
+fn maps_to<E: Entity>(index: &dyn Index<E>, key: &[u8], e: &E) -> bool {
+    if index.append_if_same_key() {
+        key.starts_with(&index.append_prefix(e))
+    } else {
+        index.key_values(e).iter().any(|kv| kv.0 == key)
+    }
+}
+
+fn secondary_indexes<E: Entity + Indexes<E>>() -> Vec<Box<dyn Index<E>>> {
+    let value_index = E::value_index();
+    E::indexes()
+        .into_iter()
+        .filter(|i| i.cf_name() != value_index.cf_name())
+        .collect()
+}
+
+// Calls f with each entity in the value index of E, in key order, until f
+// returns false or fails.
+#[allow(clippy::type_complexity)]
+fn visit_values<E: Entity + Indexes<E>>(
+    db: &dyn Store,
+    f: &mut dyn FnMut(&[u8], E) -> Result<bool, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut result = Ok(());
+    db.scan_from(
+        E::value_index().cf_name(),
+        &[],
+        &mut |id, bytes| match E::from_bytes(id, bytes).and_then(|e| f(id, e)) {
+            Ok(more) => more,
+            Err(e) => {
+                result = Err(e);
+                false
+            }
+        },
+    )?;
+    result
+}
+
+// Cross checks the secondary indexes of E against its value index.
+pub fn check<E: Entity + Indexes<E>>(
+    db: &dyn Store,
+    report: &mut Report,
+) -> Result<(), Box<dyn Error>> {
+    let value_cf = E::value_index().cf_name();
+    if !db.has_cf(value_cf) {
+        return Err(Box::new(ErrMissingIndex::new(value_cf.to_string())));
+    }
+    let indexes = secondary_indexes::<E>();
+
+    // Every entity has its entries
+    let mut count = 0u64;
+    visit_values::<E>(db, &mut |id, e| {
+        count += 1;
+        for index in indexes.iter() {
+            if !has_entry(db, index.as_ref(), value_cf, &e)? {
+                report.problems.push(Problem::Missing {
+                    index: index.cf_name().to_string(),
+                    id: id.to_vec(),
+                });
+            }
+        }
+        Ok(true)
+    })?;
+    report.entities += count;
+
+    // Every entry points at an entity that maps to it
+    for index in indexes.iter() {
+        let mut result = Ok(());
+        db.scan_from(index.cf_name(), &[], &mut |key, id| {
+            report.entries += 1;
+            let problem = match db.get_value(value_cf, id) {
+                Ok(None) => Some(Problem::Orphan {
+                    index: index.cf_name().to_string(),
+                    key: key.to_vec(),
+                    id: id.to_vec(),
+                }),
+                Ok(Some(bytes)) => match E::from_bytes(id, &bytes) {
+                    Ok(e) if maps_to(index.as_ref(), key, &e) => None,
+                    Ok(_) => Some(Problem::Stale {
+                        index: index.cf_name().to_string(),
+                        key: key.to_vec(),
+                        id: id.to_vec(),
+                    }),
+                    Err(e) => {
+                        result = Err(e);
+                        return false;
+                    }
+                },
+                Err(e) => {
+                    result = Err(e);
+                    return false;
+                }
+            };
+            report.problems.extend(problem);
+            true
+        })?;
+        result?;
+    }
+
+    let counter = db::default_counters(db).get(E::TYPE)?;
+    if counter.get() != count {
+        report.problems.push(Problem::Counter {
+            key: E::TYPE.to_string(),
+            counter: counter.get(),
+            found: count,
+        });
+    }
+    Ok(())
+}
+
+fn check_types(db: &dyn Store, report: &mut Report) -> Result<(), Box<dyn Error>> {
+    let mut found = 0u64;
+    db.scan_from(db::CF_SYSTEM_TYPES, &[], &mut |_, _| {
+        found += 1;
+        true
+    })?;
+    let counter = db::default_counters(db).get(db::COUNT_TYPES)?;
+    if counter.get() != found {
+        report.problems.push(Problem::Counter {
+            key: db::COUNT_TYPES.to_string(),
+            counter: counter.get(),
+            found,
+        });
+    }
+    Ok(())
+}
+
+pub fn fsck(db: &dyn Store) -> Result<Report, Box<dyn Error>> {
+    fsck_with(db, &registry::global())
+}
+
+// Checks every type in the registry.
+pub fn fsck_with(db: &dyn Store, registry: &Registry) -> Result<Report, Box<dyn Error>> {
+    let mut report = Report::default();
+    for t in registry.types() {
+        t.check(db, &mut report)?;
+    }
+    check_types(db, &mut report)?;
+    Ok(report)
+}
+
+// Rebuilds the secondary indexes of E from its value index, either all of
+// them or only the one named.  Entries are added before the ones that don't
+// belong are removed, each in batches, so an index that is stopped part way
+// still has every entry it had.  Where entities share a key, the one last in
+// the value index takes it.  Rebuilding all of them also resets the counter
+// of E.
+pub fn reindex<E: Entity + Indexes<E>>(
+    db: &dyn Store,
+    name: Option<&str>,
+) -> Result<u64, Box<dyn Error>> {
+    let value_cf = E::value_index().cf_name();
+    let targets: Vec<Box<dyn Index<E>>> = secondary_indexes::<E>()
+        .into_iter()
+        .filter(|i| name.is_none_or(|n| n == i.cf_name()))
+        .collect();
+
+    let mut count = 0u64;
+    let mut txn = Batch::default();
+    visit_values::<E>(db, &mut |_, e| {
+        for index in targets.iter() {
+            // Appending twice would leave two entries
+            if index.append_if_same_key() && has_entry(db, index.as_ref(), value_cf, &e)? {
+                continue;
+            }
+            index.update_entry(db, &mut txn, &e)?;
+        }
+        count += 1;
+        if txn.len() >= REINDEX_BATCH_SIZE {
+            db.commit(std::mem::take(&mut txn))?;
+        }
+        Ok(true)
+    })?;
+    db.commit(std::mem::take(&mut txn))?;
+
+    for index in targets.iter() {
+        info!("Removing stale entries from {:?}", index.cf_name());
+        remove_stale(db, index.as_ref(), value_cf)?;
+    }
+
+    if name.is_none() {
+        let mut counters = db::default_counters(db);
+        let mut counter = counters.get(E::TYPE)?;
+        counter.set(count);
+        counters.update(&mut txn, &counter)?;
+    }
+
+    // A rebuilt index needs no backfill
+    for index in targets.iter() {
+        txn.delete(db::CF_SYSTEM, db::backfill_key(index.cf_name()));
+    }
+    db.commit(txn)?;
+    info!("Reindexed {:?} {:?}", count, E::TYPE);
+    Ok(count)
+}
+
+// Deletes the entries of the index that point at no entity or at one that
+// doesn't map to them, and all but the first of the entries appended for
+// the same entity.
+fn remove_stale<E: Entity>(
+    db: &dyn Store,
+    index: &dyn Index<E>,
+    value_cf: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut txn = Batch::default();
+    let mut result = Ok(());
+    // The ids seen under the current append prefix
+    let mut prefix = vec![];
+    let mut seen = HashSet::new();
+    db.scan_from(index.cf_name(), &[], &mut |key, id| {
+        let keep = match db.get_value(value_cf, id) {
+            Ok(None) => Ok(false),
+            Ok(Some(bytes)) => E::from_bytes(id, &bytes).map(|e| {
+                if !maps_to(index, key, &e) {
+                    return false;
+                }
+                if !index.append_if_same_key() {
+                    return true;
+                }
+                let p = index.append_prefix(&e);
+                if p != prefix {
+                    prefix = p;
+                    seen.clear();
+                }
+                seen.insert(id.to_vec())
+            }),
+            Err(e) => Err(e),
+        };
+        match keep {
+            Ok(true) => {}
+            Ok(false) => txn.delete(index.cf_name(), key),
+            Err(e) => result = Err(e),
+        }
+        if result.is_ok() && txn.len() >= REINDEX_BATCH_SIZE {
+            result = db.commit(std::mem::take(&mut txn));
+        }
+        result.is_ok()
+    })?;
+    result?;
+    db.commit(txn)
+}
+
+// Rebuilds the named secondary index, or all of them.
+pub fn reindex_all(db: &dyn Store, name: Option<&str>) -> Result<(), Box<dyn Error>> {
+    reindex_with(db, &registry::global(), name)
+}
+
+// Rebuilds the named secondary index, or all of them, of the registered types.
+pub fn reindex_with(
+    db: &dyn Store,
+    registry: &Registry,
+    name: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(n) = name {
+        match registry.find_index(n) {
+            Some(t) if t.value_cf() != n => {}
+            _ => return Err(Box::new(ErrNoSuchIndex::new(n))),
+        }
+    }
+    for t in registry.types() {
+        t.reindex(db, name)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::error::Error;
+
+use crate::rocksdb::db::{self, Database, HasKey, KeyCodec, OperationsBuilder};
+use crate::rocksdb::fsck::{self, Problem};
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::store::{Batch, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use rocksdb::IteratorMode;
+
+fn put_graph(db: &Database) -> Result<(), Box<dyn Error>> {
+    let mut node_ops = Node::operations(db);
+    let mut edge_ops = Edge::operations(db);
+    for name in ["api", "db", "cache"] {
+        node_ops.put(&mut Node {
+            name: name.into(),
+            type_name: "service".into(),
+            ..Default::default()
+        })?;
+    }
+    edge_ops.put(&mut Edge {
+        head: 1,
+        tail: 2,
+        name: "depends-on".into(),
+        type_name: "depends-on".into(),
+        ..Default::default()
+    })?;
+    Ok(())
+}
+
+#[test]
+fn test_fsck_clean() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    put_graph(&db)?;
+
+    let report = fsck::fsck(&db)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(4, report.entities); // 3 nodes, 1 edge
+    Ok(())
+}
+
+#[test]
+fn test_fsck_orphans_and_reindex() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    put_graph(&db)?;
+
+    // Drop the node value behind the indexes' back
+    let cf = db.cf_handle(node::ById.cf_name()).unwrap();
+    db.delete_cf(cf, Node::id_from(3u64).as_bytes())?;
+
+    let report = fsck::fsck(&db)?;
+    assert!(report.problems.iter().any(|p| matches!(p,
+        Problem::Orphan { index, .. } if index == node::ByName.cf_name())));
+    assert!(report.problems.contains(&Problem::Counter {
+        key: "Node".into(),
+        counter: 3,
+        found: 2,
+    }));
+
+    fsck::reindex_all(&db, None)?;
+    let report = fsck::fsck(&db)?;
+    assert!(report.is_clean(), "{:?}", report.problems);
+    assert_eq!(2, db::default_counters(&db).get("Node")?.get());
+    Ok(())
+}
+
+#[test]
+fn test_fsck_missing_and_reindex_one() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    put_graph(&db)?;
+
+    let cf = db.cf_handle(node::ByName.cf_name()).unwrap();
+    db.delete_cf(cf, b"db")?;
+
+    let report = fsck::fsck(&db)?;
+    assert_eq!(
+        vec![Problem::Missing {
+            index: node::ByName.cf_name().into(),
+            id: 2u64.encode_key(),
+        }],
+        report.problems
+    );
+
+    assert!(fsck::reindex_all(&db, Some("index.node.nope")).is_err());
+    assert!(fsck::reindex_all(&db, Some(node::ById.cf_name())).is_err());
+
+    fsck::reindex_all(&db, Some(node::ByName.cf_name()))?;
+    assert!(fsck::fsck(&db)?.is_clean());
+    Ok(())
+}
+
+#[test]
+fn test_reindex_keeps_entries_and_counter() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    put_graph(&db)?;
+
+    // A stale name entry, and a node counted twice
+    let cf = db.cf_handle(node::ByName.cf_name()).unwrap();
+    db.put_cf(cf, b"gone", 1u64.encode_key())?;
+    let mut counters = db::default_counters(&db);
+    let mut counter = counters.get("Node")?;
+    counter.set(4);
+    let mut txn = Batch::default();
+    counters.update(&mut txn, &counter)?;
+    db.commit(txn)?;
+
+    let hashes = |db: &Database| {
+        let cf = db.cf_handle(node::ByNameHash.cf_name()).unwrap();
+        db.iterator_cf(cf, IteratorMode::Start)
+            .map(|item| item.unwrap().0.to_vec())
+            .collect::<Vec<_>>()
+    };
+    let before = hashes(&db);
+
+    // Appended entries are kept as they are, not appended again
+    fsck::reindex_all(&db, Some(node::ByNameHash.cf_name()))?;
+    assert_eq!(before, hashes(&db));
+
+    fsck::reindex_all(&db, Some(node::ByName.cf_name()))?;
+    assert_eq!(None, db.get_cf(cf, b"gone")?);
+    assert_eq!(4, db::default_counters(&db).get("Node")?.get());
+
+    fsck::reindex_all(&db, None)?;
+    assert_eq!(3, db::default_counters(&db).get("Node")?.get());
+    assert!(fsck::fsck(&db)?.is_clean());
+    Ok(())
+}
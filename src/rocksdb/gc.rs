@@ -0,0 +1,102 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Database, DbInfo, IndexBuilder};
+use crate::rocksdb::error::ErrMissingIndex;
+use crate::rocksdb::store::{Batch, Store};
+
+use rocksdb::DB;
+
+use std::error::Error;
+
+// A column family on disk that nothing declares anymore.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orphan {
+    pub cf_name: String,
+    pub sst_bytes: u64,
+    pub keys: u64,
+}
+
+fn orphan_names(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let want = db::all_column_families(builder);
+    let found = DB::list_cf(&info.options(), info.path())?;
+    Ok(found
+        .into_iter()
+        .filter(|c| c != rocksdb::DEFAULT_COLUMN_FAMILY_NAME && !want.contains(c))
+        .collect())
+}
+
+// Opens the db with every column family on disk, since RocksDB doesn't
+// open a db with some of them left out.  Listing and dropping column
+// families is RocksDB's business, so unlike fsck and backfill this doesn't
+// go through Store.
+fn open_all(info: &dyn DbInfo, builder: &dyn IndexBuilder) -> Result<Database, Box<dyn Error>> {
+    let mut names = db::all_column_families(builder);
+    names.extend(orphan_names(info, builder)?);
+    Ok(DB::open_cf_descriptors(
+        &info.options(),
+        info.path(),
+        db::cf_descriptors(info, names)?,
+    )?)
+}
+
+// Lists the orphaned column families with their sizes.
+pub fn orphans(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+) -> Result<Vec<Orphan>, Box<dyn Error>> {
+    let names = orphan_names(info, builder)?;
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+    let db = open_all(info, builder)?;
+    let mut result = vec![];
+    for cf_name in names {
+        let cf = match db.cf_handle(&cf_name) {
+            Some(cf) => cf,
+            None => return Err(Box::new(ErrMissingIndex::new(cf_name))),
+        };
+        let sst_bytes = db
+            .property_int_value_cf(cf, "rocksdb.total-sst-files-size")?
+            .unwrap_or(0);
+        let keys = db
+            .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?
+            .unwrap_or(0);
+        result.push(Orphan {
+            cf_name,
+            sst_bytes,
+            keys,
+        });
+    }
+    Ok(result)
+}
+
+// Drops the given column families if they are orphaned, along with any
+// backfill pending for them.  Returns the names dropped.
+pub fn drop_orphans(
+    info: &dyn DbInfo,
+    builder: &dyn IndexBuilder,
+    names: &[String],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let targets: Vec<String> = orphan_names(info, builder)?
+        .into_iter()
+        .filter(|c| names.contains(c))
+        .collect();
+    if targets.is_empty() {
+        return Ok(targets);
+    }
+    let mut db = open_all(info, builder)?;
+    for cf_name in targets.iter() {
+        info!("Dropping column family {:?}", cf_name);
+        db.drop_cf(cf_name)?;
+    }
+    let mut txn = Batch::default();
+    for cf_name in targets.iter() {
+        txn.delete(db::CF_SYSTEM, db::backfill_key(cf_name));
+    }
+    db.commit(txn)?;
+    Ok(targets)
+}
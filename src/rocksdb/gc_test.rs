@@ -0,0 +1,40 @@
+use std::error::Error;
+
+use crate::rocksdb::db::{self, DbInfo};
+use crate::rocksdb::gc;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use rocksdb::DB;
+
+#[test]
+fn test_gc() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    {
+        let mut db = db::init(&db_info, &All)?;
+        // An index that has since been removed from the code
+        db.create_cf("index.node.old", &db_info.options())?;
+        let cf = db.cf_handle("index.node.old").unwrap();
+        db.put_cf(cf, b"k", b"v")?;
+        db.flush_cf(cf)?;
+    }
+
+    let orphans = gc::orphans(&db_info, &All)?;
+    assert_eq!(1, orphans.len());
+    assert_eq!("index.node.old", orphans[0].cf_name);
+    assert!(orphans[0].sst_bytes > 0);
+
+    // Only orphans get dropped
+    let dropped = gc::drop_orphans(
+        &db_info,
+        &All,
+        &["index.node.old".to_string(), "index.node.name".to_string()],
+    )?;
+    assert_eq!(vec!["index.node.old".to_string()], dropped);
+
+    let found = DB::list_cf(&db_info.options(), db_info.path())?;
+    assert!(!found.contains(&"index.node.old".to_string()));
+    assert!(found.contains(&"index.node.name".to_string()));
+    assert!(gc::orphans(&db_info, &All)?.is_empty());
+    db::open_db(&db_info, &All)?;
+    Ok(())
+}
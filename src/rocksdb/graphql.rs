@@ -0,0 +1,373 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::{self, AttrKey};
+use crate::rocksdb::db::{HasKey, OperationsBuilder, Visitor};
+use crate::rocksdb::edge;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::store::Store;
+use crate::rocksdb::value::Value;
+
+use async_graphql::connection::Connection;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, Schema, ID};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use std::error::Error;
+use std::sync::Arc;
+
+// Page size when first is not given, and the most a page can have.
+pub static DEFAULT_PAGE: usize = 20;
+pub static MAX_PAGE: usize = 100;
+
+pub type GraphSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// The store the resolvers read from.
+pub type Db = Arc<dyn Store + Send + Sync>;
+
+// A read only schema over the nodes, edges and their attributes.  Lists are
+// relay style connections in key order; a cursor is the id of the last item
+// seen, and the next page starts after it.
+pub fn schema(db: Db) -> GraphSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(db)
+        .finish()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<NodeObject>> {
+        Ok(get_node(db(ctx)?, parse_id(&id)?)?.map(NodeObject))
+    }
+
+    /// Nodes with the name and type, if given.
+    async fn nodes(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+        #[graphql(name = "type")] type_name: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<u64, NodeObject>> {
+        let db = db(ctx)?;
+        let (first, after) = (page_size(first)?, parse_cursor(after)?);
+        let keep = |n: &Node| type_name.as_ref().is_none_or(|t| *t == n.type_name);
+        let ops = Node::operations(db.as_ref());
+        let nodes = match name {
+            Some(name) => {
+                // The name index only has the last node with a name.
+                let mut nodes = vec![];
+                let probe = Node {
+                    name,
+                    ..Default::default()
+                };
+                ops.scan(
+                    node::ByNameHash.cf_name(),
+                    node::ByNameHash.append_prefix(&probe),
+                    Box::new(Page::new(&mut nodes, usize::MAX, &keep)),
+                )
+                .map_err(fail)?;
+                nodes.retain(|n| n.name == probe.name);
+                after_cursor(nodes, after, |n| n.id)
+            }
+            None => {
+                let mut nodes = vec![];
+                ops.visit(
+                    Node::id_from(after.unwrap_or(0)),
+                    Box::new(Page::new(&mut nodes, first + 1, &|n: &Node| {
+                        Some(n.id) != after && keep(n)
+                    })),
+                )
+                .map_err(fail)?;
+                nodes
+            }
+        };
+        Ok(connection(nodes, after, first, |n| n.id, NodeObject))
+    }
+
+    async fn edge(&self, ctx: &Context<'_>, id: ID) -> Result<Option<EdgeObject>> {
+        Ok(get_edge(db(ctx)?, parse_id(&id)?)?.map(EdgeObject))
+    }
+
+    /// Edges with the name and type, if given.
+    async fn edges(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+        #[graphql(name = "type")] type_name: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<u64, EdgeObject>> {
+        let db = db(ctx)?;
+        let (first, after) = (page_size(first)?, parse_cursor(after)?);
+        let mut edges = vec![];
+        Edge::operations(db.as_ref())
+            .visit(
+                Edge::id_from(after.unwrap_or(0)),
+                Box::new(Page::new(&mut edges, first + 1, &|e: &Edge| {
+                    Some(e.id) != after && edge_matches(e, &name, &type_name)
+                })),
+            )
+            .map_err(fail)?;
+        Ok(connection(edges, after, first, |e| e.id, EdgeObject))
+    }
+}
+
+pub struct NodeObject(Node);
+
+#[Object(name = "Node")]
+impl NodeObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    #[graphql(name = "type")]
+    async fn type_name(&self) -> &str {
+        &self.0.type_name
+    }
+
+    async fn attributes(&self, ctx: &Context<'_>) -> Result<Vec<AttributeObject>> {
+        attributes(db(ctx)?, self.0.id)
+    }
+
+    async fn attribute(&self, ctx: &Context<'_>, name: String) -> Result<Option<AttributeObject>> {
+        get_attribute(db(ctx)?, self.0.id, name)
+    }
+
+    /// Edges with this node as the head.
+    async fn out(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+        #[graphql(name = "type")] type_name: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<u64, EdgeObject>> {
+        let index = edge::ByHeadTail.cf_name();
+        node_edges(ctx, index, self.0.id, name, type_name, first, after)
+    }
+
+    /// Edges with this node as the tail.
+    #[graphql(name = "in")]
+    async fn in_edges(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+        #[graphql(name = "type")] type_name: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<u64, EdgeObject>> {
+        let index = edge::ByTailHead.cf_name();
+        node_edges(ctx, index, self.0.id, name, type_name, first, after)
+    }
+}
+
+pub struct EdgeObject(Edge);
+
+#[Object(name = "Edge")]
+impl EdgeObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    #[graphql(name = "type")]
+    async fn type_name(&self) -> &str {
+        &self.0.type_name
+    }
+
+    async fn head(&self, ctx: &Context<'_>) -> Result<Option<NodeObject>> {
+        Ok(get_node(db(ctx)?, self.0.head)?.map(NodeObject))
+    }
+
+    async fn tail(&self, ctx: &Context<'_>) -> Result<Option<NodeObject>> {
+        Ok(get_node(db(ctx)?, self.0.tail)?.map(NodeObject))
+    }
+
+    async fn attributes(&self, ctx: &Context<'_>) -> Result<Vec<AttributeObject>> {
+        attributes(db(ctx)?, self.0.id)
+    }
+
+    async fn attribute(&self, ctx: &Context<'_>, name: String) -> Result<Option<AttributeObject>> {
+        get_attribute(db(ctx)?, self.0.id, name)
+    }
+}
+
+pub struct AttributeObject(Attribute);
+
+#[Object(name = "Attribute")]
+impl AttributeObject {
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn content_type(&self) -> &str {
+        &self.0.content_type
+    }
+
+    /// Typed values as text, timestamps in RFC 3339; other content as UTF-8.
+    async fn value(&self) -> String {
+        match self.0.value() {
+            Some(Value::String(s)) => s,
+            Some(Value::Timestamp(t)) => OffsetDateTime::from_unix_timestamp_nanos(t)
+                .ok()
+                .and_then(|t| t.format(&Rfc3339).ok())
+                .unwrap_or_else(|| t.to_string()),
+            Some(v) => v.to_string(),
+            None => String::from_utf8_lossy(&self.0.content).to_string(),
+        }
+    }
+}
+
+fn db<'a>(ctx: &Context<'a>) -> Result<&'a Db> {
+    ctx.data::<Db>()
+}
+
+fn fail(e: Box<dyn Error>) -> async_graphql::Error {
+    e.to_string().into()
+}
+
+fn parse_id(id: &ID) -> Result<u64> {
+    id.parse::<u64>()
+        .map_err(|_| format!("Bad id {:?}", id.as_str()).into())
+}
+
+fn parse_cursor(after: Option<String>) -> Result<Option<u64>> {
+    match after {
+        None => Ok(None),
+        Some(s) => s
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| format!("Bad cursor {:?}", s).into()),
+    }
+}
+
+fn page_size(first: Option<i32>) -> Result<usize> {
+    match first {
+        None => Ok(DEFAULT_PAGE),
+        Some(n) if n >= 0 && n as usize <= MAX_PAGE => Ok(n as usize),
+        Some(n) => Err(format!("first must be 0 to {}, not {}", MAX_PAGE, n).into()),
+    }
+}
+
+fn get_node(db: &Db, id: u64) -> Result<Option<Node>> {
+    Node::operations(db.as_ref())
+        .get(Node::id_from(id))
+        .map_err(fail)
+}
+
+fn get_edge(db: &Db, id: u64) -> Result<Option<Edge>> {
+    Edge::operations(db.as_ref())
+        .get(Edge::id_from(id))
+        .map_err(fail)
+}
+
+fn attributes(db: &Db, parent_id: u64) -> Result<Vec<AttributeObject>> {
+    let mut found = vec![];
+    attribute::visit_parent(
+        db.as_ref(),
+        parent_id,
+        Box::new(Page::new(&mut found, usize::MAX, &|_| true)),
+    )
+    .map_err(fail)?;
+    Ok(found.into_iter().map(AttributeObject).collect())
+}
+
+fn get_attribute(db: &Db, parent_id: u64, name: String) -> Result<Option<AttributeObject>> {
+    let found = Attribute::operations(db.as_ref())
+        .get(Attribute::id_from(AttrKey { parent_id, name }))
+        .map_err(fail)?;
+    Ok(found.map(AttributeObject))
+}
+
+fn edge_matches(e: &Edge, name: &Option<String>, type_name: &Option<String>) -> bool {
+    name.as_ref().is_none_or(|n| *n == e.name)
+        && type_name.as_ref().is_none_or(|t| *t == e.type_name)
+}
+
+// The edges of a node in the head-tail or tail-head index.
+fn node_edges(
+    ctx: &Context<'_>,
+    index: &str,
+    id: u64,
+    name: Option<String>,
+    type_name: Option<String>,
+    first: Option<i32>,
+    after: Option<String>,
+) -> Result<Connection<u64, EdgeObject>> {
+    let db = db(ctx)?;
+    let (first, after) = (page_size(first)?, parse_cursor(after)?);
+    let mut edges = vec![];
+    Edge::operations(db.as_ref())
+        .scan(
+            index,
+            id.to_le_bytes().to_vec(),
+            Box::new(Page::new(&mut edges, usize::MAX, &|e: &Edge| {
+                edge_matches(e, &name, &type_name)
+            })),
+        )
+        .map_err(fail)?;
+    let edges = after_cursor(edges, after, |e| e.id);
+    Ok(connection(edges, after, first, |e| e.id, EdgeObject))
+}
+
+// Sorted in key order, from after the cursor.
+fn after_cursor<T>(mut items: Vec<T>, after: Option<u64>, id: fn(&T) -> u64) -> Vec<T> {
+    items.sort_by_key(|t| id(t).to_le_bytes());
+    if let Some(after) = after {
+        items.retain(|t| id(t).to_le_bytes() > after.to_le_bytes());
+    }
+    items
+}
+
+// The first items as a page; there is a next page if there are more.
+fn connection<T, O: async_graphql::OutputType>(
+    mut items: Vec<T>,
+    after: Option<u64>,
+    first: usize,
+    id: fn(&T) -> u64,
+    object: fn(T) -> O,
+) -> Connection<u64, O> {
+    let has_next = items.len() > first;
+    items.truncate(first);
+    let mut page = Connection::new(after.is_some(), has_next);
+    page.edges.extend(
+        items
+            .into_iter()
+            .map(|t| async_graphql::connection::Edge::new(id(&t), object(t))),
+    );
+    page
+}
+
+// Collects up to max of the entities kept.
+struct Page<'a, E> {
+    list: &'a mut Vec<E>,
+    max: usize,
+    keep: &'a dyn Fn(&E) -> bool,
+}
+
+impl<'a, E> Page<'a, E> {
+    fn new(list: &'a mut Vec<E>, max: usize, keep: &'a dyn Fn(&E) -> bool) -> Self {
+        Page { list, max, keep }
+    }
+}
+
+impl<E> Visitor<E> for Page<'_, E> {
+    fn visit(&mut self, e: E) -> bool {
+        if (self.keep)(&e) {
+            self.list.push(e);
+        }
+        self.list.len() < self.max
+    }
+}
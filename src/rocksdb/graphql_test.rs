@@ -0,0 +1,181 @@
+use serde_json::{json, Value as Json};
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::rocksdb::db::OperationsBuilder;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::graphql::{self, Db, GraphSchema};
+use crate::rocksdb::store::MemStore;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+// api -> db, api -> cache, cache -> db, and a second node named api.
+fn build() -> Result<GraphSchema, Box<dyn Error>> {
+    let db: Db = Arc::new(MemStore::new(&All));
+    let mut ids = vec![];
+    let mut nodes = Node::operations(db.as_ref());
+    for (name, type_name) in [
+        ("api", "service"),
+        ("db", "database"),
+        ("cache", "service"),
+        ("api", "team"),
+    ] {
+        let mut n = Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        };
+        nodes.put(&mut n)?;
+        ids.push(n.id);
+    }
+    let mut edges = Edge::operations(db.as_ref());
+    for (name, head, tail) in [("depends-on", 0, 1), ("depends-on", 0, 2), ("reads", 2, 1)] {
+        edges.put(&mut Edge {
+            name: name.into(),
+            type_name: name.into(),
+            head: ids[head],
+            tail: ids[tail],
+            ..Default::default()
+        })?;
+    }
+    let v = Value::Int(8080);
+    Attribute::operations(db.as_ref()).put(&mut Attribute {
+        parent_id: ids[0],
+        name: "port".into(),
+        content: v.content(),
+        content_type: v.content_type().into(),
+        ..Default::default()
+    })?;
+    Ok(graphql::schema(db.clone()))
+}
+
+async fn run(schema: &GraphSchema, query: &str) -> Json {
+    let response = schema.execute(query).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    response.data.into_json().unwrap()
+}
+
+#[tokio::test]
+async fn test_nested() -> Result<(), Box<dyn Error>> {
+    let schema = build()?;
+    let data = run(
+        &schema,
+        r#"{ nodes(name: "api", type: "service") { edges { node {
+            name type
+            attribute(name: "port") { contentType value }
+            out { edges { node { type head { name } tail { name in { edges { node { head { name } } } } } } } }
+        } } } }"#,
+    )
+    .await;
+    let api = &data["nodes"]["edges"][0]["node"];
+    assert_eq!(1, data["nodes"]["edges"].as_array().unwrap().len());
+    assert_eq!(
+        json!({"contentType": "int", "value": "8080"}),
+        api["attribute"]
+    );
+    let out: Vec<&str> = api["out"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["node"]["tail"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["db", "cache"], out);
+    // Edges into db: from api and cache
+    let into_db = &api["out"]["edges"][0]["node"]["tail"]["in"]["edges"];
+    assert_eq!(2, into_db.as_array().unwrap().len());
+
+    // Both nodes named api, even though the name index only has one
+    let data = run(
+        &schema,
+        r#"{ nodes(name: "api") { edges { node { type } } } }"#,
+    )
+    .await;
+    assert_eq!(2, data["nodes"]["edges"].as_array().unwrap().len());
+
+    let data = run(
+        &schema,
+        r#"{ edges(type: "reads") { edges { node { head { name } } } } }"#,
+    )
+    .await;
+    assert_eq!(
+        json!([{"node": {"head": {"name": "cache"}}}]),
+        data["edges"]["edges"]
+    );
+
+    let data = run(
+        &schema,
+        r#"{ node(id: "2") { name attributes { name } } edge(id: "99") { name } }"#,
+    )
+    .await;
+    assert_eq!(json!({"name": "db", "attributes": []}), data["node"]);
+    assert_eq!(Json::Null, data["edge"]);
+
+    let errors = schema.execute(r#"{ node(id: "x") { name } }"#).await.errors;
+    assert_eq!(1, errors.len());
+    let errors = schema
+        .execute("{ nodes(first: 1000) { edges { cursor } } }")
+        .await
+        .errors;
+    assert_eq!(1, errors.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pages() -> Result<(), Box<dyn Error>> {
+    let schema = build()?;
+    let mut names = vec![];
+    let mut after = Json::Null;
+    loop {
+        let data = run(
+            &schema,
+            &format!(
+                "{{ nodes(first: 3, after: {}) {{ edges {{ cursor node {{ name }} }} pageInfo {{ hasNextPage endCursor }} }} }}",
+                after
+            ),
+        )
+        .await;
+        let page = &data["nodes"];
+        for e in page["edges"].as_array().unwrap() {
+            names.push(e["node"]["name"].as_str().unwrap().to_string());
+        }
+        if !page["pageInfo"]["hasNextPage"].as_bool().unwrap() {
+            break;
+        }
+        after = page["pageInfo"]["endCursor"].clone();
+    }
+    assert_eq!(vec!["api", "db", "cache", "api"], names);
+
+    // Edges of a node page the same way
+    let data = run(
+        &schema,
+        r#"{ node(id: "1") { out(first: 1) { edges { cursor node { id } } pageInfo { hasNextPage } } } }"#,
+    )
+    .await;
+    let out = &data["node"]["out"];
+    assert_eq!(true, out["pageInfo"]["hasNextPage"]);
+    let cursor = out["edges"][0]["cursor"].as_str().unwrap();
+    let data = run(
+        &schema,
+        &format!(
+            r#"{{ node(id: "1") {{ out(first: 1, after: "{}") {{ edges {{ node {{ id }} }} pageInfo {{ hasNextPage }} }} }} }}"#,
+            cursor
+        ),
+    )
+    .await;
+    let next = &data["node"]["out"];
+    assert_eq!(false, next["pageInfo"]["hasNextPage"]);
+    assert_ne!(
+        out["edges"][0]["node"]["id"],
+        next["edges"][0]["node"]["id"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_schema() -> Result<(), Box<dyn Error>> {
+    let sdl = build()?.sdl();
+    for t in ["type Node", "type Edge", "type Attribute", "NodeConnection"] {
+        assert!(sdl.contains(t), "{}", sdl);
+    }
+    Ok(())
+}
@@ -1,15 +1,67 @@
 #[allow(unused_imports)]
 use tracing::{debug, error, info, trace, warn};
 
-use crate::rocksdb::db::{Database, Entity, Transaction};
+use crate::rocksdb::db::Entity;
 use crate::rocksdb::error::ErrMissingIndex;
-use rocksdb::{Direction, IteratorMode};
+use crate::rocksdb::store::{Batch, Store};
 
 use std::error::Error;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+// Bytes of a field in an index key, the same as the hand written indexes
+// use: integers little endian, strings and bytes as is.  Used by the keys
+// that #[derive(GraphEntity)] generates.
+pub trait KeyBytes {
+    fn key_bytes(&self) -> Vec<u8>;
+}
+
+impl KeyBytes for u64 {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl KeyBytes for i64 {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl KeyBytes for u32 {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl KeyBytes for i32 {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl KeyBytes for bool {
+    fn key_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+
+impl KeyBytes for String {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl KeyBytes for Vec<u8> {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
 pub trait Indexes<E: Entity> {
     fn indexes() -> Vec<Box<dyn Index<E>>>;
+
+    // The index storing (id, value); the other indexes are derived from it.
+    fn value_index() -> Box<dyn Index<E>>;
 }
 
 pub trait Index<E: Entity> {
@@ -19,112 +71,108 @@ pub trait Index<E: Entity> {
     // Returns the (key, value) for the index
     fn key_value(&self, e: &E) -> (Vec<u8>, Vec<u8>);
 
+    // All the (key, value) pairs for the index.  An entity with several keys
+    // in the index, like one per element of an array, returns each of them.
+    fn key_values(&self, e: &E) -> Vec<(Vec<u8>, Vec<u8>)> {
+        vec![self.key_value(e)]
+    }
+
     // Appends instead of replacing the existing key-value pair by constructing a new key with a timestamp; overwrites otherwise.
     fn append_if_same_key(&self) -> bool {
         false
     }
 
-    fn update_entry(
-        &self,
-        db: &Database,
-        txn: &mut Transaction,
-        e: &E,
-    ) -> Result<(), Box<dyn Error>> {
-        match db.cf_handle(self.cf_name()) {
-            Some(cf) => {
-                let kv = self.key_value(e);
-                if self.append_if_same_key() {
-                    // get current timestamp in nanoseconds
-                    let timestamp = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_nanos();
-                    // compute the new key with timestamp
-                    let new_key = format!("{}:{}", String::from_utf8_lossy(&kv.0), timestamp);
-                    trace!(
-                        "Append entry in index {:?}, (k,v) = ({:?},{:?})",
-                        self.cf_name(),
-                        new_key,
-                        kv.1
-                    );
-                    txn.put_cf(cf, new_key, kv.1);
-                } else {
-                    trace!(
-                        "Update entry in index {:?}, (k,v) = ({:?},{:?})",
-                        self.cf_name(),
-                        kv.0,
-                        kv.1
-                    );
-                    txn.put_cf(cf, kv.0, kv.1);
-                }
-                Ok(())
-            }
-            None => {
-                trace!("Column family not found: {:?}", self.cf_name());
-                Err(Box::new(ErrMissingIndex::new(self.cf_name().to_string())))
-            }
+    // Returns the (key, value) as stored in the column family.  Appending
+    // indexes get a timestamp suffix on the key.
+    fn entry(&self, e: &E) -> (Vec<u8>, Vec<u8>) {
+        let kv = self.key_value(e);
+        if self.append_if_same_key() {
+            // get current timestamp in nanoseconds
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            // compute the new key with timestamp
+            let mut new_key = self.append_prefix(e);
+            new_key.extend(timestamp.to_string().into_bytes());
+            (new_key, kv.1)
+        } else {
+            kv
         }
     }
-    fn delete_entry(
-        &self,
-        db: &Database,
-        txn: &mut Transaction,
-        e: &E,
-    ) -> Result<(), Box<dyn Error>> {
-        match db.cf_handle(self.cf_name()) {
-            Some(cf) => {
-                if self.append_if_same_key() {
-                    // if append only then delete all entries with the same prefix
-                    let kv = self.key_value(e);
-                    let match_key_string = format!("{}:", String::from_utf8_lossy(&kv.0));
-                    let match_key = match_key_string.as_bytes();
-                    trace!(
-                        "Delete entry in index {:?}, key = {:?}",
-                        self.cf_name(),
-                        match_key,
-                    );
-
-                    let iter =
-                        db.iterator_cf(cf, IteratorMode::From(match_key, Direction::Forward));
-                    let mut target_keys = Vec::<Vec<u8>>::new();
-                    for item in iter {
-                        let (k, v) = item.unwrap();
-                        if v.len() == 0 {
-                            break;
-                        }
-                        trace!("For match={:?}, (k,v)={:?} | {:?}", match_key, k, v);
-                        // The first bytes must match
-                        if k.len() < match_key.len() {
-                            break;
-                        } else if match_key.to_owned() != k[0..match_key.len()] {
-                            break;
-                        }
-                        target_keys.push(k.to_vec());
-                    }
-                    for k in target_keys {
-                        trace!(
-                            "Scheduled for deletion in index {:?}, key = {:?}",
-                            self.cf_name(),
-                            k,
-                        );
-                        txn.delete_cf(cf, k);
-                    }
-                    Ok(())
-                } else {
-                    let kv = self.key_value(e);
-                    trace!(
-                        "Delete entry in index {:?}, key = {:?}",
-                        self.cf_name(),
-                        kv.0,
-                    );
-                    txn.delete_cf(cf, kv.0);
-                    Ok(())
+
+    // Prefix shared by all the entries appended under the same key.
+    fn append_prefix(&self, e: &E) -> Vec<u8> {
+        let kv = self.key_value(e);
+        format!("{}:", String::from_utf8_lossy(&kv.0)).into_bytes()
+    }
+
+    fn update_entry(&self, db: &dyn Store, txn: &mut Batch, e: &E) -> Result<(), Box<dyn Error>> {
+        if !db.has_cf(self.cf_name()) {
+            trace!("Column family not found: {:?}", self.cf_name());
+            return Err(Box::new(ErrMissingIndex::new(self.cf_name().to_string())));
+        }
+        let kvs = match self.append_if_same_key() {
+            true => vec![self.entry(e)],
+            false => self.key_values(e),
+        };
+        for kv in kvs {
+            trace!(
+                "Update entry in index {:?}, (k,v) = ({:?},{:?})",
+                self.cf_name(),
+                kv.0,
+                kv.1
+            );
+            txn.put(self.cf_name(), kv.0, kv.1);
+        }
+        Ok(())
+    }
+    fn delete_entry(&self, db: &dyn Store, txn: &mut Batch, e: &E) -> Result<(), Box<dyn Error>> {
+        if !db.has_cf(self.cf_name()) {
+            trace!("Column family not found: {:?}", self.cf_name());
+            return Err(Box::new(ErrMissingIndex::new(self.cf_name().to_string())));
+        }
+        if self.append_if_same_key() {
+            // if append only then delete all entries with the same prefix
+            let match_key_bytes = self.append_prefix(e);
+            let match_key = match_key_bytes.as_slice();
+            trace!(
+                "Delete entry in index {:?}, key = {:?}",
+                self.cf_name(),
+                match_key,
+            );
+
+            let mut target_keys = Vec::<Vec<u8>>::new();
+            db.scan_from(self.cf_name(), match_key, &mut |k, v| {
+                if v.is_empty() {
+                    return false;
+                }
+                trace!("For match={:?}, (k,v)={:?} | {:?}", match_key, k, v);
+                // The first bytes must match
+                if !k.starts_with(match_key) {
+                    return false;
                 }
+                target_keys.push(k.to_vec());
+                true
+            })?;
+            for k in target_keys {
+                trace!(
+                    "Scheduled for deletion in index {:?}, key = {:?}",
+                    self.cf_name(),
+                    k,
+                );
+                txn.delete(self.cf_name(), k);
             }
-            None => {
-                trace!("Column family not found: {:?}", self.cf_name());
-                Err(Box::new(ErrMissingIndex::new(self.cf_name().to_string())))
+        } else {
+            for kv in self.key_values(e) {
+                trace!(
+                    "Delete entry in index {:?}, key = {:?}",
+                    self.cf_name(),
+                    kv.0,
+                );
+                txn.delete(self.cf_name(), kv.0);
             }
         }
+        Ok(())
     }
 }
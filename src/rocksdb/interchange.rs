@@ -0,0 +1,700 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::db::{self, Collect, HasKey, OperationsBuilder};
+use crate::rocksdb::error::ErrBadXml;
+use crate::rocksdb::export::{self, Counts, Ids};
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::store::Store;
+use crate::rocksdb::value::{self, Value};
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+// XML formats of graph tools like Gephi and yEd.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XmlFormat {
+    Graphml,
+    Gexf,
+}
+
+// An attribute name and type, declared once per file: a GraphML <key> or a
+// GEXF <attribute>.  Names and types are keys too, without a content type.
+#[derive(Debug, Clone, PartialEq)]
+struct Key {
+    id: String,
+    edge: bool,
+    title: String,
+    // The Attribute.content_type, if the file has it
+    content_type: Option<String>,
+    xml_type: String,
+}
+
+// A node or edge as read from, or written to, either format.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Item {
+    id: String,
+    head: String,
+    tail: String,
+    name: Option<String>,
+    type_name: Option<String>,
+    // Key id and text of the attributes
+    values: Vec<(String, String)>,
+    // Byte offset in the file, for errors
+    offset: u64,
+}
+
+#[derive(Debug, Default)]
+struct Doc {
+    keys: Vec<Key>,
+    nodes: Vec<Item>,
+    edges: Vec<Item>,
+}
+
+// Writes the nodes and edges with their typed attributes.  Type codes and
+// ts_nano are not written, and untyped attributes are skipped.
+pub fn export_xml(
+    db: &dyn Store,
+    format: XmlFormat,
+    out: &mut dyn Write,
+) -> Result<Counts, Box<dyn Error>> {
+    let doc = read_db(db)?;
+    match format {
+        XmlFormat::Graphml => write_graphml(&doc, out)?,
+        XmlFormat::Gexf => write_gexf(&doc, out)?,
+    }
+    Ok(Counts {
+        nodes: doc.nodes.len() as u64,
+        edges: doc.edges.len() as u64,
+        attributes: doc
+            .nodes
+            .iter()
+            .chain(doc.edges.iter())
+            .map(|i| i.values.len() as u64)
+            .sum(),
+        ..Default::default()
+    })
+}
+
+// Reads a file from this or another tool.  Type names are looked up in the
+// types table, adding the new ones in name order; ids are remapped or kept
+// like import_jsonl.  Nodes without a name are named by their id, and edges
+// by their type.
+pub fn import_xml(
+    db: &dyn Store,
+    format: XmlFormat,
+    input: &mut dyn BufRead,
+    ids: Ids,
+) -> Result<Counts, Box<dyn Error>> {
+    let root = parse(input)?;
+    let doc = match format {
+        XmlFormat::Graphml => read_graphml(&root)?,
+        XmlFormat::Gexf => read_gexf(&root)?,
+    };
+    let mut counts = Counts::default();
+
+    let types: BTreeSet<String> = doc
+        .nodes
+        .iter()
+        .map(node_type)
+        .chain(doc.edges.iter().map(edge_type))
+        .collect();
+    for t in types.iter() {
+        db::type_code(db, t)?;
+    }
+    counts.symbols = types.len() as u64;
+
+    let keys: HashMap<(bool, &str), &Key> = doc
+        .keys
+        .iter()
+        .map(|k| ((k.edge, k.id.as_str()), k))
+        .collect();
+    // File id to db id
+    let mut remap: HashMap<&str, u64> = HashMap::new();
+    let mut nodes = Node::operations(db);
+    let mut edges = Edge::operations(db);
+    let mut attributes = Attribute::operations(db);
+
+    let mut put_values = |parent_id: u64, item: &Item, edge: bool| -> Result<u64, Box<dyn Error>> {
+        for (key, text) in item.values.iter() {
+            let (name, content_type) = match keys.get(&(edge, key.as_str())) {
+                Some(k) => (k.title.as_str(), content_type(k)),
+                None => (key.as_str(), value::STRING),
+            };
+            let v = Value::parse(content_type, text)
+                .map_err(|e| ErrBadXml::new(item.offset, &format!("{:?}: {}", name, e)))?;
+            attributes.put(&mut Attribute::typed(parent_id, name, &v))?;
+        }
+        Ok(item.values.len() as u64)
+    };
+    let in_use = |id: u64| -> Result<bool, Box<dyn Error>> {
+        Ok(Node::operations(db).get(Node::id_from(id))?.is_some()
+            || Edge::operations(db).get(Edge::id_from(id))?.is_some())
+    };
+
+    for item in doc.nodes.iter() {
+        let mut node = Node {
+            name: item.name.clone().unwrap_or_else(|| item.id.clone()),
+            type_name: node_type(item),
+            ..Default::default()
+        };
+        if ids == Ids::Preserve {
+            node.id = number(item, &item.id, "n")?;
+            if in_use(node.id)? {
+                return Err(bad(item, &format!("id {} is in use", node.id)));
+            }
+            export::bump_sequence(db, node.id)?;
+        }
+        nodes.put(&mut node)?;
+        remap.insert(&item.id, node.id);
+        counts.nodes += 1;
+        counts.attributes += put_values(node.id, item, false)?;
+    }
+    let resolve = |item: &Item, id: &str| match (ids, remap.get(id)) {
+        (_, Some(id)) => Ok(*id),
+        (Ids::Preserve, None) => number(item, id, "n"),
+        (Ids::Remap, None) => Err(bad(item, &format!("no node {:?}", id))),
+    };
+    for item in doc.edges.iter() {
+        let mut edge = Edge {
+            name: item.name.clone().unwrap_or_else(|| edge_type(item)),
+            type_name: edge_type(item),
+            head: resolve(item, &item.head)?,
+            tail: resolve(item, &item.tail)?,
+            ..Default::default()
+        };
+        if ids == Ids::Preserve {
+            edge.id = number(item, &item.id, "e")?;
+            if in_use(edge.id)? {
+                return Err(bad(item, &format!("id {} is in use", edge.id)));
+            }
+            export::bump_sequence(db, edge.id)?;
+        }
+        edges.put(&mut edge)?;
+        counts.edges += 1;
+        counts.attributes += put_values(edge.id, item, true)?;
+    }
+    Ok(counts)
+}
+
+fn node_type(item: &Item) -> String {
+    item.type_name.clone().unwrap_or_else(|| "entity".into())
+}
+
+fn edge_type(item: &Item) -> String {
+    item.type_name
+        .clone()
+        .or_else(|| item.name.clone())
+        .unwrap_or_else(|| "edge".into())
+}
+
+fn bad(item: &Item, reason: &str) -> Box<dyn Error> {
+    Box::new(ErrBadXml::new(item.offset, reason))
+}
+
+// The id as a number, with or without the prefix GraphML ids get.
+fn number(item: &Item, id: &str, prefix: &str) -> Result<u64, Box<dyn Error>> {
+    id.strip_prefix(prefix)
+        .unwrap_or(id)
+        .parse()
+        .map_err(|_| bad(item, &format!("id {:?} is not a number", id)))
+}
+
+// The attribute type in both formats.  Timestamps are nanoseconds.
+fn xml_type(content_type: &str) -> &'static str {
+    match content_type {
+        t if t == value::INT || t == value::TIMESTAMP => "long",
+        t if t == value::FLOAT => "double",
+        t if t == value::BOOL => "boolean",
+        _ => "string",
+    }
+}
+
+fn content_type(key: &Key) -> &str {
+    if let Some(t) = key.content_type.as_deref() {
+        return t;
+    }
+    match key.xml_type.as_str() {
+        "int" | "integer" | "long" => value::INT,
+        "float" | "double" => value::FLOAT,
+        "boolean" => value::BOOL,
+        _ => value::STRING,
+    }
+}
+
+// Text that Value::parse reads back.
+fn text(v: &Value) -> String {
+    match v {
+        Value::Float(v) => format!("{:?}", v),
+        Value::String(v) => v.clone(),
+        Value::Int(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::Timestamp(v) => v.to_string(),
+    }
+}
+
+fn read_db(db: &dyn Store) -> Result<Doc, Box<dyn Error>> {
+    let mut nodes = vec![];
+    Node::operations(db).visit(Node::id_from(0), Box::new(Collect(&mut nodes)))?;
+    let mut edges = vec![];
+    Edge::operations(db).visit(Edge::id_from(0), Box::new(Collect(&mut edges)))?;
+    let mut attributes = vec![];
+    Attribute::operations(db).visit(
+        Attribute::id_from(AttrKey {
+            parent_id: 0,
+            name: String::new(),
+        }),
+        Box::new(Collect(&mut attributes)),
+    )?;
+    let mut by_parent: HashMap<u64, Vec<Attribute>> = HashMap::new();
+    for a in attributes {
+        by_parent.entry(a.parent_id).or_default().push(a);
+    }
+
+    let mut doc = Doc::default();
+    let mut key_ids: HashMap<(bool, String, String), String> = HashMap::new();
+    let mut item = |id: u64, edge: bool| -> Item {
+        let mut item = Item {
+            id: id.to_string(),
+            ..Default::default()
+        };
+        for a in by_parent.remove(&id).unwrap_or_default() {
+            let v = match Value::from_content(&a.content_type, &a.content) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("Skipped attribute {:?} of {}: {}", a.name, id, e);
+                    continue;
+                }
+            };
+            let next = format!("k{}", key_ids.len());
+            let key = key_ids
+                .entry((edge, a.name.clone(), a.content_type.clone()))
+                .or_insert_with(|| {
+                    doc.keys.push(Key {
+                        id: next.clone(),
+                        edge,
+                        title: a.name.clone(),
+                        content_type: Some(a.content_type.clone()),
+                        xml_type: xml_type(&a.content_type).into(),
+                    });
+                    next
+                });
+            item.values.push((key.clone(), text(&v)));
+        }
+        item
+    };
+    let nodes: Vec<Item> = nodes
+        .into_iter()
+        .map(|n| Item {
+            name: Some(n.name),
+            type_name: Some(n.type_name),
+            ..item(n.id, false)
+        })
+        .collect();
+    let edges: Vec<Item> = edges
+        .into_iter()
+        .map(|e| Item {
+            head: e.head.to_string(),
+            tail: e.tail.to_string(),
+            name: Some(e.name),
+            type_name: Some(e.type_name),
+            ..item(e.id, true)
+        })
+        .collect();
+    doc.nodes = nodes;
+    doc.edges = edges;
+    Ok(doc)
+}
+
+fn key_attrs(k: &Key) -> String {
+    match &k.content_type {
+        Some(t) => format!(" content.type=\"{}\"", escape(t.as_str())),
+        None => String::new(),
+    }
+}
+
+fn write_graphml(doc: &Doc, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    for name in ["name", "type"] {
+        writeln!(
+            out,
+            r#"  <key id="{0}" for="all" attr.name="{0}" attr.type="string"/>"#,
+            name
+        )?;
+    }
+    for k in doc.keys.iter() {
+        writeln!(
+            out,
+            r#"  <key id="{}" for="{}" attr.name="{}" attr.type="{}"{}/>"#,
+            k.id,
+            if k.edge { "edge" } else { "node" },
+            escape(k.title.as_str()),
+            k.xml_type,
+            key_attrs(k)
+        )?;
+    }
+    writeln!(out, r#"  <graph id="G" edgedefault="directed">"#)?;
+    let data = |out: &mut dyn Write, item: &Item| -> Result<(), Box<dyn Error>> {
+        for (key, v) in [("name", &item.name), ("type", &item.type_name)] {
+            if let Some(v) = v {
+                writeln!(
+                    out,
+                    r#"      <data key="{}">{}</data>"#,
+                    key,
+                    escape(v.as_str())
+                )?;
+            }
+        }
+        for (key, v) in item.values.iter() {
+            writeln!(
+                out,
+                r#"      <data key="{}">{}</data>"#,
+                key,
+                escape(v.as_str())
+            )?;
+        }
+        Ok(())
+    };
+    for n in doc.nodes.iter() {
+        writeln!(out, r#"    <node id="n{}">"#, n.id)?;
+        data(out, n)?;
+        writeln!(out, "    </node>")?;
+    }
+    for e in doc.edges.iter() {
+        writeln!(
+            out,
+            r#"    <edge id="e{}" source="n{}" target="n{}">"#,
+            e.id, e.head, e.tail
+        )?;
+        data(out, e)?;
+        writeln!(out, "    </edge>")?;
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    Ok(())
+}
+
+fn write_gexf(doc: &Doc, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+    writeln!(out, r#"  <graph defaultedgetype="directed">"#)?;
+    for (class, edge) in [("node", false), ("edge", true)] {
+        writeln!(out, r#"    <attributes class="{}">"#, class)?;
+        writeln!(
+            out,
+            r#"      <attribute id="type" title="type" type="string"/>"#
+        )?;
+        for k in doc.keys.iter().filter(|k| k.edge == edge) {
+            writeln!(
+                out,
+                r#"      <attribute id="{}" title="{}" type="{}"{}/>"#,
+                k.id,
+                escape(k.title.as_str()),
+                k.xml_type,
+                key_attrs(k)
+            )?;
+        }
+        writeln!(out, "    </attributes>")?;
+    }
+    let attvalues = |out: &mut dyn Write, item: &Item| -> Result<(), Box<dyn Error>> {
+        writeln!(out, "        <attvalues>")?;
+        let type_name = item.type_name.iter().map(|t| ("type", t));
+        let values = item.values.iter().map(|(k, v)| (k.as_str(), v));
+        for (key, v) in type_name.chain(values) {
+            writeln!(
+                out,
+                r#"          <attvalue for="{}" value="{}"/>"#,
+                key,
+                escape(v.as_str())
+            )?;
+        }
+        writeln!(out, "        </attvalues>")?;
+        Ok(())
+    };
+    let label = |item: &Item| escape(item.name.clone().unwrap_or_default()).to_string();
+    writeln!(out, "    <nodes>")?;
+    for n in doc.nodes.iter() {
+        writeln!(out, r#"      <node id="{}" label="{}">"#, n.id, label(n))?;
+        attvalues(out, n)?;
+        writeln!(out, "      </node>")?;
+    }
+    writeln!(out, "    </nodes>")?;
+    writeln!(out, "    <edges>")?;
+    for e in doc.edges.iter() {
+        writeln!(
+            out,
+            r#"      <edge id="{}" source="{}" target="{}" label="{}">"#,
+            e.id,
+            e.head,
+            e.tail,
+            label(e)
+        )?;
+        attvalues(out, e)?;
+        writeln!(out, "      </edge>")?;
+    }
+    writeln!(out, "    </edges>")?;
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")?;
+    Ok(())
+}
+
+// An XML element, with namespace prefixes dropped from its name.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attrs: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+    offset: u64,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(|s| s.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, Box<dyn Error>> {
+        self.attr(name).ok_or_else(|| {
+            let reason = format!("<{}> has no {}", self.name, name);
+            Box::new(ErrBadXml::new(self.offset, &reason)) as Box<dyn Error>
+        })
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn child(&self, name: &str) -> Result<&Element, Box<dyn Error>> {
+        self.children
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| {
+                let reason = format!("<{}> has no <{}>", self.name, name);
+                Box::new(ErrBadXml::new(self.offset, &reason)) as Box<dyn Error>
+            })
+    }
+}
+
+// The document element.
+fn parse(input: &mut dyn BufRead) -> Result<Element, Box<dyn Error>> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = vec![];
+    let mut stack = vec![Element::default()];
+    loop {
+        let offset = reader.buffer_position();
+        let bad = |reason: String| Box::new(ErrBadXml::new(offset, &reason));
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| bad(e.to_string()))?;
+        let top = stack.len() - 1;
+        match event {
+            Event::Start(e) => stack.push(element(&e, offset)?),
+            Event::Empty(e) => stack[top].children.push(element(&e, offset)?),
+            Event::End(_) => {
+                let done = stack
+                    .pop()
+                    .filter(|_| top > 0)
+                    .ok_or_else(|| bad("unexpected end".into()))?;
+                stack[top - 1].children.push(done);
+            }
+            Event::Text(t) => stack[top]
+                .text
+                .push_str(&t.unescape().map_err(|e| bad(e.to_string()))?),
+            Event::CData(t) => stack[top]
+                .text
+                .push_str(&String::from_utf8_lossy(&t.into_inner())),
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    let offset = reader.buffer_position();
+    match (
+        stack.len(),
+        stack.pop().and_then(|mut root| root.children.pop()),
+    ) {
+        (1, Some(root)) => Ok(root),
+        _ => Err(Box::new(ErrBadXml::new(
+            offset,
+            "no document element or unclosed",
+        ))),
+    }
+}
+
+fn element(e: &BytesStart, offset: u64) -> Result<Element, Box<dyn Error>> {
+    let mut attrs = HashMap::new();
+    for a in e.attributes() {
+        let a = a.map_err(|e| ErrBadXml::new(offset, &e.to_string()))?;
+        let value = a
+            .unescape_value()
+            .map_err(|e| ErrBadXml::new(offset, &e.to_string()))?;
+        attrs.insert(
+            String::from_utf8_lossy(a.key.as_ref()).to_string(),
+            value.to_string(),
+        );
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(e.local_name().as_ref()).to_string(),
+        attrs,
+        offset,
+        ..Default::default()
+    })
+}
+
+fn check_root(root: &Element, name: &str) -> Result<(), Box<dyn Error>> {
+    if root.name != name {
+        let reason = format!("expected <{}>, not <{}>", name, root.name);
+        return Err(Box::new(ErrBadXml::new(root.offset, &reason)));
+    }
+    Ok(())
+}
+
+// What a value is for: names and types are keys without a content type,
+// so attributes of the same names still round trip.
+fn role(keys: &HashMap<(bool, &str), &Key>, edge: bool, key: &str) -> Option<&'static str> {
+    match keys.get(&(edge, key)) {
+        Some(k) if k.content_type.is_none() => match k.title.as_str() {
+            "name" | "label" => Some("name"),
+            "type" => Some("type"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn set_value(item: &mut Item, role: Option<&str>, key: &str, text: &str) {
+    match role {
+        Some("name") => item.name = Some(text.to_string()),
+        Some(_) => item.type_name = Some(text.to_string()),
+        None => item.values.push((key.to_string(), text.to_string())),
+    }
+}
+
+fn read_graphml(root: &Element) -> Result<Doc, Box<dyn Error>> {
+    check_root(root, "graphml")?;
+    let mut doc = Doc::default();
+    for k in root.children("key") {
+        let id = k.required("id")?;
+        let key = Key {
+            id: id.to_string(),
+            edge: false,
+            title: k.attr("attr.name").unwrap_or(id).to_string(),
+            content_type: k.attr("content.type").map(|t| t.to_string()),
+            xml_type: k.attr("attr.type").unwrap_or("string").to_string(),
+        };
+        match k.attr("for").unwrap_or("all") {
+            "node" => doc.keys.push(key),
+            "edge" => doc.keys.push(Key { edge: true, ..key }),
+            "all" => {
+                doc.keys.push(Key {
+                    edge: true,
+                    ..key.clone()
+                });
+                doc.keys.push(key);
+            }
+            _ => {}
+        }
+    }
+    let keys: HashMap<(bool, &str), &Key> = doc
+        .keys
+        .iter()
+        .map(|k| ((k.edge, k.id.as_str()), k))
+        .collect();
+    let graph = root.child("graph")?;
+    let mut nodes = vec![];
+    let mut edges = vec![];
+    for e in graph.children.iter() {
+        let edge = match e.name.as_str() {
+            "node" => false,
+            "edge" => true,
+            _ => continue,
+        };
+        let mut item = Item {
+            offset: e.offset,
+            ..Default::default()
+        };
+        if edge {
+            item.id = e.attr("id").unwrap_or_default().to_string();
+            item.head = e.required("source")?.to_string();
+            item.tail = e.required("target")?.to_string();
+        } else {
+            item.id = e.required("id")?.to_string();
+        }
+        for d in e.children("data") {
+            let key = d.required("key")?;
+            set_value(&mut item, role(&keys, edge, key), key, d.text.trim());
+        }
+        match edge {
+            true => edges.push(item),
+            false => nodes.push(item),
+        }
+    }
+    doc.nodes = nodes;
+    doc.edges = edges;
+    Ok(doc)
+}
+
+fn read_gexf(root: &Element) -> Result<Doc, Box<dyn Error>> {
+    check_root(root, "gexf")?;
+    let graph = root.child("graph")?;
+    let mut doc = Doc::default();
+    for attributes in graph.children("attributes") {
+        let edge = attributes.attr("class") == Some("edge");
+        for a in attributes.children("attribute") {
+            let id = a.required("id")?;
+            doc.keys.push(Key {
+                id: id.to_string(),
+                edge,
+                title: a.attr("title").unwrap_or(id).to_string(),
+                content_type: a.attr("content.type").map(|t| t.to_string()),
+                xml_type: a.attr("type").unwrap_or("string").to_string(),
+            });
+        }
+    }
+    let keys: HashMap<(bool, &str), &Key> = doc
+        .keys
+        .iter()
+        .map(|k| ((k.edge, k.id.as_str()), k))
+        .collect();
+    let item = |e: &Element, edge: bool| -> Result<Item, Box<dyn Error>> {
+        let mut item = Item {
+            id: e.attr("id").unwrap_or_default().to_string(),
+            name: e.attr("label").map(|l| l.to_string()),
+            type_name: e.attr("kind").map(|k| k.to_string()),
+            offset: e.offset,
+            ..Default::default()
+        };
+        for v in e.children("attvalues").flat_map(|a| a.children("attvalue")) {
+            let key = v.required("for")?;
+            let text = v.required("value")?;
+            set_value(&mut item, role(&keys, edge, key), key, text);
+        }
+        Ok(item)
+    };
+    let mut nodes = vec![];
+    for n in graph.children("nodes").flat_map(|n| n.children("node")) {
+        n.required("id")?;
+        nodes.push(item(n, false)?);
+    }
+    let mut edges = vec![];
+    for e in graph.children("edges").flat_map(|e| e.children("edge")) {
+        edges.push(Item {
+            head: e.required("source")?.to_string(),
+            tail: e.required("target")?.to_string(),
+            ..item(e, true)?
+        });
+    }
+    doc.nodes = nodes;
+    doc.edges = edges;
+    Ok(doc)
+}
@@ -0,0 +1,267 @@
+use std::error::Error;
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::export::{Counts, Ids};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::interchange::{self, XmlFormat};
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+// api -> db with attributes of each type, including one named type.
+fn build(db: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut ids = vec![];
+    for (name, type_name) in [("api", "service"), ("db <main>", "database")] {
+        let mut n = Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        };
+        Node::operations(db).put(&mut n)?;
+        ids.push(n.id);
+    }
+    let mut e = Edge {
+        name: "depends-on".into(),
+        type_name: "dependency".into(),
+        head: ids[0],
+        tail: ids[1],
+        ..Default::default()
+    };
+    Edge::operations(db).put(&mut e)?;
+    ids.push(e.id);
+    for (parent_id, name, v) in [
+        (ids[0], "port", Value::Int(8080)),
+        (ids[0], "public", Value::Bool(true)),
+        (ids[0], "type", Value::String("rest".into())),
+        (ids[1], "engine", Value::String("\"pg\" & <co>".into())),
+        (
+            ids[1],
+            "created",
+            Value::Timestamp(1_700_000_000_000_000_001),
+        ),
+        (ids[2], "weight", Value::Float(0.5)),
+    ] {
+        Attribute::operations(db).put(&mut Attribute::typed(parent_id, name, &v))?;
+    }
+    Ok(())
+}
+
+fn export(db: &dyn Store, format: XmlFormat) -> Result<String, Box<dyn Error>> {
+    let mut out = vec![];
+    interchange::export_xml(db, format, &mut out)?;
+    Ok(String::from_utf8(out)?)
+}
+
+fn import(
+    db: &dyn Store,
+    format: XmlFormat,
+    text: &str,
+    ids: Ids,
+) -> Result<Counts, Box<dyn Error>> {
+    interchange::import_xml(db, format, &mut text.as_bytes(), ids)
+}
+
+fn value(db: &dyn Store, parent_id: u64, name: &str) -> Option<Value> {
+    Attribute::operations(db)
+        .get(Attribute::id_from(AttrKey {
+            parent_id,
+            name: name.into(),
+        }))
+        .unwrap()
+        .and_then(|a| a.value())
+}
+
+fn round_trip(format: XmlFormat) -> Result<String, Box<dyn Error>> {
+    let from = MemStore::new(&All);
+    build(&from)?;
+    let text = export(&from, format)?;
+
+    let to = MemStore::new(&All);
+    let counts = import(&to, format, &text, Ids::Preserve)?;
+    assert_eq!(
+        Counts {
+            symbols: 3,
+            nodes: 2,
+            edges: 1,
+            attributes: 6,
+        },
+        counts
+    );
+    assert_eq!(text, export(&to, format)?);
+    assert_eq!(3, db::last_id(&to)?);
+    assert_eq!(Some(Value::String("rest".into())), value(&to, 1, "type"));
+    assert_eq!(
+        Some(Value::Timestamp(1_700_000_000_000_000_001)),
+        value(&to, 2, "created")
+    );
+
+    // Remapped after the ids in use, with the type codes of this db
+    let to = MemStore::new(&All);
+    let mut n = Node {
+        name: "cache".into(),
+        type_name: "database".into(),
+        ..Default::default()
+    };
+    Node::operations(&to).put(&mut n)?;
+    import(&to, format, &text, Ids::Remap)?;
+    let api = Node::operations(&to).get(Node::id_from(2))?.unwrap();
+    assert_eq!(
+        ("api", "service"),
+        (api.name.as_str(), api.type_name.as_str())
+    );
+    let db_node = Node::operations(&to).get(Node::id_from(3))?.unwrap();
+    assert_eq!(n.type_code, db_node.type_code);
+    let edge = Edge::operations(&to).get(Edge::id_from(4))?.unwrap();
+    assert_eq!(
+        (2, 3, "dependency"),
+        (edge.head, edge.tail, edge.type_name.as_str())
+    );
+    assert_eq!(Some(Value::Float(0.5)), value(&to, 4, "weight"));
+    Ok(text)
+}
+
+#[test]
+fn test_graphml_round_trip() -> Result<(), Box<dyn Error>> {
+    let text = round_trip(XmlFormat::Graphml)?;
+    assert!(
+        text.contains(
+            r#"<key id="k0" for="node" attr.name="port" attr.type="long" content.type="int"/>"#
+        ),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains(r#"<edge id="e3" source="n1" target="n2">"#),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("<data key=\"name\">db &lt;main&gt;</data>"),
+        "{}",
+        text
+    );
+    Ok(())
+}
+
+#[test]
+fn test_gexf_round_trip() -> Result<(), Box<dyn Error>> {
+    let text = round_trip(XmlFormat::Gexf)?;
+    assert!(
+        text.contains(r#"<node id="2" label="db &lt;main&gt;">"#),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains(r#"<attvalue for="type" value="database"/>"#),
+        "{}",
+        text
+    );
+    Ok(())
+}
+
+#[test]
+fn test_foreign_files() -> Result<(), Box<dyn Error>> {
+    // As yEd writes it: string ids, labels in a key, no types
+    let graphml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:y="http://www.yworks.com/xml/graphml">
+  <key id="d0" for="node" attr.name="label" attr.type="string"/>
+  <key id="d1" for="edge" attr.name="cost" attr.type="double"/>
+  <graph id="G" edgedefault="undirected">
+    <node id="a"><data key="d0">Alpha</data></node>
+    <node id="b"><y:ShapeNode/></node>
+    <edge source="a" target="b"><data key="d1">2.5</data></edge>
+  </graph>
+</graphml>"#;
+    let db = MemStore::new(&All);
+    let counts = import(&db, XmlFormat::Graphml, graphml, Ids::Remap)?;
+    assert_eq!(
+        (2, 2, 1, 1),
+        (
+            counts.symbols,
+            counts.nodes,
+            counts.edges,
+            counts.attributes
+        )
+    );
+    let b = Node::operations(&db).get(Node::id_from(2))?.unwrap();
+    assert_eq!(("b", "entity"), (b.name.as_str(), b.type_name.as_str()));
+    let edge = Edge::operations(&db).get(Edge::id_from(3))?.unwrap();
+    assert_eq!(("edge", 1, 2), (edge.name.as_str(), edge.head, edge.tail));
+    assert_eq!(Some(Value::Float(2.5)), value(&db, 3, "cost"));
+
+    let err = import(&db, XmlFormat::Graphml, graphml, Ids::Preserve).unwrap_err();
+    assert!(
+        err.to_string().contains("id \"a\" is not a number"),
+        "{}",
+        err
+    );
+
+    // As Gephi writes it, with an edge kind
+    let gexf = r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">
+  <graph defaultedgetype="directed">
+    <attributes class="node"><attribute id="0" title="team" type="string"/></attributes>
+    <nodes>
+      <node id="0" label="x"><attvalues><attvalue for="0" value="core"/></attvalues></node>
+      <node id="1" label="y"/>
+    </nodes>
+    <edges><edge id="0" source="0" target="1" kind="calls"/></edges>
+  </graph>
+</gexf>"#;
+    let db = MemStore::new(&All);
+    import(&db, XmlFormat::Gexf, gexf, Ids::Remap)?;
+    assert_eq!(Some(Value::String("core".into())), value(&db, 1, "team"));
+    let edge = Edge::operations(&db).get(Edge::id_from(3))?.unwrap();
+    assert_eq!(
+        ("calls", "calls"),
+        (edge.name.as_str(), edge.type_name.as_str())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_import_errors() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let graphml = |body: &str| format!("<graphml><graph>{}</graph></graphml>", body);
+    let err = import(
+        &db,
+        XmlFormat::Graphml,
+        &graphml(r#"<edge source="a" target="b"/>"#),
+        Ids::Remap,
+    )
+    .unwrap_err();
+    assert_eq!("Bad XML at byte 16: no node \"a\"", err.to_string());
+    let err = import(&db, XmlFormat::Graphml, &graphml("<node/>"), Ids::Remap).unwrap_err();
+    assert!(err.to_string().contains("<node> has no id"), "{}", err);
+    let err = import(&db, XmlFormat::Gexf, &graphml(""), Ids::Remap).unwrap_err();
+    assert!(err.to_string().contains("expected <gexf>"), "{}", err);
+    let err = import(&db, XmlFormat::Graphml, "<graphml><graph>", Ids::Remap).unwrap_err();
+    assert!(err.to_string().starts_with("Bad XML"), "{}", err);
+    let err = import(
+        &db,
+        XmlFormat::Graphml,
+        "<graphml><graph></node>",
+        Ids::Remap,
+    )
+    .unwrap_err();
+    assert!(err.to_string().starts_with("Bad XML"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_round_trip_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let from_info = TestDbInfo::new();
+    let from = db::init(&from_info, &All)?;
+    build(&from)?;
+    let text = export(&from, XmlFormat::Graphml)?;
+
+    let to_info = TestDbInfo::new();
+    let to = db::init(&to_info, &All)?;
+    import(&to, XmlFormat::Graphml, &text, Ids::Preserve)?;
+    import(&to, XmlFormat::Graphml, &text, Ids::Remap)?;
+    assert!(fsck::fsck(&to)?.is_clean());
+    assert_eq!(6, db::last_id(&to)?);
+    Ok(())
+}
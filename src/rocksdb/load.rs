@@ -0,0 +1,435 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Database, DbInfo, Entity, HasKey, KeyCodec, Transaction};
+use crate::rocksdb::edge;
+use crate::rocksdb::error::{ErrMissingIndex, ErrNoSuchNode};
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::node;
+use crate::rocksdb::All;
+
+use rocksdb::{ColumnFamily, Direction, IteratorMode, ReadOptions, SstFileWriter};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+// One line of the load input (JSON lines), e.g.
+//
+// {"node": {"name": "api", "type_name": "service"}}
+// {"node": {"name": "db"}}
+// {"edge": {"head": "api", "name": "depends-on", "tail": "db"}}
+//
+// Edges refer to their head and tail by node name.  Ids are assigned from
+// the db sequence unless given.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Record {
+    Node(NodeRecord),
+    Edge(EdgeRecord),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct NodeRecord {
+    pub id: Option<u64>,
+    pub name: String,
+    pub type_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EdgeRecord {
+    pub id: Option<u64>,
+    pub head: String,
+    pub name: String,
+    pub tail: String,
+    pub type_name: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoadStats {
+    pub nodes: u64,
+    pub edges: u64,
+    pub files: usize,
+}
+
+// Entries staged for one column family, kept sorted by key since that's
+// the order SstFileWriter wants them in.  None marks a deletion.
+pub(crate) type Table = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+// Stages everything Operations::put would write for each record, reading
+// through the staged tables first so later records see the earlier ones.
+struct Loader<'a> {
+    db: &'a Database,
+    tables: BTreeMap<String, Table>,
+    types: HashMap<String, u64>,
+    counts: BTreeMap<&'static str, u64>,
+    start_sequence: u64,
+    sequence: u64,
+    stats: LoadStats,
+}
+
+impl<'a> Loader<'a> {
+    fn new(db: &'a Database) -> Result<Loader<'a>, Box<dyn Error>> {
+        let sequence = db::last_id(db)?;
+        Ok(Loader {
+            db,
+            tables: BTreeMap::new(),
+            types: HashMap::new(),
+            counts: BTreeMap::new(),
+            start_sequence: sequence,
+            sequence,
+            stats: LoadStats::default(),
+        })
+    }
+
+    fn cf(&self, cf_name: &str) -> Result<&'a ColumnFamily, Box<dyn Error>> {
+        match self.db.cf_handle(cf_name) {
+            Some(cf) => Ok(cf),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        }
+    }
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        if let Some(staged) = self.tables.get(cf_name).and_then(|t| t.get(key)) {
+            return Ok(staged.clone());
+        }
+        Ok(self.db.get_cf(self.cf(cf_name)?, key)?)
+    }
+
+    fn put(&mut self, cf_name: &str, key: Vec<u8>, value: Vec<u8>) {
+        self.tables
+            .entry(cf_name.to_string())
+            .or_default()
+            .insert(key, Some(value));
+    }
+
+    fn delete(&mut self, cf_name: &str, key: Vec<u8>) {
+        self.tables
+            .entry(cf_name.to_string())
+            .or_default()
+            .insert(key, None);
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    // The id given in the record, else a new one.  The sequence is moved
+    // past a given id so that no record after it gets the same one, and
+    // commit saves it so that puts after the load don't either.
+    fn id(&mut self, id: Option<u64>) -> u64 {
+        match id {
+            Some(id) if id > 0 => {
+                self.sequence = self.sequence.max(id);
+                id
+            }
+            _ => self.next_id(),
+        }
+    }
+
+    // Same as db::type_code, but staged with the rest of the load so that
+    // a load that fails doesn't leave new type codes behind.
+    fn type_code(&mut self, name: &String) -> Result<u64, Box<dyn Error>> {
+        if let Some(code) = self.types.get(name) {
+            return Ok(*code);
+        }
+        let code = match self.get(db::CF_SYSTEM_TYPES, name.as_bytes())? {
+            Some(v) => u64::decode_key(v),
+            None => {
+                let count = match self.get(db::CF_COUNTERS, db::COUNT_TYPES.as_bytes())? {
+                    Some(v) => u64::decode_key(v),
+                    None => 0,
+                };
+                let code = count + 1;
+                self.put(
+                    db::CF_COUNTERS,
+                    db::COUNT_TYPES.as_bytes().to_vec(),
+                    code.encode_key(),
+                );
+                self.put(
+                    db::CF_SYSTEM_TYPES,
+                    name.as_bytes().to_vec(),
+                    code.encode_key(),
+                );
+                code
+            }
+        };
+        self.types.insert(name.clone(), code);
+        Ok(code)
+    }
+
+    // Same as Index::delete_entry, but against the staged view of the db.
+    fn delete_entry<E: Entity>(
+        &mut self,
+        index: &dyn Index<E>,
+        e: &E,
+    ) -> Result<(), Box<dyn Error>> {
+        if !index.append_if_same_key() {
+            self.delete(index.cf_name(), index.key_value(e).0);
+            return Ok(());
+        }
+
+        let prefix = index.append_prefix(e);
+        let mut target_keys = Vec::<Vec<u8>>::new();
+        // The append prefix needn't be as long as the spec's prefix length
+        let mut options = ReadOptions::default();
+        options.set_total_order_seek(true);
+        let iter = self.db.iterator_cf_opt(
+            self.cf(index.cf_name())?,
+            options,
+            IteratorMode::From(prefix.as_slice(), Direction::Forward),
+        );
+        for item in iter {
+            let (k, v) = item?;
+            if v.is_empty() || !k.starts_with(&prefix) {
+                break;
+            }
+            target_keys.push(k.to_vec());
+        }
+        if let Some(table) = self.tables.get(index.cf_name()) {
+            for (k, v) in table.range(prefix.clone()..) {
+                if !k.starts_with(&prefix) {
+                    break;
+                }
+                if v.is_some() {
+                    target_keys.push(k.clone());
+                }
+            }
+        }
+        for k in target_keys {
+            self.delete(index.cf_name(), k);
+        }
+        Ok(())
+    }
+
+    // Stages the index updates of Operations::put, and counts new entities.
+    fn stage<E: Entity + HasKey<u64>>(
+        &mut self,
+        value_index: &dyn Index<E>,
+        indexes: Vec<Box<dyn Index<E>>>,
+        e: &E,
+    ) -> Result<(), Box<dyn Error>> {
+        let id = e.id().as_bytes();
+        match self.get(value_index.cf_name(), &id)? {
+            Some(bytes) => {
+                let found = E::from_bytes(&id, &bytes)?;
+                trace!("Replacing old={:?} new={:?}", found, e);
+                for index in indexes.iter() {
+                    self.delete_entry(index.as_ref(), &found)?;
+                }
+            }
+            None => *self.counts.entry(E::TYPE).or_insert(0) += 1,
+        }
+        for index in indexes.iter() {
+            let (k, v) = index.entry(e);
+            self.put(index.cf_name(), k, v);
+        }
+        Ok(())
+    }
+
+    // Resolves a node name the way `edge associate` does, via the name index.
+    fn node_id(&self, name: &str) -> Result<u64, Box<dyn Error>> {
+        if let Some(v) = self.get(node::ByName.cf_name(), name.as_bytes())? {
+            let id = u64::decode_key(v);
+            if self.get(node::ById.cf_name(), &id.encode_key())?.is_some() {
+                return Ok(id);
+            }
+        }
+        Err(Box::new(ErrNoSuchNode::new(name.to_string())))
+    }
+
+    fn node(&mut self, r: &NodeRecord) -> Result<(), Box<dyn Error>> {
+        let type_name = match &r.type_name {
+            Some(v) => v.to_string(),
+            None => "entity".to_string(),
+        };
+        let node = Node {
+            id: self.id(r.id),
+            type_code: self.type_code(&type_name)?,
+            type_name,
+            name: r.name.clone(),
+            ts_nano: now(),
+        };
+        self.stage(&node::ById, Node::indexes(), &node)?;
+        self.stats.nodes += 1;
+        Ok(())
+    }
+
+    fn edge(&mut self, r: &EdgeRecord) -> Result<(), Box<dyn Error>> {
+        let head = self.node_id(&r.head)?;
+        let tail = self.node_id(&r.tail)?;
+        let type_name = match &r.type_name {
+            Some(v) => v.to_string(),
+            None => r.name.clone(),
+        };
+        let edge = Edge {
+            id: self.id(r.id),
+            type_code: self.type_code(&type_name)?,
+            type_name,
+            name: r.name.clone(),
+            head,
+            tail,
+            ts_nano: now(),
+        };
+        self.stage(&edge::ById, Edge::indexes(), &edge)?;
+        self.stats.edges += 1;
+        Ok(())
+    }
+
+    // Writes one sst file per column family and ingests them.  RocksDB can
+    // ingest into several column families atomically, but not through the C
+    // API that the rocksdb crate wraps, so each column family is ingested on
+    // its own.  To keep a failed load from leaving some column families
+    // loaded and others not, the current values of the staged keys are read
+    // first and put back if an ingest fails.
+    fn commit(mut self, info: &dyn DbInfo) -> Result<LoadStats, Box<dyn Error>> {
+        let counters = db::default_counters(self.db);
+        let counts: Vec<(&'static str, u64)> = self.counts.iter().map(|(k, v)| (*k, *v)).collect();
+        for (key, n) in counts {
+            let mut counter = counters.get(key)?;
+            counter.set(counter.get() + n);
+            self.put(db::CF_COUNTERS, counter.id().as_bytes(), counter.as_bytes());
+        }
+        if self.sequence != self.start_sequence {
+            self.put(
+                db::CF_SYSTEM,
+                db::SEQ_KEY.as_bytes().to_vec(),
+                self.sequence.to_le_bytes().to_vec(),
+            );
+        }
+
+        // Value indexes after the secondary indexes, and the system tables
+        // last, so that a crash part way leaves nothing that points to them.
+        let last = [
+            node::ById.cf_name(),
+            edge::ById.cf_name(),
+            db::CF_SYSTEM_TYPES,
+            db::CF_COUNTERS,
+            db::CF_SYSTEM,
+        ];
+        let mut order: Vec<String> = self
+            .tables
+            .keys()
+            .filter(|k| !last.contains(&k.as_str()))
+            .cloned()
+            .collect();
+        for k in last.iter() {
+            if self.tables.contains_key(*k) {
+                order.push(k.to_string());
+            }
+        }
+
+        let dir = tempfile::tempdir()?;
+        let options = info.options();
+        let mut files = Vec::new();
+        for (i, cf_name) in order.iter().enumerate() {
+            let table = &self.tables[cf_name];
+            let path = dir.path().join(format!("{:03}-{}.sst", i, cf_name));
+            // The column family's own options, so that the file has the
+            // compression, filters and prefix extractor of the spec
+            let cf_options = db::cf_options(info, &options, cf_name);
+            let mut writer = SstFileWriter::create(&cf_options);
+            writer.open(&path)?;
+            for (k, v) in table.iter() {
+                match v {
+                    Some(v) => writer.put(k, v)?,
+                    None => writer.delete(k)?,
+                }
+            }
+            writer.finish()?;
+            trace!("Wrote {:?}, size={:?}", path, writer.file_size());
+            files.push((cf_name.to_string(), path));
+        }
+
+        let mut undo = BTreeMap::new();
+        for (cf_name, table) in self.tables.iter() {
+            let cf = self.cf(cf_name)?;
+            let mut old = Table::new();
+            for k in table.keys() {
+                old.insert(k.clone(), self.db.get_cf(cf, k)?);
+            }
+            undo.insert(cf_name.to_string(), old);
+        }
+        ingest(self.db, &files, &undo)?;
+
+        self.stats.files = files.len();
+        Ok(self.stats)
+    }
+}
+
+// Ingests the sst file of each column family in order.  If one fails, the
+// column families already ingested get the entries in undo written back in
+// one batch, so the db is as it was before.
+pub(crate) fn ingest(
+    db: &Database,
+    files: &[(String, PathBuf)],
+    undo: &BTreeMap<String, Table>,
+) -> Result<(), Box<dyn Error>> {
+    for (n, (cf_name, path)) in files.iter().enumerate() {
+        info!("Ingesting {:?} into {:?}", path, cf_name);
+        let result = match db.cf_handle(cf_name) {
+            Some(cf) => db
+                .ingest_external_file_cf(cf, vec![path])
+                .map_err(|e| -> Box<dyn Error> { Box::new(e) }),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string())) as Box<dyn Error>),
+        };
+        if let Err(e) = result {
+            error!("Ingesting {:?} failed: {:?}; rolling back", cf_name, e);
+            let mut txn = Transaction::default();
+            for (cf_name, _) in files[..n].iter() {
+                let cf = db.cf_handle(cf_name).unwrap();
+                for (k, v) in undo.get(cf_name).into_iter().flatten() {
+                    match v {
+                        Some(v) => txn.put_cf(cf, k, v),
+                        None => txn.delete_cf(cf, k),
+                    }
+                }
+            }
+            db.write(txn)?;
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn now() -> Vec<u8> {
+    OffsetDateTime::now_utc()
+        .unix_timestamp_nanos()
+        .to_le_bytes()
+        .to_vec()
+}
+
+pub fn load(info: &dyn DbInfo, input: &mut dyn BufRead) -> Result<LoadStats, Box<dyn Error>> {
+    let db = db::init(info, &All)?;
+    let mut loader = Loader::new(&db)?;
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = match serde_json::from_str(&line) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Bad record at line {}: {:?}", n + 1, e);
+                return Err(Box::new(e));
+            }
+        };
+        trace!("Loading {:?}", record);
+        match &record {
+            Record::Node(r) => loader.node(r)?,
+            Record::Edge(r) => loader.edge(r)?,
+        }
+    }
+    loader.commit(info)
+}
+
+pub fn load_file(info: &dyn DbInfo, path: &str) -> Result<LoadStats, Box<dyn Error>> {
+    let mut input = BufReader::new(File::open(path)?);
+    load(info, &mut input)
+}
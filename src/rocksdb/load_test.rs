@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::rocksdb::db::{self, Database, DbInfo, Entity, HasKey, OperationsBuilder};
+use crate::rocksdb::edge;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::load;
+use crate::rocksdb::node;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use rocksdb::{IteratorMode, Options, SstFileWriter, DB};
+
+static INPUT: &str = r#"
+{"node": {"name": "api", "type_name": "service"}}
+{"node": {"name": "db", "type_name": "service"}}
+{"node": {"name": "cache"}}
+{"edge": {"head": "api", "name": "depends-on", "tail": "db"}}
+{"edge": {"head": "api", "name": "depends-on", "tail": "cache", "type_name": "soft"}}
+{"node": {"id": 3, "name": "redis", "type_name": "service"}}
+"#;
+
+type Dump = BTreeMap<String, Vec<(Vec<u8>, Vec<u8>)>>;
+
+// Contents of every column family, with the timestamps taken out.
+fn dump(db: &Database) -> Dump {
+    let mut out = BTreeMap::new();
+    for cf_name in DB::list_cf(&Options::default(), db.path()).unwrap() {
+        let cf = db.cf_handle(&cf_name).unwrap();
+        let mut entries = vec![];
+        for item in db.iterator_cf(cf, IteratorMode::Start) {
+            let (k, v) = item.unwrap();
+            let (mut k, mut v) = (k.to_vec(), v.to_vec());
+            if cf_name == node::ById.cf_name() {
+                let mut n = Node::from_bytes(&k, &v).unwrap();
+                n.ts_nano = vec![];
+                v = n.as_bytes();
+            } else if cf_name == edge::ById.cf_name() {
+                let mut e = Edge::from_bytes(&k, &v).unwrap();
+                e.ts_nano = vec![];
+                v = e.as_bytes();
+            } else if cf_name == node::ByNameHash.cf_name() {
+                let end = k.iter().position(|b| *b == b':').unwrap();
+                k.truncate(end);
+            }
+            entries.push((k, v));
+        }
+        out.insert(cf_name, entries);
+    }
+    out
+}
+
+fn put_all(db: &Database) -> Result<(), Box<dyn Error>> {
+    let mut node_ops = Node::operations(db);
+    let mut edge_ops = Edge::operations(db);
+    let by_name = node::ByName.cf_name().to_string();
+    for (name, type_name) in [("api", "service"), ("db", "service"), ("cache", "entity")] {
+        node_ops.put(&mut Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        })?;
+    }
+    let api = node_ops.first(&by_name, b"api")?.unwrap();
+    for (tail, type_name) in [("db", "depends-on"), ("cache", "soft")] {
+        let tail = node_ops.first(&by_name, tail.as_bytes())?.unwrap();
+        edge_ops.put(&mut Edge {
+            head: api.id,
+            tail: tail.id,
+            name: "depends-on".into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        })?;
+    }
+    node_ops.put(&mut Node {
+        id: 3,
+        name: "redis".into(),
+        type_name: "service".into(),
+        ..Default::default()
+    })?;
+    Ok(())
+}
+
+#[test]
+fn test_load_same_as_put() -> Result<(), Box<dyn Error>> {
+    let loaded_info = TestDbInfo::new();
+    let stats = load::load(&loaded_info, &mut INPUT.as_bytes())?;
+    assert_eq!(4, stats.nodes);
+    assert_eq!(2, stats.edges);
+
+    let put_info = TestDbInfo::new();
+    put_all(&db::init(&put_info, &All)?)?;
+
+    let loaded = dump(&db::open_db(&loaded_info, &All)?);
+    let put = dump(&db::open_db(&put_info, &All)?);
+    assert_eq!(put, loaded);
+
+    // The replaced node is gone from the name index
+    let db = db::open_db(&loaded_info, &All)?;
+    let node_ops = Node::operations(&db);
+    let by_name = node::ByName.cf_name().to_string();
+    assert!(node_ops.first(&by_name, b"cache")?.is_none());
+    assert_eq!(3, node_ops.first(&by_name, b"redis")?.unwrap().id);
+    Ok(())
+}
+
+#[test]
+fn test_load_on_top_of_existing() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    {
+        let db = db::init(&db_info, &All)?;
+        let mut node_ops = Node::operations(&db);
+        node_ops.put(&mut Node {
+            name: "api".into(),
+            type_name: "service".into(),
+            ..Default::default()
+        })?;
+    }
+
+    let input = r#"{"node": {"name": "db", "type_name": "service"}}
+{"edge": {"head": "api", "name": "depends-on", "tail": "db"}}"#;
+    load::load(&db_info, &mut input.as_bytes())?;
+
+    let db = db::open_db(&db_info, &All)?;
+    assert_eq!(3, db::last_id(&db)?);
+    assert_eq!(2, db::default_counters(&db).get("Node")?.get());
+    assert_eq!(1, db::default_counters(&db).get("Edge")?.get());
+
+    let edge_ops = Edge::operations(&db);
+    let edge = edge_ops.get(Edge::id_from(3u64))?.unwrap();
+    assert_eq!((1, 2), (edge.head, edge.tail));
+    Ok(())
+}
+
+#[test]
+fn test_load_mixed_ids() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let input = r#"{"node": {"id": 5, "name": "api"}}
+{"node": {"name": "db"}}
+{"node": {"id": 2, "name": "cache"}}
+{"node": {"name": "redis"}}
+{"edge": {"head": "api", "name": "depends-on", "tail": "db"}}"#;
+    load::load(&db_info, &mut input.as_bytes())?;
+
+    // New ids come after the given ones, none overwritten
+    let db = db::open_db(&db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    let by_name = node::ByName.cf_name().to_string();
+    for (name, id) in [("api", 5), ("db", 6), ("cache", 2), ("redis", 7)] {
+        assert_eq!(id, node_ops.first(&by_name, name.as_bytes())?.unwrap().id);
+    }
+    assert_eq!(4, db::default_counters(&db).get("Node")?.get());
+    let edge = Edge::operations(&db).get(Edge::id_from(8u64))?.unwrap();
+    assert_eq!((5, 6), (edge.head, edge.tail));
+
+    // and so do the ids of puts after the load
+    assert_eq!(8, db::last_id(&db)?);
+    let mut node = Node {
+        name: "queue".into(),
+        type_name: "service".into(),
+        ..Default::default()
+    };
+    node_ops.put(&mut node)?;
+    assert_eq!(9, node.id);
+    Ok(())
+}
+
+#[test]
+fn test_load_unknown_node() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let input = r#"{"node": {"name": "api"}}
+{"edge": {"head": "api", "name": "depends-on", "tail": "nope"}}"#;
+    assert!(load::load(&db_info, &mut input.as_bytes()).is_err());
+
+    // Nothing is ingested
+    let db = db::open_db(&db_info, &All)?;
+    assert_eq!(0, db::last_id(&db)?);
+    assert!(Node::operations(&db).get(Node::id_from(1u64))?.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_ingest_rolls_back() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::open_db(&db_info, &All)?;
+    let cf_name = node::ByName.cf_name().to_string();
+    let cf = db.cf_handle(&cf_name).unwrap();
+    db.put_cf(cf, b"api", b"1")?;
+
+    // The first file loads, the second one isn't there.
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("000.sst");
+    let options = db_info.options();
+    let mut writer = SstFileWriter::create(&options);
+    writer.open(&path)?;
+    writer.put(b"api", b"2")?;
+    writer.put(b"db", b"3")?;
+    writer.finish()?;
+    let files = vec![
+        (cf_name.clone(), path),
+        (db::CF_SYSTEM.to_string(), dir.path().join("001.sst")),
+    ];
+    let mut undo = BTreeMap::new();
+    undo.insert(
+        cf_name.clone(),
+        load::Table::from([
+            (b"api".to_vec(), Some(b"1".to_vec())),
+            (b"db".to_vec(), None),
+        ]),
+    );
+    assert!(load::ingest(&db, &files, &undo).is_err());
+
+    assert_eq!(Some(b"1".to_vec()), db.get_cf(cf, b"api")?);
+    assert_eq!(None, db.get_cf(cf, b"db")?);
+    Ok(())
+}
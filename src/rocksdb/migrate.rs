@@ -0,0 +1,148 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Database, DbInfo};
+use crate::rocksdb::edge;
+use crate::rocksdb::error::{ErrNoMigration, ErrSchemaVersion};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::store::Store;
+use crate::rocksdb::All;
+
+use rocksdb::checkpoint::Checkpoint;
+
+use std::error::Error;
+
+// A migration step changes the db in place and returns the number of
+// changes made, or would make when dry_run is set.
+pub type Step = fn(db: &mut Database, dry_run: bool) -> Result<u64, Box<dyn Error>>;
+
+// Takes the db from schema version `from` to `from + 1`.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub from: u64,
+    pub description: &'static str,
+    pub step: Step,
+}
+
+// The registry of steps, in order.  Add a step here when changing the
+// on-disk layout and bump db::SCHEMA_VERSION to match.
+pub fn migrations() -> Vec<Migration> {
+    vec![Migration {
+        from: 1,
+        description: "key the type indexes by type code and id",
+        step: rekey_type_indexes,
+    }]
+}
+
+// Version 1 kept the last node or edge put of each type in
+// index.node.type and index.edge.type; 2 has all of them.
+fn rekey_type_indexes(db: &mut Database, dry_run: bool) -> Result<u64, Box<dyn Error>> {
+    if dry_run {
+        let nodes = count_keys(&*db, node::ById.cf_name())?;
+        let edges = count_keys(&*db, edge::ById.cf_name())?;
+        return Ok(nodes + edges);
+    }
+    let nodes = fsck::reindex::<Node>(&*db, Some(node::ByType.cf_name()))?;
+    let edges = fsck::reindex::<Edge>(&*db, Some(edge::ByType.cf_name()))?;
+    Ok(nodes + edges)
+}
+
+// The entity counters of a version 1 db can be off, so a dry run counts the
+// entities the step would reindex.
+fn count_keys(db: &dyn Store, cf_name: &str) -> Result<u64, Box<dyn Error>> {
+    let mut count = 0u64;
+    db.scan_from(cf_name, &[], &mut |_, _| {
+        count += 1;
+        true
+    })?;
+    Ok(count)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Applied {
+    pub from: u64,
+    pub description: String,
+    pub changes: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Report {
+    pub from: u64,
+    pub to: u64,
+    pub dry_run: bool,
+    pub backup: Option<String>,
+    pub steps: Vec<Applied>,
+}
+
+// Migrates the db to the version of this binary.
+pub fn migrate(
+    info: &dyn DbInfo,
+    dry_run: bool,
+    backup_dir: Option<&str>,
+) -> Result<Report, Box<dyn Error>> {
+    migrate_with(info, &migrations(), db::SCHEMA_VERSION, dry_run, backup_dir)
+}
+
+// Runs the steps from the db's version up to target.  Unless it's a dry
+// run, a checkpoint of the db is taken first, in backup_dir or next to the
+// db.  A dry run doesn't apply any step, so the later steps of a dry run
+// see the data as it was before the earlier ones.
+pub(crate) fn migrate_with(
+    info: &dyn DbInfo,
+    registry: &[Migration],
+    target: u64,
+    dry_run: bool,
+    backup_dir: Option<&str>,
+) -> Result<Report, Box<dyn Error>> {
+    let mut db = db::open_cfs(info, &All)?;
+    let version = db::schema_version(&db)?;
+    if version > target {
+        return Err(Box::new(ErrSchemaVersion::new(version, target)));
+    }
+
+    let mut steps = vec![];
+    for from in version..target {
+        match registry.iter().find(|m| m.from == from) {
+            Some(m) => steps.push(m),
+            None => return Err(Box::new(ErrNoMigration::new(from))),
+        }
+    }
+
+    let mut report = Report {
+        from: version,
+        to: target,
+        dry_run,
+        ..Default::default()
+    };
+    if steps.is_empty() {
+        info!("Db schema version {} is current", version);
+        return Ok(report);
+    }
+
+    if !dry_run {
+        let dir = match backup_dir {
+            Some(d) => d.to_string(),
+            None => format!("{}.v{}.backup", info.path(), version),
+        };
+        info!("Checkpoint before migrating to {:?}", dir);
+        Checkpoint::new(&db)?.create_checkpoint(&dir)?;
+        report.backup = Some(dir);
+    }
+
+    for m in steps {
+        info!("Migrating from {}: {}", m.from, m.description);
+        let changes = (m.step)(&mut db, dry_run)?;
+        if !dry_run {
+            db::set_schema_version(&db, m.from + 1)?;
+        }
+        report.steps.push(Applied {
+            from: m.from,
+            description: m.description.to_string(),
+            changes,
+        });
+    }
+    Ok(report)
+}
@@ -0,0 +1,192 @@
+use std::error::Error;
+use tempfile::tempdir;
+
+use crate::rocksdb::db::{self, Database, Entity, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::migrate::{self, Migration};
+use crate::rocksdb::node;
+use crate::rocksdb::store::{Batch, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use rocksdb::IteratorMode;
+
+// Marks every node in cf.system
+fn mark_nodes(db: &mut Database, dry_run: bool) -> Result<u64, Box<dyn Error>> {
+    let nodes = db.cf_handle("index.node.id").unwrap();
+    let system = db.cf_handle("cf.system").unwrap();
+    let mut changes = 0;
+    for item in db.iterator_cf(nodes, IteratorMode::Start) {
+        let (k, _) = item?;
+        if !dry_run {
+            db.put_cf(system, [b"marked.", &k[..]].concat(), b"")?;
+        }
+        changes += 1;
+    }
+    Ok(changes)
+}
+
+fn registry() -> Vec<Migration> {
+    vec![Migration {
+        from: 1,
+        description: "mark nodes",
+        step: mark_nodes,
+    }]
+}
+
+fn setup(db_info: &TestDbInfo) -> Result<(), Box<dyn Error>> {
+    let db = db::init(db_info, &All)?;
+    let mut node_ops = Node::operations(&db);
+    for name in ["api", "db"] {
+        node_ops.put(&mut Node {
+            name: name.into(),
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+// A db at version 1, for the test registry
+fn setup_v1(db_info: &TestDbInfo) -> Result<(), Box<dyn Error>> {
+    setup(db_info)?;
+    let db = db::open_db(db_info, &All)?;
+    db::set_schema_version(&db, 1)
+}
+
+#[test]
+fn test_schema_version() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    {
+        let db = db::init(&db_info, &All)?;
+        assert_eq!(db::SCHEMA_VERSION, db::schema_version(&db)?);
+        db::set_schema_version(&db, db::SCHEMA_VERSION + 1)?;
+    }
+    // Written by a newer binary
+    assert!(db::open_db(&db_info, &All).is_err());
+    assert!(migrate::migrate(&db_info, false, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_migrate_current() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    setup(&db_info)?;
+    let report = migrate::migrate(&db_info, false, None)?;
+    assert!(report.steps.is_empty());
+    assert!(report.backup.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_migrate_dry_run() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    setup_v1(&db_info)?;
+
+    let report = migrate::migrate_with(&db_info, &registry(), 2, true, None)?;
+    assert_eq!(1, report.steps.len());
+    assert_eq!(2, report.steps[0].changes);
+    assert!(report.backup.is_none());
+
+    let db = db::open_db(&db_info, &All)?;
+    assert_eq!(1, db::schema_version(&db)?);
+    Ok(())
+}
+
+#[test]
+fn test_migrate() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    setup_v1(&db_info)?;
+    let dir = tempdir()?;
+    let backup = dir.path().join("backup");
+    let backup = backup.to_str().unwrap();
+
+    let report = migrate::migrate_with(&db_info, &registry(), 2, false, Some(backup))?;
+    assert_eq!((1, 2), (report.from, report.to));
+    assert_eq!(Some(backup.to_string()), report.backup);
+
+    let db = db::open_cfs(&db_info, &All)?;
+    assert_eq!(2, db::schema_version(&db)?);
+    let system = db.cf_handle("cf.system").unwrap();
+    assert!(db.get_cf(system, b"marked.\x01\0\0\0\0\0\0\0")?.is_some());
+
+    // The checkpoint has the db as it was
+    let before = db::open_db(&TestDbInfo::at(backup), &All)?;
+    assert_eq!(1, db::schema_version(&before)?);
+
+    // Nothing to go from 2 to 3
+    assert!(migrate::migrate_with(&db_info, &registry(), 3, true, None).is_err());
+    Ok(())
+}
+
+fn set_node_counter(db_info: &TestDbInfo, n: u64) -> Result<(), Box<dyn Error>> {
+    let db = db::open_cfs(db_info, &All)?;
+    let mut counters = db::default_counters(&db);
+    let mut counter = counters.get(Node::TYPE)?;
+    counter.set(n);
+    let mut txn = Batch::default();
+    counters.update(&mut txn, &counter)?;
+    db.commit(txn)
+}
+
+#[test]
+fn test_rekey_type_indexes() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    {
+        let db = db::init(&db_info, &All)?;
+        let mut node_ops = Node::operations(&db);
+        for name in ["api", "db", "cache"] {
+            node_ops.put(&mut Node {
+                name: name.into(),
+                type_name: "service".into(),
+                ..Default::default()
+            })?;
+        }
+        Edge::operations(&db).put(&mut Edge {
+            head: 1,
+            tail: 2,
+            type_name: "depends-on".into(),
+            ..Default::default()
+        })?;
+
+        // As version 1 left it: the last node of the type only
+        let cf = db.cf_handle(node::ByType.cf_name()).unwrap();
+        let keys: Vec<_> = db
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(|item| item.map(|(k, _)| k))
+            .collect::<Result<_, _>>()?;
+        for k in keys {
+            db.delete_cf(cf, k)?;
+        }
+        let service = db::find_type_code(&db, "service")?.unwrap();
+        db.put_cf(cf, service.to_le_bytes(), 3u64.to_le_bytes())?;
+        db::set_schema_version(&db, 1)?;
+    }
+
+    // The dry run counts the entities, not the counters
+    set_node_counter(&db_info, 10)?;
+    let report = migrate::migrate(&db_info, true, None)?;
+    assert_eq!(4, report.steps[0].changes);
+    set_node_counter(&db_info, 3)?;
+
+    let dir = tempdir()?;
+    let backup = dir.path().join("backup");
+    let report = migrate::migrate(&db_info, false, backup.to_str())?;
+    assert_eq!((1, db::SCHEMA_VERSION), (report.from, report.to));
+    assert_eq!(4, report.steps[0].changes);
+
+    let db = db::open_db(&db_info, &All)?;
+    assert!(fsck::fsck(&db)?.is_clean());
+    let service = db::find_type_code(&db, "service")?.unwrap();
+    let cf = db.cf_handle(node::ByType.cf_name()).unwrap();
+    let mut ids = vec![];
+    for item in db.iterator_cf(cf, IteratorMode::Start) {
+        let (k, v) = item?;
+        if k.starts_with(&service.to_le_bytes()) {
+            ids.push(u64::from_le_bytes(v[..].try_into()?));
+        }
+    }
+    ids.sort();
+    assert_eq!(vec![1, 2, 3], ids);
+    Ok(())
+}
@@ -1,41 +1,104 @@
-use graph::Node;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, trace, warn};
 
+mod attribute;
+#[cfg(test)]
+mod attribute_test;
+mod backfill;
+#[cfg(test)]
+mod backfill_test;
+mod backup;
+#[cfg(test)]
+mod backup_test;
+mod changes;
+#[cfg(test)]
+mod changes_test;
+mod columnar;
+#[cfg(test)]
+mod columnar_test;
 pub mod command;
 mod counter;
+mod csv_import;
+#[cfg(test)]
+mod csv_import_test;
 mod db;
+#[cfg(test)]
+mod db_hooks_test;
+#[cfg(test)]
+mod db_open_test;
+#[cfg(test)]
+mod derive_test;
+mod diagram;
+#[cfg(test)]
+mod diagram_test;
+mod document;
+#[cfg(test)]
+mod document_test;
 
 mod edge;
 #[cfg(test)]
 mod edge_test;
 mod error;
+mod export;
+#[cfg(test)]
+mod export_test;
+mod fsck;
+#[cfg(test)]
+mod fsck_test;
+mod gc;
+#[cfg(test)]
+mod gc_test;
+mod graphql;
+#[cfg(test)]
+mod graphql_test;
 pub mod hash;
 mod index;
+mod interchange;
+#[cfg(test)]
+mod interchange_test;
+mod load;
+#[cfg(test)]
+mod load_test;
+mod migrate;
+#[cfg(test)]
+mod migrate_test;
 mod node;
 #[cfg(test)]
 mod node_test;
+mod planner;
+mod query;
+#[cfg(test)]
+mod query_test;
+mod rdf;
+#[cfg(test)]
+mod rdf_test;
+mod registry;
+#[cfg(test)]
+mod registry_test;
+mod script;
+#[cfg(test)]
+mod script_test;
 
 #[path = "rocksdb.graph.v1.rs"] // generated by protoc
 mod graph;
 
 mod server;
+mod spec;
+#[cfg(test)]
+mod spec_test;
+mod store;
+#[cfg(test)]
+mod store_test;
+#[cfg(test)]
+mod testing;
+mod value;
 
+// The indexes of all the entity types of the graph; see registry::global.
 #[derive(Debug, Clone, PartialEq)]
 pub struct All;
 
-use crate::rocksdb::graph::Edge;
-use crate::rocksdb::index::Indexes; // use here to access to cf_name() method on index.
-
 impl db::IndexBuilder for All {
     fn cf_names(&self) -> Vec<String> {
-        let mut cfs = Vec::<String>::new();
-        for i in Node::indexes().iter() {
-            cfs.push(i.cf_name().to_string());
-        }
-        for i in Edge::indexes().iter() {
-            cfs.push(i.cf_name().to_string());
-        }
-        cfs
+        db::IndexBuilder::cf_names(&registry::global())
     }
 }
@@ -0,0 +1,268 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::{Condition, Op};
+use crate::rocksdb::db::{self, Entity};
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::query::{Access, Direction, Expand, Plan, Query, Slot};
+use crate::rocksdb::store::Store;
+use crate::rocksdb::value::Value;
+
+use std::error::Error;
+
+// Fraction of the values kept by a condition the stats say nothing about,
+// e.g. an attribute compared with a literal.
+static EQ_SELECTIVITY: f64 = 0.1;
+static RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+
+// What the planner knows about the db: entity counts from the counters and
+// key estimates of the index column families.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub nodes: f64,
+    pub edges: f64,
+    pub attributes: f64,
+    // Keys in index.node.name, one per distinct name
+    pub node_names: f64,
+    // Keys in index.node.name_hash, one per node
+    pub node_name_entries: f64,
+    // Types in cf.system.types, which nodes and edges share
+    pub node_types: f64,
+    pub edge_types: f64,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            nodes: 1.0,
+            edges: 1.0,
+            attributes: 1.0,
+            node_names: 1.0,
+            node_name_entries: 1.0,
+            node_types: 1.0,
+            edge_types: 1.0,
+        }
+    }
+}
+
+impl Stats {
+    pub fn gather(db: &dyn Store) -> Result<Stats, Box<dyn Error>> {
+        let counters = db::default_counters(db);
+        let count = |t: &str| -> Result<f64, Box<dyn Error>> {
+            Ok((counters.get(t)?.get() as f64).max(1.0))
+        };
+        let keys = |cf: &str| -> Result<f64, Box<dyn Error>> {
+            Ok((db.estimate_keys(cf)? as f64).max(1.0))
+        };
+        Ok(Stats {
+            nodes: count(Node::TYPE)?,
+            edges: count(Edge::TYPE)?,
+            attributes: count(Attribute::TYPE)?,
+            node_names: keys(node::ByName.cf_name())?,
+            node_name_entries: keys(node::ByNameHash.cf_name())?,
+            node_types: count(db::COUNT_TYPES)?,
+            edge_types: count(db::COUNT_TYPES)?,
+        })
+    }
+
+    // Nodes per name; more than one when names repeat.
+    fn nodes_per_name(&self) -> f64 {
+        (self.node_name_entries / self.node_names).max(1.0)
+    }
+
+    fn node_selectivity(&self, c: &Condition) -> f64 {
+        match (c.name.as_str(), c.op) {
+            ("id", Op::Eq) => 1.0 / self.nodes,
+            ("name", Op::Eq) => self.nodes_per_name() / self.nodes,
+            ("type", Op::Eq) => 1.0 / self.node_types,
+            _ => selectivity(c.op),
+        }
+    }
+
+    fn edge_selectivity(&self, c: &Condition) -> f64 {
+        match (c.name.as_str(), c.op) {
+            ("id", Op::Eq) => 1.0 / self.edges,
+            ("type", Op::Eq) | ("name", Op::Eq) => 1.0 / self.edge_types,
+            ("head", Op::Eq) | ("tail", Op::Eq) => 1.0 / self.nodes,
+            _ => selectivity(c.op),
+        }
+    }
+
+    // Edges per node in the direction scanned.
+    fn degree(&self, x: &Expand) -> f64 {
+        let d = self.edges / self.nodes;
+        match (x.out, x.r#in) {
+            (true, true) => 2.0 * d,
+            _ => d,
+        }
+    }
+}
+
+fn selectivity(op: Op) -> f64 {
+    match op {
+        Op::Eq => EQ_SELECTIVITY,
+        Op::Ne => 1.0 - EQ_SELECTIVITY,
+        _ => RANGE_SELECTIVITY,
+    }
+}
+
+// Node and edge fields; other properties are attributes.
+pub(crate) fn is_field(name: &str) -> bool {
+    matches!(name, "id" | "name" | "type" | "head" | "tail")
+}
+
+// Estimated (keys read, rows) of the best access to the nodes matching the
+// conditions.
+fn node_access(stats: &Stats, conditions: &[Condition]) -> (f64, f64, Access) {
+    let rows = conditions
+        .iter()
+        .fold(stats.nodes, |r, c| r * stats.node_selectivity(c));
+    let eq = |name: &str| {
+        conditions
+            .iter()
+            .find(|c| c.name == name && c.op == Op::Eq)
+            .map(|c| c.value.clone())
+    };
+    // A full scan of the value index
+    let mut best = (stats.nodes, rows, Access::NodeScan);
+    let mut consider = |cost: f64, access: Access| {
+        if cost < best.0 {
+            best = (cost, rows, access);
+        }
+    };
+    if let Some(Value::Int(id)) = eq("id") {
+        consider(1.0, Access::NodeById(id as u64));
+    }
+    if let Some(Value::String(t)) = eq("type") {
+        // A scan of the entries of the type, then a get per node.
+        consider(2.0 * stats.nodes / stats.node_types, Access::NodeByType(t));
+    }
+    if let Some(Value::String(name)) = eq("name") {
+        // The name index only keeps the last node put with a name, so it
+        // is only used to count the names; all the nodes with the name are
+        // in the name hash index.
+        consider(2.0 * stats.nodes_per_name(), Access::NodeByNameHash(name));
+    }
+    let attrs: Vec<Condition> = conditions
+        .iter()
+        .filter(|c| !is_field(&c.name))
+        .cloned()
+        .collect();
+    if !attrs.is_empty() {
+        // Each condition is a range scan; then a get per node.
+        let scanned: f64 = attrs
+            .iter()
+            .map(|c| stats.attributes * selectivity(c.op))
+            .sum();
+        consider(scanned + rows, Access::NodeByAttributes(attrs));
+    }
+    best
+}
+
+fn expands(query: &Query, left: usize, right: usize) -> Vec<Expand> {
+    let mut expands = vec![];
+    for i in right..query.rels.len() {
+        let d = query.rels[i].direction;
+        expands.push(Expand {
+            from: i,
+            rel: i,
+            to: i + 1,
+            out: d != Direction::In,
+            r#in: d != Direction::Out,
+        });
+    }
+    for i in (0..left).rev() {
+        let d = query.rels[i].direction;
+        expands.push(Expand {
+            from: i + 1,
+            rel: i,
+            to: i,
+            out: d != Direction::Out,
+            r#in: d != Direction::In,
+        });
+    }
+    expands
+}
+
+// Picks the start with the least estimated keys read over the whole plan:
+// each node's best access, or the edges of a type from the type index,
+// followed by the expansions from it to both ends of the path.
+pub fn plan(query: &Query, stats: &Stats) -> Plan {
+    let node_sel: Vec<f64> = (0..query.nodes.len())
+        .map(|i| {
+            query
+                .conditions_on(Slot::Node(i))
+                .iter()
+                .map(|c| stats.node_selectivity(c))
+                .product()
+        })
+        .collect();
+    let rel_sel: Vec<f64> = (0..query.rels.len())
+        .map(|i| {
+            query
+                .conditions_on(Slot::Rel(i))
+                .iter()
+                .map(|c| stats.edge_selectivity(c))
+                .product()
+        })
+        .collect();
+
+    let mut candidates = vec![];
+    for i in 0..query.nodes.len() {
+        let (cost, rows, access) = node_access(stats, &query.conditions_on(Slot::Node(i)));
+        candidates.push((Slot::Node(i), access, cost, rows));
+    }
+    for (i, r) in query.rels.iter().enumerate() {
+        if let (Some(label), None) = (&r.label, r.hops) {
+            let both = if r.direction == Direction::Both {
+                2.0
+            } else {
+                1.0
+            };
+            let edges = stats.edges * rel_sel[i] * both;
+            // A scan of the entries of the type and a get per edge, then a
+            // get of the two nodes per edge
+            let cost = 2.0 * stats.edges / stats.edge_types + 2.0 * edges;
+            let rows = edges * node_sel[i] * node_sel[i + 1];
+            candidates.push((Slot::Rel(i), Access::EdgeByType(label.clone()), cost, rows));
+        }
+    }
+
+    let mut best: Option<Plan> = None;
+    for (start, access, cost, rows) in candidates {
+        let (left, right) = match start {
+            Slot::Node(i) => (i, i),
+            Slot::Rel(i) => (i, i + 1),
+        };
+        let mut plan = Plan {
+            start,
+            access,
+            expands: expands(query, left, right),
+            rows: vec![rows],
+            cost,
+        };
+        let mut rows = rows;
+        for x in plan.expands.iter() {
+            let (min, max) = query.rels[x.rel].hops.unwrap_or((1, 1));
+            let fanout = stats.degree(x) * rel_sel[x.rel];
+            let (mut frontier, mut reached) = (rows, if min == 0 { rows } else { 0.0 });
+            for hop in 1..=max {
+                // A scan per frontier node, then a get per edge
+                plan.cost += frontier + 2.0 * frontier * fanout;
+                frontier *= fanout;
+                if hop >= min {
+                    reached += frontier;
+                }
+            }
+            rows = reached * node_sel[x.to];
+            plan.rows.push(rows);
+        }
+        trace!("Candidate:\n{}", plan);
+        if best.as_ref().is_none_or(|b| plan.cost < b.cost) {
+            best = Some(plan);
+        }
+    }
+    best.unwrap()
+}
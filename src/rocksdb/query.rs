@@ -0,0 +1,933 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::{self, AttrKey, Condition, Op, Token};
+use crate::rocksdb::db::{self, Collect, HasKey, OperationsBuilder};
+use crate::rocksdb::edge;
+use crate::rocksdb::error::ErrBadQuery;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::planner::{self, Stats};
+use crate::rocksdb::store::Store;
+use crate::rocksdb::value::Value;
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+
+// Upper bound on the hops of an unbounded variable length relationship,
+// e.g. -[*]->.
+pub static MAX_HOPS: usize = 10;
+
+// A query like
+//
+//   MATCH (a:service)-[:depends-on*1..3]->(b) WHERE a.name = "api" RETURN b.name
+//
+// One path of nodes and relationships; conditions compare a property of a
+// variable with a literal and are joined by AND.  Properties other than id,
+// name and type are attributes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub nodes: Vec<NodePattern>,
+    pub rels: Vec<RelPattern>,
+    pub conditions: Vec<Predicate>,
+    pub distinct: bool,
+    pub items: Vec<Item>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodePattern {
+    pub var: Option<String>,
+    pub label: Option<String>,
+    pub props: Vec<(String, Value)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelPattern {
+    pub var: Option<String>,
+    pub label: Option<String>,
+    pub props: Vec<(String, Value)>,
+    pub direction: Direction,
+    // (min, max) hops for -[*min..max]->
+    pub hops: Option<(usize, usize)>,
+}
+
+// var.prop op value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    pub var: String,
+    pub condition: Condition,
+}
+
+// var or var.prop
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub var: String,
+    pub prop: Option<String>,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.prop {
+            Some(p) => write!(f, "{}.{}", self.var, p),
+            None => write!(f, "{}", self.var),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn err(&self, reason: &str) -> ErrBadQuery {
+        ErrBadQuery::new(self.text, &format!("{} at token {}", reason, self.at + 1))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn is_keyword(&self, k: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(n)) if n.eq_ignore_ascii_case(k))
+    }
+
+    fn punct(&mut self, c: char) -> Result<(), ErrBadQuery> {
+        if !self.is_punct(c) {
+            return Err(self.err(&format!("expected {}", c)));
+        }
+        self.at += 1;
+        Ok(())
+    }
+
+    fn op(&mut self, op: Op) -> Result<(), ErrBadQuery> {
+        if self.peek() != Some(&Token::Op(op)) {
+            return Err(self.err(&format!("expected {}", op)));
+        }
+        self.at += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, k: &str) -> Result<(), ErrBadQuery> {
+        if !self.is_keyword(k) {
+            return Err(self.err(&format!("expected {}", k)));
+        }
+        self.at += 1;
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String, ErrBadQuery> {
+        match self.peek() {
+            Some(Token::Name(n)) => {
+                let n = n.clone();
+                self.at += 1;
+                Ok(n)
+            }
+            _ => Err(self.err("expected a name")),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, ErrBadQuery> {
+        let v = match self.peek() {
+            Some(Token::Literal(v)) => v.clone(),
+            Some(Token::Name(n)) if n == "true" || n == "false" => Value::Bool(n == "true"),
+            _ => return Err(self.err("expected a value")),
+        };
+        self.at += 1;
+        Ok(v)
+    }
+
+    fn int(&mut self) -> Result<usize, ErrBadQuery> {
+        match self.literal()? {
+            Value::Int(v) if v >= 0 => Ok(v as usize),
+            _ => Err(self.err("expected a count")),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, ErrBadQuery> {
+        self.keyword("match")?;
+        let mut nodes = vec![self.node()?];
+        let mut rels = vec![];
+        while self.is_punct('-') || self.peek() == Some(&Token::Op(Op::Lt)) {
+            rels.push(self.rel()?);
+            nodes.push(self.node()?);
+        }
+
+        let mut conditions = vec![];
+        if self.is_keyword("where") {
+            self.at += 1;
+            loop {
+                let var = self.name()?;
+                self.punct('.')?;
+                let name = self.name()?;
+                let op = match self.peek() {
+                    Some(Token::Op(op)) => *op,
+                    _ => return Err(self.err("expected =, !=, <, <=, > or >=")),
+                };
+                self.at += 1;
+                let value = self.literal()?;
+                conditions.push(Predicate {
+                    var,
+                    condition: Condition { name, op, value },
+                });
+                if !self.is_keyword("and") {
+                    break;
+                }
+                self.at += 1;
+            }
+        }
+
+        self.keyword("return")?;
+        let distinct = self.is_keyword("distinct");
+        if distinct {
+            self.at += 1;
+        }
+        let mut items = vec![];
+        loop {
+            let var = self.name()?;
+            let prop = match self.is_punct('.') {
+                true => {
+                    self.at += 1;
+                    Some(self.name()?)
+                }
+                false => None,
+            };
+            items.push(Item { var, prop });
+            if !self.is_punct(',') {
+                break;
+            }
+            self.at += 1;
+        }
+
+        let mut limit = None;
+        if self.is_keyword("limit") {
+            self.at += 1;
+            limit = Some(self.int()?);
+        }
+        if self.peek().is_some() {
+            return Err(self.err("unexpected trailing input"));
+        }
+        Ok(Query {
+            nodes,
+            rels,
+            conditions,
+            distinct,
+            items,
+            limit,
+        })
+    }
+
+    // (var:label {prop: value, ...}), all parts optional
+    fn node(&mut self) -> Result<NodePattern, ErrBadQuery> {
+        self.punct('(')?;
+        let mut n = NodePattern::default();
+        if let Some(Token::Name(_)) = self.peek() {
+            n.var = Some(self.name()?);
+        }
+        if self.is_punct(':') {
+            self.at += 1;
+            n.label = Some(self.name()?);
+        }
+        n.props = self.props()?;
+        self.punct(')')?;
+        Ok(n)
+    }
+
+    fn props(&mut self) -> Result<Vec<(String, Value)>, ErrBadQuery> {
+        let mut props = vec![];
+        if !self.is_punct('{') {
+            return Ok(props);
+        }
+        self.at += 1;
+        while !self.is_punct('}') {
+            let name = self.name()?;
+            self.punct(':')?;
+            props.push((name, self.literal()?));
+            if !self.is_punct(',') {
+                break;
+            }
+            self.at += 1;
+        }
+        self.punct('}')?;
+        Ok(props)
+    }
+
+    // -[var:label*min..max {props}]->, <-[...]- or -[...]-; also --> and --
+    fn rel(&mut self) -> Result<RelPattern, ErrBadQuery> {
+        let left = self.peek() == Some(&Token::Op(Op::Lt));
+        if left {
+            self.at += 1;
+        }
+        self.punct('-')?;
+        let mut r = RelPattern {
+            var: None,
+            label: None,
+            props: vec![],
+            direction: Direction::Both,
+            hops: None,
+        };
+        if self.is_punct('[') {
+            self.at += 1;
+            if let Some(Token::Name(_)) = self.peek() {
+                r.var = Some(self.name()?);
+            }
+            if self.is_punct(':') {
+                self.at += 1;
+                r.label = Some(self.name()?);
+            }
+            if self.is_punct('*') {
+                self.at += 1;
+                r.hops = Some(self.hops()?);
+            }
+            r.props = self.props()?;
+            self.punct(']')?;
+        }
+        self.punct('-')?;
+        let right = self.peek() == Some(&Token::Op(Op::Gt));
+        if right {
+            self.op(Op::Gt)?;
+        }
+        r.direction = match (left, right) {
+            (false, true) => Direction::Out,
+            (true, false) => Direction::In,
+            (false, false) => Direction::Both,
+            (true, true) => return Err(self.err("a relationship has one direction")),
+        };
+        Ok(r)
+    }
+
+    // After *: nothing, n, min.., ..max or min..max
+    fn hops(&mut self) -> Result<(usize, usize), ErrBadQuery> {
+        let min = match self.peek() {
+            Some(Token::Literal(_)) => Some(self.int()?),
+            _ => None,
+        };
+        let (min, max) = if self.is_punct('.') {
+            self.punct('.')?;
+            self.punct('.')?;
+            let max = match self.peek() {
+                Some(Token::Literal(_)) => self.int()?,
+                _ => MAX_HOPS,
+            };
+            (min.unwrap_or(1), max)
+        } else {
+            match min {
+                Some(n) => (n, n),
+                None => (1, MAX_HOPS),
+            }
+        };
+        if min > max || max > MAX_HOPS {
+            return Err(self.err(&format!("hops must be in 0..{}", MAX_HOPS)));
+        }
+        Ok((min, max))
+    }
+}
+
+pub fn parse(text: &str) -> Result<Query, ErrBadQuery> {
+    let mut parser = Parser {
+        text,
+        tokens: attribute::tokenize(text)?,
+        at: 0,
+    };
+    let query = parser.query()?;
+    let slots = query.slots();
+    let vars = query
+        .nodes
+        .iter()
+        .map(|n| &n.var)
+        .chain(query.rels.iter().map(|r| &r.var))
+        .flatten()
+        .count();
+    if vars != slots.len() {
+        return Err(ErrBadQuery::new(text, "a variable can be bound once"));
+    }
+    for v in query
+        .conditions
+        .iter()
+        .map(|p| &p.var)
+        .chain(query.items.iter().map(|i| &i.var))
+    {
+        match slots.get(v) {
+            None => return Err(ErrBadQuery::new(text, &format!("unknown variable {}", v))),
+            Some(Slot::Rel(r)) if query.rels[*r].hops.is_some() => {
+                return Err(ErrBadQuery::new(
+                    text,
+                    &format!("{} has several hops; it can't be used", v),
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok(query)
+}
+
+// Where a variable is bound in the path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    Node(usize),
+    Rel(usize),
+}
+
+impl Query {
+    pub fn slots(&self) -> HashMap<String, Slot> {
+        let nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, n)| n.var.clone().map(|v| (v, Slot::Node(i))));
+        let rels = self
+            .rels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| r.var.clone().map(|v| (v, Slot::Rel(i))));
+        nodes.chain(rels).collect()
+    }
+
+    // The conditions on the slot: its label, inline properties and WHERE.
+    pub fn conditions_on(&self, slot: Slot) -> Vec<Condition> {
+        let (var, label, props) = match slot {
+            Slot::Node(i) => (
+                &self.nodes[i].var,
+                &self.nodes[i].label,
+                &self.nodes[i].props,
+            ),
+            Slot::Rel(i) => (&self.rels[i].var, &self.rels[i].label, &self.rels[i].props),
+        };
+        let mut conditions: Vec<Condition> = label
+            .iter()
+            .map(|l| Condition {
+                name: "type".to_string(),
+                op: Op::Eq,
+                value: Value::String(l.clone()),
+            })
+            .collect();
+        conditions.extend(props.iter().map(|(name, value)| Condition {
+            name: name.clone(),
+            op: Op::Eq,
+            value: value.clone(),
+        }));
+        if let Some(var) = var {
+            conditions.extend(
+                self.conditions
+                    .iter()
+                    .filter(|p| &p.var == var)
+                    .map(|p| p.condition.clone()),
+            );
+        }
+        conditions
+    }
+}
+
+// How the first rows are found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access {
+    // index.node.id
+    NodeById(u64),
+    // index.node.name_hash, which has all the nodes with the name
+    NodeByNameHash(String),
+    // index.node.type, which has all the nodes of the type
+    NodeByType(String),
+    // index.attr.value, for the attribute conditions
+    NodeByAttributes(Vec<Condition>),
+    // index.edge.type, which has all the edges of the type
+    EdgeByType(String),
+    // All of index.node.id
+    NodeScan,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::NodeById(id) => write!(f, "get {} id = {}", node::ById.cf_name(), id),
+            Access::NodeByNameHash(name) => {
+                write!(f, "scan {} name = {:?}", node::ByNameHash.cf_name(), name)
+            }
+            Access::NodeByType(t) => {
+                write!(f, "scan {} type = {:?}", node::ByType.cf_name(), t)
+            }
+            Access::NodeByAttributes(c) => write!(
+                f,
+                "range scan {} {}",
+                attribute::ByValue.cf_name(),
+                c.iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            ),
+            Access::EdgeByType(t) => write!(f, "scan {} type = {:?}", edge::ByType.cf_name(), t),
+            Access::NodeScan => write!(f, "scan {}", node::ById.cf_name()),
+        }
+    }
+}
+
+// From a bound node along a relationship to the next node of the path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expand {
+    pub from: usize,
+    pub rel: usize,
+    pub to: usize,
+    // Index scanned from the bound node, head-tail or tail-head, or both.
+    pub out: bool,
+    pub r#in: bool,
+}
+
+impl fmt::Display for Expand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cfs: Vec<&str> = [
+            (self.out, edge::ByHeadTail.cf_name()),
+            (self.r#in, edge::ByTailHead.cf_name()),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, cf)| *cf)
+        .collect();
+        write!(
+            f,
+            "expand node {} -> node {} along rel {}, scan {}",
+            self.from,
+            self.to,
+            self.rel,
+            cfs.join(" and ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    // The slot the access binds; a relationship binds its two nodes too.
+    pub start: Slot,
+    pub access: Access,
+    pub expands: Vec<Expand>,
+    // Estimated rows after the start and after each expand
+    pub rows: Vec<f64>,
+    // Estimated keys read
+    pub cost: f64,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = |i: usize| self.rows.get(i).cloned().unwrap_or_default();
+        writeln!(
+            f,
+            "start {:?}: {} (rows ~ {:.1})",
+            self.start,
+            self.access,
+            rows(0)
+        )?;
+        for (i, e) in self.expands.iter().enumerate() {
+            writeln!(f, "{} (rows ~ {:.1})", e, rows(i + 1))?;
+        }
+        writeln!(f, "cost ~ {:.1}", self.cost)
+    }
+}
+
+// A value in a result row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Node(Node),
+    Edge(Edge),
+    Value(Option<Value>),
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cell::Node(n) => write!(f, "({}:{} {:?})", n.id, n.type_name, n.name),
+            Cell::Edge(e) => write!(
+                f,
+                "[{}:{} {:?} {}->{}]",
+                e.id, e.type_name, e.name, e.head, e.tail
+            ),
+            Cell::Value(Some(v)) => write!(f, "{}", v),
+            Cell::Value(None) => write!(f, "null"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    nodes: Vec<Option<Node>>,
+    rels: Vec<Option<Edge>>,
+}
+
+fn node_property(db: &dyn Store, n: &Node, name: &str) -> Result<Option<Value>, Box<dyn Error>> {
+    Ok(match name {
+        "id" => Some(Value::Int(n.id as i64)),
+        "name" => Some(Value::String(n.name.clone())),
+        "type" => Some(Value::String(n.type_name.clone())),
+        _ => attribute_value(db, n.id, name)?,
+    })
+}
+
+fn edge_property(db: &dyn Store, e: &Edge, name: &str) -> Result<Option<Value>, Box<dyn Error>> {
+    Ok(match name {
+        "id" => Some(Value::Int(e.id as i64)),
+        "name" => Some(Value::String(e.name.clone())),
+        "type" => Some(Value::String(e.type_name.clone())),
+        "head" => Some(Value::Int(e.head as i64)),
+        "tail" => Some(Value::Int(e.tail as i64)),
+        _ => attribute_value(db, e.id, name)?,
+    })
+}
+
+fn attribute_value(
+    db: &dyn Store,
+    parent_id: u64,
+    name: &str,
+) -> Result<Option<Value>, Box<dyn Error>> {
+    let found = Attribute::operations(db).get(Attribute::id_from(AttrKey {
+        parent_id,
+        name: name.to_string(),
+    }))?;
+    Ok(found.and_then(|a| a.value()))
+}
+
+fn matches(v: Option<Value>, c: &Condition) -> bool {
+    match v.as_ref().and_then(|v| v.partial_cmp(&c.value)) {
+        None => false,
+        Some(o) => match c.op {
+            Op::Eq => o.is_eq(),
+            Op::Ne => o.is_ne(),
+            Op::Lt => o.is_lt(),
+            Op::Le => o.is_le(),
+            Op::Gt => o.is_gt(),
+            Op::Ge => o.is_ge(),
+        },
+    }
+}
+
+struct Executor<'a> {
+    db: &'a dyn Store,
+    query: &'a Query,
+    node_conditions: Vec<Vec<Condition>>,
+    rel_conditions: Vec<Vec<Condition>>,
+}
+
+impl Executor<'_> {
+    fn node_ok(&self, i: usize, n: &Node) -> Result<bool, Box<dyn Error>> {
+        for c in self.node_conditions[i].iter() {
+            if !matches(node_property(self.db, n, &c.name)?, c) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn edge_ok(&self, i: usize, e: &Edge) -> Result<bool, Box<dyn Error>> {
+        for c in self.rel_conditions[i].iter() {
+            if !matches(edge_property(self.db, e, &c.name)?, c) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn node(&self, id: u64) -> Result<Option<Node>, Box<dyn Error>> {
+        Node::operations(self.db).get(Node::id_from(id))
+    }
+
+    // Edges with the node as head (out) or tail (in), with the other end.
+    fn edges(&self, id: u64, out: bool, r#in: bool) -> Result<Vec<(Edge, u64)>, Box<dyn Error>> {
+        let ops = Edge::operations(self.db);
+        let mut found = vec![];
+        for (on, index) in [
+            (out, edge::ByHeadTail.cf_name()),
+            (r#in, edge::ByTailHead.cf_name()),
+        ] {
+            if !on {
+                continue;
+            }
+            let mut edges = vec![];
+            ops.scan(
+                index,
+                id.to_le_bytes().to_vec(),
+                Box::new(Collect(&mut edges)),
+            )?;
+            for e in edges {
+                let other = if e.head == id { e.tail } else { e.head };
+                found.push((e, other));
+            }
+        }
+        Ok(found)
+    }
+
+    fn start(&self, plan: &Plan) -> Result<Vec<Row>, Box<dyn Error>> {
+        let empty = Row {
+            nodes: vec![None; self.query.nodes.len()],
+            rels: vec![None; self.query.rels.len()],
+        };
+        let mut rows = vec![];
+        match (&plan.access, plan.start) {
+            (Access::EdgeByType(t), Slot::Rel(r)) => {
+                let mut edges = vec![];
+                if let Some(code) = db::find_type_code(self.db, t)? {
+                    Edge::operations(self.db).scan(
+                        edge::ByType.cf_name(),
+                        code.to_le_bytes().to_vec(),
+                        Box::new(Collect(&mut edges)),
+                    )?;
+                }
+                let d = self.query.rels[r].direction;
+                for e in edges {
+                    if !self.edge_ok(r, &e)? {
+                        continue;
+                    }
+                    let mut ends = vec![];
+                    if d != Direction::In {
+                        ends.push((e.head, e.tail));
+                    }
+                    if d != Direction::Out && e.head != e.tail {
+                        ends.push((e.tail, e.head));
+                    }
+                    for (left, right) in ends {
+                        let (left, right) = match (self.node(left)?, self.node(right)?) {
+                            (Some(left), Some(right)) => (left, right),
+                            _ => continue,
+                        };
+                        if self.node_ok(r, &left)? && self.node_ok(r + 1, &right)? {
+                            let mut row = empty.clone();
+                            row.nodes[r] = Some(left);
+                            row.nodes[r + 1] = Some(right);
+                            row.rels[r] = Some(e.clone());
+                            rows.push(row);
+                        }
+                    }
+                }
+            }
+            (access, Slot::Node(i)) => {
+                let nodes = match access {
+                    Access::NodeById(id) => self.node(*id)?.into_iter().collect(),
+                    Access::NodeByNameHash(name) => {
+                        let prefix = node::ByNameHash.append_prefix(&Node {
+                            name: name.clone(),
+                            ..Default::default()
+                        });
+                        let mut nodes = vec![];
+                        Node::operations(self.db).scan(
+                            node::ByNameHash.cf_name(),
+                            prefix,
+                            Box::new(Collect(&mut nodes)),
+                        )?;
+                        nodes
+                    }
+                    Access::NodeByType(t) => {
+                        let mut nodes = vec![];
+                        if let Some(code) = db::find_type_code(self.db, t)? {
+                            Node::operations(self.db).scan(
+                                node::ByType.cf_name(),
+                                code.to_le_bytes().to_vec(),
+                                Box::new(Collect(&mut nodes)),
+                            )?;
+                        }
+                        nodes
+                    }
+                    Access::NodeByAttributes(conditions) => {
+                        let mut nodes = vec![];
+                        for id in attribute::select(self.db, conditions)? {
+                            nodes.extend(self.node(id)?);
+                        }
+                        nodes
+                    }
+                    _ => {
+                        let mut nodes = vec![];
+                        Node::operations(self.db)
+                            .visit(Node::id_from(0), Box::new(Collect(&mut nodes)))?;
+                        nodes
+                    }
+                };
+                for n in nodes {
+                    if self.node_ok(i, &n)? {
+                        let mut row = empty.clone();
+                        row.nodes[i] = Some(n);
+                        rows.push(row);
+                    }
+                }
+            }
+            (access, slot) => {
+                return Err(format!("Bad plan: {} for {:?}", access, slot).into());
+            }
+        }
+        Ok(rows)
+    }
+
+    fn expand(&self, rows: Vec<Row>, x: &Expand) -> Result<Vec<Row>, Box<dyn Error>> {
+        let (min, max) = self.query.rels[x.rel].hops.unwrap_or((1, 1));
+        let mut out = vec![];
+        for row in rows {
+            let from = match &row.nodes[x.from] {
+                Some(n) => n.id,
+                None => continue,
+            };
+            // Paths of nodes from the bound node; none visits a node twice.
+            let mut paths: Vec<(Vec<u64>, Option<Edge>)> = vec![(vec![from], None)];
+            let mut ends: Vec<(u64, Option<Edge>)> = vec![];
+            if min == 0 {
+                ends.push((from, None));
+            }
+            for hop in 1..=max {
+                let mut next = vec![];
+                for (path, _) in paths.iter() {
+                    let last = *path.last().unwrap();
+                    for (e, other) in self.edges(last, x.out, x.r#in)? {
+                        if path.contains(&other) || !self.edge_ok(x.rel, &e)? {
+                            continue;
+                        }
+                        let mut p = path.clone();
+                        p.push(other);
+                        next.push((p, Some(e)));
+                    }
+                }
+                if hop >= min {
+                    ends.extend(next.iter().map(|(p, e)| (*p.last().unwrap(), e.clone())));
+                }
+                paths = next;
+                if paths.is_empty() {
+                    break;
+                }
+            }
+            for (id, e) in ends {
+                let n = match self.node(id)? {
+                    Some(n) => n,
+                    None => continue,
+                };
+                if !self.node_ok(x.to, &n)? {
+                    continue;
+                }
+                let mut r = row.clone();
+                r.nodes[x.to] = Some(n);
+                if self.query.rels[x.rel].hops.is_none() {
+                    r.rels[x.rel] = e;
+                }
+                out.push(r);
+            }
+        }
+        Ok(out)
+    }
+
+    fn cell(&self, row: &Row, slot: Slot, prop: &Option<String>) -> Result<Cell, Box<dyn Error>> {
+        Ok(match (slot, prop) {
+            (Slot::Node(i), None) => match &row.nodes[i] {
+                Some(n) => Cell::Node(n.clone()),
+                None => Cell::Value(None),
+            },
+            (Slot::Rel(i), None) => match &row.rels[i] {
+                Some(e) => Cell::Edge(e.clone()),
+                None => Cell::Value(None),
+            },
+            (Slot::Node(i), Some(p)) => Cell::Value(match &row.nodes[i] {
+                Some(n) => node_property(self.db, n, p)?,
+                None => None,
+            }),
+            (Slot::Rel(i), Some(p)) => Cell::Value(match &row.rels[i] {
+                Some(e) => edge_property(self.db, e, p)?,
+                None => None,
+            }),
+        })
+    }
+}
+
+pub struct Results {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+// Runs the plan for the query.
+pub fn execute(db: &dyn Store, query: &Query, plan: &Plan) -> Result<Results, Box<dyn Error>> {
+    let x = Executor {
+        db,
+        query,
+        node_conditions: (0..query.nodes.len())
+            .map(|i| query.conditions_on(Slot::Node(i)))
+            .collect(),
+        rel_conditions: (0..query.rels.len())
+            .map(|i| query.conditions_on(Slot::Rel(i)))
+            .collect(),
+    };
+    let mut rows = x.start(plan)?;
+    trace!("{} rows from {}", rows.len(), plan.access);
+    for e in plan.expands.iter() {
+        rows = x.expand(rows, e)?;
+        trace!("{} rows after {}", rows.len(), e);
+    }
+
+    let slots = query.slots();
+    let mut results = Results {
+        columns: query.items.iter().map(|i| i.to_string()).collect(),
+        rows: vec![],
+    };
+    let mut seen = BTreeSet::new();
+    for row in rows {
+        let mut cells = vec![];
+        for item in query.items.iter() {
+            cells.push(x.cell(&row, slots[&item.var], &item.prop)?);
+        }
+        if query.distinct
+            && !seen.insert(
+                cells
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\t"),
+            )
+        {
+            continue;
+        }
+        results.rows.push(cells);
+        if query.limit.is_some_and(|l| results.rows.len() >= l) {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+// MATCH (n) WHERE <conditions on n> RETURN n LIMIT n
+pub fn node_query(conditions: Vec<Condition>, limit: usize) -> Query {
+    let var = "n".to_string();
+    Query {
+        nodes: vec![NodePattern {
+            var: Some(var.clone()),
+            ..Default::default()
+        }],
+        rels: vec![],
+        conditions: conditions
+            .into_iter()
+            .map(|condition| Predicate {
+                var: var.clone(),
+                condition,
+            })
+            .collect(),
+        distinct: false,
+        items: vec![Item { var, prop: None }],
+        limit: Some(limit),
+    }
+}
+
+// Plans the query with the stats of the db.
+pub fn explain(db: &dyn Store, query: &Query) -> Result<Plan, Box<dyn Error>> {
+    let stats = Stats::gather(db)?;
+    trace!("{:?}", stats);
+    Ok(planner::plan(query, &stats))
+}
+
+// Parses, plans and runs the query.
+pub fn run(db: &dyn Store, text: &str) -> Result<Results, Box<dyn Error>> {
+    let query = parse(text)?;
+    let plan = explain(db, &query)?;
+    debug!("Plan:\n{}", plan);
+    execute(db, &query, &plan)
+}
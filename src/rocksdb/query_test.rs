@@ -0,0 +1,316 @@
+use std::error::Error;
+
+use crate::rocksdb::attribute::Op;
+use crate::rocksdb::db::{self, OperationsBuilder};
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::planner::{self, Stats};
+use crate::rocksdb::query::{self, Access, Cell, Direction, Slot};
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+#[test]
+fn test_parse() {
+    let q = query::parse(
+        r#"MATCH (a:service {tier: 1})-[:depends-on*1..3]->(b)<-[e]-(c) WHERE a.name = "api" RETURN DISTINCT b.name, c LIMIT 5"#,
+    )
+    .unwrap();
+    assert_eq!(3, q.nodes.len());
+    assert_eq!(Some("service".to_string()), q.nodes[0].label);
+    assert_eq!(vec![("tier".to_string(), Value::Int(1))], q.nodes[0].props);
+    assert_eq!(Some("depends-on".to_string()), q.rels[0].label);
+    assert_eq!(Some((1, 3)), q.rels[0].hops);
+    assert_eq!(Direction::Out, q.rels[0].direction);
+    assert_eq!(Direction::In, q.rels[1].direction);
+    assert_eq!(Some("e".to_string()), q.rels[1].var);
+    assert_eq!("a", q.conditions[0].var);
+    assert_eq!(Op::Eq, q.conditions[0].condition.op);
+    assert!(q.distinct);
+    assert_eq!(
+        vec!["b.name", "c"],
+        q.items.iter().map(|i| i.to_string()).collect::<Vec<_>>()
+    );
+    assert_eq!(Some(5), q.limit);
+
+    let q = query::parse("match (a)--(b)-[*2]-(c) return a").unwrap();
+    assert_eq!(Direction::Both, q.rels[0].direction);
+    assert_eq!(Some((2, 2)), q.rels[1].hops);
+    let q = query::parse("MATCH (a)-[*]->(b) RETURN b").unwrap();
+    assert_eq!(Some((1, query::MAX_HOPS)), q.rels[0].hops);
+
+    for bad in [
+        "",
+        "MATCH (a) RETURN",
+        "MATCH (a RETURN a",
+        "MATCH (a) RETURN b",
+        "MATCH (a)-[e*1..2]->(b) RETURN e",
+        "MATCH (a)-->(a) RETURN a",
+        "MATCH (a)<-->(b) RETURN a",
+        "MATCH (a)-[*3..1]->(b) RETURN a",
+        "MATCH (a)-[*11]->(b) RETURN a",
+        "MATCH (a)-[*11..]->(b) RETURN a",
+        "MATCH (a) WHERE a.x RETURN a",
+        "MATCH (a) RETURN a LIMIT 1 2",
+    ] {
+        assert!(query::parse(bad).is_err(), "{:?}", bad);
+    }
+}
+
+fn stats() -> Stats {
+    Stats {
+        nodes: 1000.0,
+        edges: 3000.0,
+        attributes: 2000.0,
+        node_names: 1000.0,
+        node_name_entries: 1000.0,
+        node_types: 10.0,
+        edge_types: 5.0,
+    }
+}
+
+#[test]
+fn test_plan() {
+    let plan_with = |stats: &Stats, text: &str| planner::plan(&query::parse(text).unwrap(), stats);
+    let plan = |text: &str| plan_with(&stats(), text);
+
+    let p = plan(r#"MATCH (a:service)-[:depends-on]->(b) WHERE a.name = "api" RETURN b"#);
+    assert_eq!(Slot::Node(0), p.start);
+    assert_eq!(Access::NodeByNameHash("api".into()), p.access);
+    assert_eq!(1, p.expands.len());
+    assert!(p.expands[0].out && !p.expands[0].r#in);
+    assert_eq!(2, p.rows.len());
+    assert!(p.cost < 10.0, "{}", p);
+
+    let p = plan(r#"MATCH (a)-[:depends-on]->(b {name: "db"}) RETURN a"#);
+    assert_eq!(Slot::Node(1), p.start);
+    assert_eq!((1, 0), (p.expands[0].from, p.expands[0].to));
+    assert!(!p.expands[0].out && p.expands[0].r#in);
+
+    let p = plan(r#"MATCH (a)-[:depends-on]->(b) WHERE b.id = 7 RETURN a"#);
+    assert_eq!(Access::NodeById(7), p.access);
+
+    // Getting the few databases beats getting the edges of the type
+    let p = plan(r#"MATCH (a)-[:depends-on]->(b:database) RETURN a"#);
+    assert_eq!(
+        (Slot::Node(1), Access::NodeByType("database".into())),
+        (p.start, p.access)
+    );
+    // unless there are fewer edges than nodes
+    let sparse = Stats {
+        edges: 100.0,
+        ..stats()
+    };
+    let p = plan_with(&sparse, r#"MATCH (a)-[:depends-on]->(b:database) RETURN a"#);
+    assert_eq!(Slot::Rel(0), p.start);
+    assert_eq!(Access::EdgeByType("depends-on".into()), p.access);
+    assert!(p.to_string().contains("index.edge.type"), "{}", p);
+    assert!(p.expands.is_empty());
+
+    let p = plan(r#"MATCH (a)-->(b) WHERE a.size = 100 RETURN a"#);
+    assert!(matches!(p.access, Access::NodeByAttributes(ref c) if c.len() == 1));
+    // Too many attributes to beat a scan
+    let attrs = Stats {
+        attributes: 100000.0,
+        ..stats()
+    };
+    let p = plan_with(&attrs, r#"MATCH (a)-->(b) WHERE a.size > 100 RETURN a"#);
+    assert_eq!(Access::NodeScan, p.access);
+    // or the nodes of the type
+    let p = plan(r#"MATCH (a:service)-->(b) WHERE a.size > 100 RETURN a"#);
+    assert_eq!(Access::NodeByType("service".into()), p.access);
+
+    let p = plan("MATCH (a:service)-->(b) RETURN a");
+    assert_eq!(Access::NodeByType("service".into()), p.access);
+    assert!(p.to_string().contains("index.node.type"), "{}", p);
+    // unless most nodes are of the type
+    let few_types = Stats {
+        node_types: 1.0,
+        ..stats()
+    };
+    let p = plan_with(&few_types, "MATCH (a:service)-->(b) RETURN a");
+    assert_eq!(Access::NodeScan, p.access);
+
+    // Repeated names make the lookup read more
+    let repeated = Stats {
+        node_name_entries: 2000.0,
+        ..stats()
+    };
+    let p = plan_with(&repeated, r#"MATCH (a {name: "api"}) RETURN a"#);
+    assert_eq!(Access::NodeByNameHash("api".into()), p.access);
+    assert!(p.to_string().contains("index.node.name_hash"), "{}", p);
+    assert!(p.cost > plan(r#"MATCH (a {name: "api"}) RETURN a"#).cost);
+}
+
+fn names(db: &dyn Store, text: &str) -> Vec<String> {
+    let results = query::run(db, text).unwrap();
+    let mut names: Vec<String> = results
+        .rows
+        .iter()
+        .map(|r| {
+            r.iter()
+                .map(|c| match c {
+                    Cell::Value(Some(Value::String(s))) => s.clone(),
+                    c => c.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn exercise(db: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut nodes = Node::operations(db);
+    let mut ids = std::collections::HashMap::new();
+    for (name, type_name, size) in [
+        ("api", "service", 300),
+        ("auth", "service", 50),
+        ("cache", "service", 150),
+        ("db", "database", 900),
+        ("logs", "service", 10),
+    ] {
+        let mut n = Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        };
+        nodes.put(&mut n)?;
+        Attribute::operations(db).put(&mut Attribute::typed(n.id, "size", &Value::Int(size)))?;
+        ids.insert(name, n.id);
+    }
+    let mut edges = Edge::operations(db);
+    for (head, tail, name) in [
+        ("api", "auth", "depends-on"),
+        ("auth", "db", "depends-on"),
+        ("api", "cache", "depends-on"),
+        ("cache", "db", "depends-on"),
+        ("db", "logs", "depends-on"),
+        ("logs", "api", "reports-to"),
+    ] {
+        edges.put(&mut Edge {
+            head: ids[head],
+            tail: ids[tail],
+            name: name.into(),
+            type_name: name.into(),
+            ..Default::default()
+        })?;
+    }
+
+    assert_eq!(
+        vec!["auth", "cache", "db", "logs"],
+        names(
+            db,
+            r#"MATCH (a:service)-[:depends-on*1..3]->(b) WHERE a.name = "api" RETURN DISTINCT b.name"#
+        )
+    );
+    // Without DISTINCT, one row per path
+    assert_eq!(
+        vec!["auth", "cache", "db", "db"],
+        names(
+            db,
+            r#"MATCH (a {name: "api"})-[:depends-on*..2]->(b) RETURN b.name"#
+        )
+    );
+    assert_eq!(
+        vec!["auth,depends-on", "cache,depends-on"],
+        names(
+            db,
+            "MATCH (a)-[e:depends-on]->(b:database) RETURN a.name, e.type"
+        )
+    );
+    assert_eq!(
+        vec!["auth", "cache"],
+        names(
+            db,
+            r#"MATCH (b)<-[:depends-on]-(a) WHERE b.name = "db" RETURN a.name"#
+        )
+    );
+    assert_eq!(
+        vec!["api", "db"],
+        names(db, r#"MATCH (a {name: "logs"})--(b) RETURN b.name"#)
+    );
+    assert_eq!(
+        vec!["api", "cache"],
+        names(
+            db,
+            r#"MATCH (a:service) WHERE a.size > 100 AND a.size <= 300 RETURN a.name"#
+        )
+    );
+    assert_eq!(
+        vec!["api,logs", "api,logs"],
+        names(
+            db,
+            r#"MATCH (a)-[:reports-to]->(b)-[:depends-on]->(c) RETURN b.name, a.name"#
+        )
+    );
+    assert_eq!(
+        1,
+        query::run(db, "MATCH (a:service) RETURN a LIMIT 1")?
+            .rows
+            .len()
+    );
+    assert!(names(db, r#"MATCH (a {name: "nope"})-->(b) RETURN b"#).is_empty());
+
+    // Whole nodes and edges, and attributes that aren't there
+    let results = query::run(
+        db,
+        r#"MATCH (a {name: "db"})-[e]->(b) RETURN a, e, b.color"#,
+    )?;
+    assert_eq!(vec!["a", "e", "b.color"], results.columns);
+    assert!(matches!(&results.rows[0][0], Cell::Node(n) if n.name == "db"));
+    assert!(matches!(&results.rows[0][1], Cell::Edge(e) if e.tail == ids["logs"]));
+    assert_eq!(Cell::Value(None), results.rows[0][2]);
+    Ok(())
+}
+
+#[test]
+fn test_query_mem_store() -> Result<(), Box<dyn Error>> {
+    exercise(&MemStore::new(&All))
+}
+
+#[test]
+fn test_query() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    exercise(&db)
+}
+
+#[test]
+fn test_repeated_names() -> Result<(), Box<dyn Error>> {
+    let db = MemStore::new(&All);
+    let mut nodes = Node::operations(&db);
+    for type_name in ["service", "service", "database"] {
+        nodes.put(&mut Node {
+            name: "api".into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        })?;
+    }
+    // Enough others that a scan costs more than the name hash lookup
+    for i in 0..10 {
+        nodes.put(&mut Node {
+            name: format!("host-{}", i),
+            type_name: "host".into(),
+            ..Default::default()
+        })?;
+    }
+    let stats = Stats::gather(&db)?;
+    assert_eq!(
+        (13.0, 11.0, 13.0),
+        (stats.nodes, stats.node_names, stats.node_name_entries)
+    );
+
+    let q = query::parse(r#"MATCH (a {name: "api"}) RETURN a.type"#)?;
+    // The name index only has one of them
+    assert_eq!(
+        Access::NodeByNameHash("api".into()),
+        query::explain(&db, &q)?.access
+    );
+    assert_eq!(
+        vec!["database", "service", "service"],
+        names(&db, r#"MATCH (a {name: "api"}) RETURN a.type"#)
+    );
+    Ok(())
+}
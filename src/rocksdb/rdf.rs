@@ -0,0 +1,982 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute;
+use crate::rocksdb::db::{self, Collect, HasKey, OperationsBuilder, Visitor};
+use crate::rocksdb::edge;
+use crate::rocksdb::error::ErrBadImport;
+use crate::rocksdb::export::Counts;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::store::{Batch, Overlay, Store};
+use crate::rocksdb::value::{self, Value};
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+pub static RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+pub static RDFS: &str = "http://www.w3.org/2000/01/rdf-schema#";
+pub static XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+pub static DEFAULT_BASE: &str = "http://example.org/";
+
+// Triples imported per commit.
+pub static IMPORT_BATCH: usize = 10_000;
+
+// Keys in cf.system from the IRIs, and _:labels of blank nodes, of the file
+// being imported to their node ids.  Cleared before and after each import.
+static IMPORT_NODE_PREFIX: &str = "rdf.import.node.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RdfFormat {
+    NTriples,
+    Turtle,
+}
+
+static SECTIONS: [&str; 4] = ["node", "type", "edge", "attr"];
+
+// How the graph maps to IRIs under the base:
+//
+//   node/<id>      nodes, with rdf:type type/<type name> and rdfs:label name
+//   edge/<name>    predicates of the edges
+//   attr/<name>    predicates of the literal attributes
+//
+// Names are percent encoded, except those that are IRIs or prefixed by one
+// of the prefixes, which go out as is.  The prefixes shorten IRIs when writing Turtle,
+// and name the nodes, types, edges and attributes of IRIs from elsewhere on
+// import, e.g. foaf:knows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vocabulary {
+    pub base: String,
+    pub prefixes: BTreeMap<String, String>,
+}
+
+impl Vocabulary {
+    // The rdf, rdfs, xsd and base prefixes, then the given ones.
+    pub fn new(base: &str, prefixes: BTreeMap<String, String>) -> Vocabulary {
+        let mut all = BTreeMap::new();
+        all.insert("rdf".to_string(), RDF.to_string());
+        all.insert("rdfs".to_string(), RDFS.to_string());
+        all.insert("xsd".to_string(), XSD.to_string());
+        for section in SECTIONS {
+            all.insert(section.to_string(), format!("{}{}/", base, section));
+        }
+        all.extend(prefixes);
+        Vocabulary {
+            base: base.to_string(),
+            prefixes: all,
+        }
+    }
+
+    // With the prefixes of a YAML map, e.g. "foaf: http://xmlns.com/foaf/0.1/".
+    pub fn load(base: &str, path: &str) -> Result<Vocabulary, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Vocabulary::new(base, serde_yaml::from_str(&text)?))
+    }
+
+    // The inverse of name: a prefixed or full IRI name as is, else the name
+    // under the base section.
+    fn iri(&self, section: &str, name: &str) -> String {
+        if name.contains("://") {
+            return name.to_string();
+        }
+        let expanded = name.split_once(':').and_then(|(p, local)| {
+            match SECTIONS.contains(&p) || local.is_empty() {
+                true => None,
+                false => self.prefixes.get(p).map(|ns| format!("{}{}", ns, local)),
+            }
+        });
+        expanded.unwrap_or_else(|| format!("{}{}/{}", self.base, section, encode(name)))
+    }
+
+    // The name under the base section, else the IRI shortened by the longest
+    // prefix, else the IRI.
+    fn name(&self, iri: &str, section: &str) -> String {
+        let ours = format!("{}{}/", self.base, section);
+        match iri.strip_prefix(&ours) {
+            Some(name) if !name.is_empty() => decode(name),
+            _ => match self.shorten(iri) {
+                Some((prefix, local)) => format!("{}:{}", prefix, local),
+                None => iri.to_string(),
+            },
+        }
+    }
+
+    fn shorten<'a>(&'a self, iri: &'a str) -> Option<(&'a str, &'a str)> {
+        self.prefixes
+            .iter()
+            .filter_map(|(p, ns)| iri.strip_prefix(ns.as_str()).map(|local| (p, ns, local)))
+            .filter(|(_, _, local)| !local.is_empty())
+            .max_by_key(|(_, ns, _)| ns.len())
+            .map(|(p, _, local)| (p.as_str(), local))
+    }
+}
+
+impl Default for Vocabulary {
+    fn default() -> Vocabulary {
+        Vocabulary::new(DEFAULT_BASE, BTreeMap::new())
+    }
+}
+
+// Everything but unreserved characters as %XX.
+fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Iri(String),
+    Blank(String),
+    Literal {
+        value: String,
+        datatype: String,
+        lang: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triple {
+    pub subject: Term,
+    pub predicate: String,
+    pub object: Term,
+}
+
+fn literal(v: &Value) -> Result<Term, Box<dyn Error>> {
+    let (value, datatype) = match v {
+        Value::Int(v) => (v.to_string(), "integer"),
+        Value::Float(v) => (format!("{:?}", v), "double"),
+        Value::Bool(v) => (v.to_string(), "boolean"),
+        Value::String(v) => (v.clone(), "string"),
+        Value::Timestamp(v) => (
+            OffsetDateTime::from_unix_timestamp_nanos(*v)?.format(&Rfc3339)?,
+            "dateTime",
+        ),
+    };
+    Ok(Term::Literal {
+        value,
+        datatype: format!("{}{}", XSD, datatype),
+        lang: None,
+    })
+}
+
+// The value of a literal by its XSD type; strings if it doesn't parse.
+fn value_of(value: &str, datatype: &str) -> Value {
+    let content_type = match datatype.strip_prefix(XSD).unwrap_or_default() {
+        "integer" | "long" | "int" | "short" | "byte" | "nonNegativeInteger"
+        | "positiveInteger" | "negativeInteger" | "nonPositiveInteger" | "unsignedLong"
+        | "unsignedInt" | "unsignedShort" | "unsignedByte" => value::INT,
+        "double" | "float" | "decimal" => value::FLOAT,
+        "boolean" => value::BOOL,
+        "dateTime" | "dateTimeStamp" => value::TIMESTAMP,
+        _ => value::STRING,
+    };
+    Value::parse(content_type, value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+// Writes each node with its type, name, attributes and out edges, one node at
+// a time.  Edge types and the attributes of edges have no place in a triple
+// and are not written.
+pub fn export_rdf(
+    db: &dyn Store,
+    vocabulary: &Vocabulary,
+    format: RdfFormat,
+    out: &mut dyn Write,
+) -> Result<Counts, Box<dyn Error>> {
+    if format == RdfFormat::Turtle {
+        for (prefix, ns) in vocabulary.prefixes.iter() {
+            writeln!(out, "@prefix {}: <{}> .", prefix, ns)?;
+        }
+    }
+    let mut subjects = Subjects {
+        db,
+        vocabulary,
+        format,
+        out,
+        counts: Counts::default(),
+        error: None,
+    };
+    Node::operations(db).visit(Node::id_from(0), Box::new(&mut subjects))?;
+    match subjects.error {
+        Some(e) => Err(e),
+        None => Ok(subjects.counts),
+    }
+}
+
+// Writes the triples of each node visited; stops at the first error.
+struct Subjects<'a> {
+    db: &'a dyn Store,
+    vocabulary: &'a Vocabulary,
+    format: RdfFormat,
+    out: &'a mut dyn Write,
+    counts: Counts,
+    error: Option<Box<dyn Error>>,
+}
+
+impl Subjects<'_> {
+    fn write(&mut self, node: Node) -> Result<(), Box<dyn Error>> {
+        let v = self.vocabulary;
+        let mut objects = vec![
+            (
+                format!("{}type", RDF),
+                Term::Iri(v.iri("type", &node.type_name)),
+            ),
+            (
+                format!("{}label", RDFS),
+                literal(&Value::String(node.name.clone()))?,
+            ),
+        ];
+        let mut attributes = vec![];
+        attribute::visit_parent(self.db, node.id, Box::new(Collect(&mut attributes)))?;
+        for a in attributes {
+            match a.value() {
+                Some(value) => {
+                    objects.push((v.iri("attr", &a.name), literal(&value)?));
+                    self.counts.attributes += 1;
+                }
+                None => warn!("Skipped attribute {:?} of {}: not typed", a.name, node.id),
+            }
+        }
+        let mut edges = vec![];
+        Edge::operations(self.db).scan(
+            edge::ByHeadTail.cf_name(),
+            node.id.to_le_bytes().to_vec(),
+            Box::new(Collect(&mut edges)),
+        )?;
+        for e in edges {
+            objects.push((
+                v.iri("edge", &e.name),
+                Term::Iri(v.iri("node", &e.tail.to_string())),
+            ));
+            self.counts.edges += 1;
+        }
+        let subject = Term::Iri(v.iri("node", &node.id.to_string()));
+        match self.format {
+            RdfFormat::NTriples => {
+                for (p, o) in objects {
+                    writeln!(
+                        self.out,
+                        "{} <{}> {} .",
+                        term(&subject, None),
+                        p,
+                        term(&o, None)
+                    )?;
+                }
+            }
+            RdfFormat::Turtle => {
+                writeln!(self.out)?;
+                writeln!(self.out, "{}", term(&subject, Some(v)))?;
+                // Objects of the same predicate, e.g. the edges of a name,
+                // share a line
+                let mut grouped: Vec<(&String, Vec<String>)> = vec![];
+                for (p, o) in objects.iter() {
+                    match grouped.last_mut() {
+                        Some((last, os)) if *last == p => os.push(term(o, Some(v))),
+                        _ => grouped.push((p, vec![term(o, Some(v))])),
+                    }
+                }
+                let last = grouped.len() - 1;
+                for (i, (p, os)) in grouped.into_iter().enumerate() {
+                    let p = match p.strip_prefix(RDF) {
+                        Some("type") => "a".to_string(),
+                        _ => term(&Term::Iri(p.clone()), Some(v)),
+                    };
+                    let end = if i == last { "." } else { ";" };
+                    writeln!(self.out, "    {} {} {}", p, os.join(", "), end)?;
+                }
+            }
+        }
+        self.counts.nodes += 1;
+        Ok(())
+    }
+}
+
+impl Visitor<Node> for &mut Subjects<'_> {
+    fn visit(&mut self, node: Node) -> bool {
+        match self.write(node) {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+// N-Triples syntax, or Turtle with the prefixes.
+fn term(t: &Term, prefixes: Option<&Vocabulary>) -> String {
+    let iri = |iri: &str| match prefixes.and_then(|v| v.shorten(iri)) {
+        Some((p, local)) if is_local(local) => format!("{}:{}", p, local),
+        _ => format!("<{}>", iri.replace('>', "%3E")),
+    };
+    match t {
+        Term::Iri(i) => iri(i),
+        Term::Blank(b) => format!("_:{}", b),
+        Term::Literal {
+            value,
+            datatype,
+            lang,
+        } => {
+            let mut s = String::from("\"");
+            for c in value.chars() {
+                match c {
+                    '"' => s.push_str("\\\""),
+                    '\\' => s.push_str("\\\\"),
+                    '\n' => s.push_str("\\n"),
+                    '\r' => s.push_str("\\r"),
+                    '\t' => s.push_str("\\t"),
+                    c => s.push(c),
+                }
+            }
+            s.push('"');
+            match lang {
+                Some(lang) => format!("{}@{}", s, lang),
+                None if datatype == &format!("{}string", XSD) => s,
+                None => format!("{}^^{}", s, iri(datatype)),
+            }
+        }
+    }
+}
+
+// A local name Turtle takes without escapes.
+fn is_local(s: &str) -> bool {
+    let ok = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    s.chars().all(|c| ok(c) || c == '.')
+        && s.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        && s.ends_with(ok)
+}
+
+// Reads N-Triples or Turtle a statement at a time, committing every
+// batch_triples triples, so neither the writes nor the map of IRIs to node
+// ids, which is kept in the db, grow in memory with the file.  Subjects and
+// IRI objects are nodes, named by rdfs:label and typed by rdf:type when the
+// file has them; other triples are edges, or attributes if the object is a
+// literal.  Ids are always new.  A bad file leaves the batches before the
+// error imported.
+pub fn import_rdf(
+    db: Rc<dyn Store>,
+    vocabulary: &Vocabulary,
+    input: &mut dyn BufRead,
+    batch_triples: usize,
+) -> Result<Counts, Box<dyn Error>> {
+    forget_nodes(db.as_ref(), batch_triples)?;
+    let mut importer = Importer {
+        overlay: Overlay::new(db.clone()),
+        db: db.clone(),
+        vocabulary,
+        batch_triples,
+        pending: 0,
+        counts: Counts::default(),
+    };
+    read_triples(input, &mut |t| importer.triple(t))?;
+    importer.commit()?;
+    forget_nodes(db.as_ref(), batch_triples)?;
+    Ok(importer.counts)
+}
+
+// Deletes the node ids of the last import, batch_size keys per commit.
+fn forget_nodes(db: &dyn Store, batch_size: usize) -> Result<(), Box<dyn Error>> {
+    let prefix = IMPORT_NODE_PREFIX.as_bytes();
+    loop {
+        let mut txn = Batch::default();
+        db.scan_from(db::CF_SYSTEM, prefix, &mut |k, _| {
+            if !k.starts_with(prefix) {
+                return false;
+            }
+            txn.delete(db::CF_SYSTEM, k);
+            txn.len() < batch_size
+        })?;
+        if txn.is_empty() {
+            return Ok(());
+        }
+        db.commit(txn)?;
+    }
+}
+
+// Calls emit with each triple of N-Triples or Turtle as it's read.
+pub fn read_triples(
+    input: &mut dyn BufRead,
+    emit: &mut dyn FnMut(Triple) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    Parser::new(input).parse(emit)
+}
+
+struct Importer<'a> {
+    db: Rc<dyn Store>,
+    // The writes since the last commit
+    overlay: Overlay,
+    vocabulary: &'a Vocabulary,
+    batch_triples: usize,
+    pending: usize,
+    counts: Counts,
+}
+
+impl Importer<'_> {
+    fn triple(&mut self, t: Triple) -> Result<(), Box<dyn Error>> {
+        self.put(t)?;
+        self.pending += 1;
+        if self.pending >= self.batch_triples {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+        trace!("Committing {} triples", self.pending);
+        self.db.commit(self.overlay.batch())?;
+        self.overlay = Overlay::new(self.db.clone());
+        self.pending = 0;
+        Ok(())
+    }
+
+    fn put(&mut self, t: Triple) -> Result<(), Box<dyn Error>> {
+        let v = self.vocabulary;
+        let subject = self.node(&t.subject)?;
+        let p = t.predicate.as_str();
+        match t.object {
+            Term::Iri(o) if p == format!("{}type", RDF) => {
+                self.update(subject, |n| n.type_name = v.name(&o, "type"))
+            }
+            Term::Literal { value, .. } if p == format!("{}label", RDFS) => {
+                self.update(subject, |n| n.name = value)
+            }
+            Term::Literal {
+                value, datatype, ..
+            } => {
+                let value = value_of(&value, &datatype);
+                Attribute::operations(&self.overlay).put(&mut Attribute::typed(
+                    subject,
+                    &v.name(p, "attr"),
+                    &value,
+                ))?;
+                self.counts.attributes += 1;
+                Ok(())
+            }
+            object => {
+                let name = v.name(p, "edge");
+                let tail = self.node(&object)?;
+                Edge::operations(&self.overlay).put(&mut Edge {
+                    type_name: name.clone(),
+                    name,
+                    head: subject,
+                    tail,
+                    ..Default::default()
+                })?;
+                self.counts.edges += 1;
+                Ok(())
+            }
+        }
+    }
+
+    // The node of the IRI or blank node, added the first time it's seen.
+    fn node(&mut self, t: &Term) -> Result<u64, Box<dyn Error>> {
+        let (key, name) = match t {
+            Term::Iri(iri) => (iri.clone(), self.vocabulary.name(iri, "node")),
+            Term::Blank(b) => (format!("_:{}", b), format!("_:{}", b)),
+            Term::Literal { .. } => return Err("a literal is not a node".into()),
+        };
+        let key = format!("{}{}", IMPORT_NODE_PREFIX, key);
+        if let Some(v) = self.overlay.get_value(db::CF_SYSTEM, key.as_bytes())? {
+            if let Ok(le) = v.as_slice().try_into() {
+                return Ok(u64::from_le_bytes(le));
+            }
+        }
+        let mut node = Node {
+            name,
+            type_name: "entity".into(),
+            ..Default::default()
+        };
+        Node::operations(&self.overlay).put(&mut node)?;
+        self.overlay
+            .put_value(db::CF_SYSTEM, key.as_bytes(), &node.id.to_le_bytes())?;
+        self.counts.nodes += 1;
+        Ok(node.id)
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Node)) -> Result<(), Box<dyn Error>> {
+        let mut nodes = Node::operations(&self.overlay);
+        if let Some(mut node) = nodes.get(Node::id_from(id))? {
+            f(&mut node);
+            nodes.put(&mut node)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Iri(String),
+    PName(String, String),
+    Blank(String),
+    String(String),
+    LangTag(String),
+    Datatype,
+    Number(String, &'static str),
+    Bool(bool),
+    A,
+    // True for @prefix and @base, which end with a dot
+    Prefix(bool),
+    Base(bool),
+    Dot,
+    Semicolon,
+    Comma,
+    Open,
+    Close,
+    Paren,
+    Eof,
+}
+
+// Characters of the input, read a line at a time.
+struct Chars<'a> {
+    input: &'a mut dyn BufRead,
+    buf: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Chars<'_> {
+    fn peek_at(&mut self, n: usize) -> Result<Option<char>, Box<dyn Error>> {
+        while self.pos + n >= self.buf.len() {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.buf.extend(line.chars());
+        }
+        Ok(Some(self.buf[self.pos + n]))
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, Box<dyn Error>> {
+        self.peek_at(0)
+    }
+
+    fn next(&mut self) -> Result<Option<char>, Box<dyn Error>> {
+        let c = self.peek()?;
+        if let Some(c) = c {
+            self.pos += 1;
+            if c == '\n' {
+                self.line += 1;
+            }
+        }
+        Ok(c)
+    }
+}
+
+// A Turtle parser, which also reads N-Triples.  Collections are not
+// supported, and relative IRIs are simply appended to the base.
+struct Parser<'a> {
+    chars: Chars<'a>,
+    peeked: Option<Token>,
+    prefixes: HashMap<String, String>,
+    base: String,
+    blanks: u64,
+}
+
+type Emit<'e> = dyn FnMut(Triple) -> Result<(), Box<dyn Error>> + 'e;
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a mut dyn BufRead) -> Parser<'a> {
+        Parser {
+            chars: Chars {
+                input,
+                buf: vec![],
+                pos: 0,
+                line: 1,
+            },
+            peeked: None,
+            prefixes: HashMap::new(),
+            base: String::new(),
+            blanks: 0,
+        }
+    }
+
+    fn bad(&self, reason: &str) -> Box<dyn Error> {
+        Box::new(ErrBadImport::new(self.chars.line, reason))
+    }
+
+    fn parse(&mut self, emit: &mut Emit) -> Result<(), Box<dyn Error>> {
+        loop {
+            match self.next()? {
+                Token::Eof => return Ok(()),
+                Token::Prefix(dot) => {
+                    let prefix = match self.next()? {
+                        Token::PName(p, local) if local.is_empty() => p,
+                        t => return Err(self.bad(&format!("expected a prefix, not {:?}", t))),
+                    };
+                    let iri = self.iri_ref()?;
+                    self.prefixes.insert(prefix, iri);
+                    if dot {
+                        self.expect(Token::Dot)?;
+                    }
+                }
+                Token::Base(dot) => {
+                    self.base = self.iri_ref()?;
+                    if dot {
+                        self.expect(Token::Dot)?;
+                    }
+                }
+                Token::Open => {
+                    let subject = self.blank_list(emit)?;
+                    if self.peek()? != &Token::Dot {
+                        self.predicate_objects(&subject, emit)?;
+                    }
+                    self.expect(Token::Dot)?;
+                }
+                t => {
+                    let subject = match self.term(t)? {
+                        Term::Literal { .. } => return Err(self.bad("a literal is not a subject")),
+                        s => s,
+                    };
+                    self.predicate_objects(&subject, emit)?;
+                    self.expect(Token::Dot)?;
+                }
+            }
+        }
+    }
+
+    fn predicate_objects(&mut self, subject: &Term, emit: &mut Emit) -> Result<(), Box<dyn Error>> {
+        loop {
+            let predicate = match self.next()? {
+                Token::A => format!("{}type", RDF),
+                t => match self.term(t)? {
+                    Term::Iri(iri) => iri,
+                    _ => return Err(self.bad("a predicate must be an IRI")),
+                },
+            };
+            loop {
+                let object = match self.next()? {
+                    Token::Open => self.blank_list(emit)?,
+                    t => self.term(t)?,
+                };
+                let line = self.chars.line;
+                emit(Triple {
+                    subject: subject.clone(),
+                    predicate: predicate.clone(),
+                    object,
+                })
+                .map_err(|e| ErrBadImport::new(line, &e.to_string()))?;
+                if self.peek()? != &Token::Comma {
+                    break;
+                }
+                self.next()?;
+            }
+            if self.peek()? != &Token::Semicolon {
+                return Ok(());
+            }
+            // Repeated and trailing semicolons are allowed
+            while self.peek()? == &Token::Semicolon {
+                self.next()?;
+            }
+            if matches!(self.peek()?, Token::Dot | Token::Close) {
+                return Ok(());
+            }
+        }
+    }
+
+    // [ predicate objects ], after the [.
+    fn blank_list(&mut self, emit: &mut Emit) -> Result<Term, Box<dyn Error>> {
+        self.blanks += 1;
+        // Not a valid label, so not one from the file
+        let blank = Term::Blank(format!("#{}", self.blanks));
+        if self.peek()? != &Token::Close {
+            self.predicate_objects(&blank, emit)?;
+        }
+        self.expect(Token::Close)?;
+        Ok(blank)
+    }
+
+    fn term(&mut self, t: Token) -> Result<Term, Box<dyn Error>> {
+        let xsd = |t: &str| format!("{}{}", XSD, t);
+        match t {
+            Token::Iri(iri) => Ok(Term::Iri(self.resolve(iri))),
+            Token::PName(p, local) => match self.prefixes.get(&p) {
+                Some(ns) => Ok(Term::Iri(format!("{}{}", ns, local))),
+                None => Err(self.bad(&format!("no prefix {:?}", p))),
+            },
+            Token::Blank(b) => Ok(Term::Blank(b)),
+            Token::Number(n, t) => Ok(Term::Literal {
+                value: n,
+                datatype: xsd(t),
+                lang: None,
+            }),
+            Token::Bool(b) => Ok(Term::Literal {
+                value: b.to_string(),
+                datatype: xsd("boolean"),
+                lang: None,
+            }),
+            Token::String(value) => match self.peek()?.clone() {
+                Token::LangTag(lang) => {
+                    self.next()?;
+                    Ok(Term::Literal {
+                        value,
+                        datatype: format!("{}langString", RDF),
+                        lang: Some(lang),
+                    })
+                }
+                Token::Datatype => {
+                    self.next()?;
+                    let next = self.next()?;
+                    match self.term(next)? {
+                        Term::Iri(datatype) => Ok(Term::Literal {
+                            value,
+                            datatype,
+                            lang: None,
+                        }),
+                        _ => Err(self.bad("a datatype must be an IRI")),
+                    }
+                }
+                _ => Ok(Term::Literal {
+                    value,
+                    datatype: xsd("string"),
+                    lang: None,
+                }),
+            },
+            Token::Paren => Err(self.bad("collections are not supported")),
+            t => Err(self.bad(&format!("unexpected {:?}", t))),
+        }
+    }
+
+    fn resolve(&self, iri: String) -> String {
+        let scheme = iri
+            .find(':')
+            .is_some_and(|i| !iri[..i].contains(['/', '?', '#']));
+        match scheme {
+            true => iri,
+            false => format!("{}{}", self.base, iri),
+        }
+    }
+
+    fn iri_ref(&mut self) -> Result<String, Box<dyn Error>> {
+        match self.next()? {
+            Token::Iri(iri) => Ok(self.resolve(iri)),
+            t => Err(self.bad(&format!("expected an IRI, not {:?}", t))),
+        }
+    }
+
+    fn expect(&mut self, want: Token) -> Result<(), Box<dyn Error>> {
+        match self.next()? {
+            t if t == want => Ok(()),
+            t => Err(self.bad(&format!("expected {:?}, not {:?}", want, t))),
+        }
+    }
+
+    fn peek(&mut self) -> Result<&Token, Box<dyn Error>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.token()?);
+        }
+        Ok(self.peeked.as_ref().unwrap())
+    }
+
+    fn next(&mut self) -> Result<Token, Box<dyn Error>> {
+        match self.peeked.take() {
+            Some(t) => Ok(t),
+            None => self.token(),
+        }
+    }
+
+    fn token(&mut self) -> Result<Token, Box<dyn Error>> {
+        loop {
+            match self.chars.peek()? {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next()?;
+                }
+                Some('#') => while !matches!(self.chars.next()?, None | Some('\n')) {},
+                _ => break,
+            }
+        }
+        let c = match self.chars.next()? {
+            None => return Ok(Token::Eof),
+            Some(c) => c,
+        };
+        let next = self.chars.peek()?;
+        let t = match c {
+            '.' if !next.is_some_and(|n| n.is_ascii_digit()) => Token::Dot,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '[' => Token::Open,
+            ']' => Token::Close,
+            '(' | ')' => Token::Paren,
+            '^' if next == Some('^') => {
+                self.chars.next()?;
+                Token::Datatype
+            }
+            '<' => {
+                let mut iri = String::new();
+                loop {
+                    match self.chars.next()? {
+                        Some('>') => break,
+                        Some('\\') => iri.push(self.escape()?),
+                        Some(c) if c != '\n' => iri.push(c),
+                        _ => return Err(self.bad("unterminated IRI")),
+                    }
+                }
+                Token::Iri(iri)
+            }
+            '"' | '\'' => Token::String(self.string(c)?),
+            '@' => {
+                let word = self.word(|c| c.is_ascii_alphanumeric() || c == '-')?;
+                match word.as_str() {
+                    "prefix" => Token::Prefix(true),
+                    "base" => Token::Base(true),
+                    _ => Token::LangTag(word),
+                }
+            }
+            '_' if next == Some(':') => {
+                self.chars.next()?;
+                Token::Blank(self.word(name_char)?)
+            }
+            c if c.is_ascii_digit() || c == '+' || c == '-' || c == '.' => {
+                let rest = self.word(|c| c.is_ascii_digit() || "+-.eE".contains(c))?;
+                let n = format!("{}{}", c, rest);
+                let t = match (n.contains(['e', 'E']), n.contains('.')) {
+                    (true, _) => "double",
+                    (false, true) => "decimal",
+                    (false, false) => "integer",
+                };
+                Token::Number(n, t)
+            }
+            c if name_char(c) || c == ':' => {
+                let word = format!("{}{}", c, self.word(|c| name_char(c) || c == ':')?);
+                match word.split_once(':') {
+                    Some((p, local)) => Token::PName(p.to_string(), local.to_string()),
+                    None => match word.to_ascii_uppercase().as_str() {
+                        "PREFIX" => Token::Prefix(false),
+                        "BASE" => Token::Base(false),
+                        _ if word == "a" => Token::A,
+                        _ if word == "true" => Token::Bool(true),
+                        _ if word == "false" => Token::Bool(false),
+                        _ => return Err(self.bad(&format!("unexpected {:?}", word))),
+                    },
+                }
+            }
+            c => return Err(self.bad(&format!("unexpected {:?}", c))),
+        };
+        Ok(t)
+    }
+
+    // Characters while ok.  A dot is only taken if more follow, as one at the
+    // end ends the statement.
+    fn word(&mut self, ok: impl Fn(char) -> bool) -> Result<String, Box<dyn Error>> {
+        let mut word = String::new();
+        loop {
+            match self.chars.peek()? {
+                Some('.') if ok('.') => match self.chars.peek_at(1)? {
+                    Some(c) if ok(c) && c != '.' => {}
+                    _ => return Ok(word),
+                },
+                Some('\\') => {
+                    self.chars.next()?;
+                    if let Some(c) = self.chars.next()? {
+                        word.push(c);
+                    }
+                    continue;
+                }
+                Some(c) if ok(c) => {}
+                _ => return Ok(word),
+            }
+            word.extend(self.chars.next()?);
+        }
+    }
+
+    // A string after its opening quote, which may be tripled.
+    fn string(&mut self, quote: char) -> Result<String, Box<dyn Error>> {
+        let long = self.chars.peek()? == Some(quote) && self.chars.peek_at(1)? == Some(quote);
+        if long {
+            self.chars.next()?;
+            self.chars.next()?;
+        } else if self.chars.peek()? == Some(quote) {
+            self.chars.next()?;
+            return Ok(String::new());
+        }
+        let mut s = String::new();
+        loop {
+            // Checked before it's read, so the error has the string's line
+            if !long && self.chars.peek()? == Some('\n') {
+                return Err(self.bad("unterminated string"));
+            }
+            match self.chars.next()? {
+                None => return Err(self.bad("unterminated string")),
+                Some('\\') => s.push(self.escape()?),
+                Some(c) if c == quote => {
+                    if !long {
+                        return Ok(s);
+                    }
+                    if self.chars.peek()? == Some(quote) && self.chars.peek_at(1)? == Some(quote) {
+                        // The closing quotes are the last three of a run
+                        if self.chars.peek_at(2)? != Some(quote) {
+                            self.chars.next()?;
+                            self.chars.next()?;
+                            return Ok(s);
+                        }
+                    }
+                    s.push(c);
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    // The character after a backslash.
+    fn escape(&mut self) -> Result<char, Box<dyn Error>> {
+        let c = match self.chars.next()? {
+            Some('t') => '\t',
+            Some('b') => '\u{8}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('f') => '\u{c}',
+            Some(c @ ('u' | 'U')) => {
+                let n = if c == 'u' { 4 } else { 8 };
+                let mut hex = String::new();
+                for _ in 0..n {
+                    hex.extend(self.chars.next()?);
+                }
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.bad(&format!("bad escape \\{}{}", c, hex)))?
+            }
+            Some(c) => c,
+            None => return Err(self.bad("unterminated escape")),
+        };
+        Ok(c)
+    }
+}
+
+// Characters of prefixed and blank node names, leaving out the escapes.
+fn name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '%' || !c.is_ascii()
+}
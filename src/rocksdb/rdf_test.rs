@@ -0,0 +1,373 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufReader, Read};
+use std::rc::Rc;
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::export::Counts;
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::rdf::{self, RdfFormat, Term, Triple, Vocabulary, RDF, XSD};
+use crate::rocksdb::store::{Batch, MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::value::Value;
+use crate::rocksdb::All;
+
+// api -> db, with attributes on both and on the edge.
+fn build(db: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut ids = vec![];
+    for (name, type_name) in [("api", "service"), ("db \"main\"", "data store")] {
+        let mut n = Node {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        };
+        Node::operations(db).put(&mut n)?;
+        ids.push(n.id);
+    }
+    let mut e = Edge {
+        name: "depends-on".into(),
+        type_name: "depends-on".into(),
+        head: ids[0],
+        tail: ids[1],
+        ..Default::default()
+    };
+    Edge::operations(db).put(&mut e)?;
+    ids.push(e.id);
+    for (parent_id, name, v) in [
+        (ids[0], "port", Value::Int(8080)),
+        (ids[0], "public", Value::Bool(true)),
+        (
+            ids[1],
+            "created",
+            Value::Timestamp(1_700_000_000_000_000_001),
+        ),
+        (ids[1], "load", Value::Float(0.25)),
+        (ids[2], "weight", Value::Float(0.5)),
+    ] {
+        Attribute::operations(db).put(&mut Attribute::typed(parent_id, name, &v))?;
+    }
+    Ok(())
+}
+
+fn export(db: &dyn Store, format: RdfFormat) -> Result<String, Box<dyn Error>> {
+    let mut out = vec![];
+    rdf::export_rdf(db, &Vocabulary::default(), format, &mut out)?;
+    Ok(String::from_utf8(out)?)
+}
+
+fn import(
+    db: Rc<dyn Store>,
+    vocabulary: &Vocabulary,
+    text: &str,
+) -> Result<Counts, Box<dyn Error>> {
+    rdf::import_rdf(db, vocabulary, &mut text.as_bytes(), rdf::IMPORT_BATCH)
+}
+
+fn value(db: &dyn Store, parent_id: u64, name: &str) -> Option<Value> {
+    Attribute::operations(db)
+        .get(Attribute::id_from(AttrKey {
+            parent_id,
+            name: name.into(),
+        }))
+        .unwrap()
+        .and_then(|a| a.value())
+}
+
+fn node(db: &dyn Store, id: u64) -> (String, String) {
+    let n = Node::operations(db)
+        .get(Node::id_from(id))
+        .unwrap()
+        .unwrap();
+    (n.name, n.type_name)
+}
+
+#[test]
+fn test_round_trip() -> Result<(), Box<dyn Error>> {
+    let from = MemStore::new(&All);
+    build(&from)?;
+    let turtle = export(&from, RdfFormat::Turtle)?;
+    assert!(turtle.contains(
+        "\nnode:1\n    a type:service ;\n    rdfs:label \"api\" ;\n    attr:port \"8080\"^^xsd:integer ;\n"
+    ), "{}", turtle);
+    assert!(
+        turtle.contains("<http://example.org/type/data%20store> ;"),
+        "{}",
+        turtle
+    );
+    let ntriples = export(&from, RdfFormat::NTriples)?;
+    // The edge's attributes have no subject to go on
+    assert_eq!(9, ntriples.lines().count());
+    assert!(ntriples.contains(
+        "<http://example.org/node/2> <http://www.w3.org/2000/01/rdf-schema#label> \"db \\\"main\\\"\" .\n"
+    ), "{}", ntriples);
+
+    for text in [&turtle, &ntriples] {
+        let to = Rc::new(MemStore::new(&All));
+        let counts = import(to.clone(), &Vocabulary::default(), text)?;
+        let to = to.as_ref();
+        assert_eq!((2, 1, 4), (counts.nodes, counts.edges, counts.attributes));
+        assert_eq!(("db \"main\"".into(), "data store".into()), node(to, 2));
+        assert_eq!(
+            Some(Value::Timestamp(1_700_000_000_000_000_001)),
+            value(to, 2, "created")
+        );
+        assert_eq!(Some(Value::Bool(true)), value(to, 1, "public"));
+        let edge = Edge::operations(to).get(Edge::id_from(3))?.unwrap();
+        assert_eq!(
+            ("depends-on", 1, 2),
+            (edge.name.as_str(), edge.head, edge.tail)
+        );
+        // Same ids here, as each node is first seen in id order
+        assert_eq!(turtle, export(to, RdfFormat::Turtle)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_turtle() -> Result<(), Box<dyn Error>> {
+    let text = r#"# People
+@prefix foaf: <http://xmlns.com/foaf/0.1/> .
+PREFIX ex: <http://ex.org/>
+@base <http://ex.org/people/> .
+
+<alice> a foaf:Person ;
+    foaf:name "Alice"@en, 'Al' ;
+    foaf:age 42 ; ex:height 1.7 ; ex:mass 6.1e1 ; ex:active true ;
+    ex:bio """Likes "quotes"
+and lines""" ;
+    foaf:knows <bob>, _:c, [ foaf:name "Dan" ] ;
+    ex:tag\-line "xé" ;
+    .
+_:c foaf:knows <http://ex.org/people/alice>.
+"#;
+    let mut triples = vec![];
+    rdf::read_triples(&mut text.as_bytes(), &mut |t| {
+        triples.push(t);
+        Ok(())
+    })?;
+    assert_eq!(14, triples.len());
+    let alice = Term::Iri("http://ex.org/people/alice".into());
+    assert_eq!(
+        Triple {
+            subject: alice.clone(),
+            predicate: format!("{}type", RDF),
+            object: Term::Iri("http://xmlns.com/foaf/0.1/Person".into()),
+        },
+        triples[0]
+    );
+    let literal = |value: &str, datatype: &str| Term::Literal {
+        value: value.into(),
+        datatype: format!("{}{}", XSD, datatype),
+        lang: None,
+    };
+    let objects: Vec<&Term> = triples.iter().map(|t| &t.object).collect();
+    assert_eq!(
+        Some("en"),
+        match objects[1] {
+            Term::Literal { lang, .. } => lang.as_deref(),
+            _ => None,
+        }
+    );
+    assert_eq!(&literal("Al", "string"), objects[2]);
+    assert_eq!(&literal("42", "integer"), objects[3]);
+    assert_eq!(&literal("1.7", "decimal"), objects[4]);
+    assert_eq!(&literal("6.1e1", "double"), objects[5]);
+    assert_eq!(&literal("true", "boolean"), objects[6]);
+    assert_eq!(
+        &literal("Likes \"quotes\"\nand lines", "string"),
+        objects[7]
+    );
+    assert_eq!(&Term::Blank("c".into()), objects[9]);
+    // The [ ] is emitted before the triple it's the object of
+    assert!(
+        matches!(&triples[10].subject, Term::Blank(b) if objects[11] == &Term::Blank(b.clone()))
+    );
+    assert_eq!("http://ex.org/tag-line", triples[12].predicate);
+    assert_eq!(&literal("x\u{e9}", "string"), objects[12]);
+
+    // Named by the prefixes, including the ones given
+    let db = Rc::new(MemStore::new(&All));
+    let mut prefixes = BTreeMap::new();
+    prefixes.insert("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string());
+    prefixes.insert("people".to_string(), "http://ex.org/people/".to_string());
+    let vocabulary = Vocabulary::new("http://ex.org/people/", prefixes);
+    let counts = import(db.clone(), &vocabulary, text)?;
+    let db = db.as_ref();
+    assert_eq!((4, 4), (counts.nodes, counts.edges));
+    assert_eq!(("people:alice".into(), "foaf:Person".into()), node(db, 1));
+    assert_eq!(Some(Value::Int(42)), value(db, 1, "foaf:age"));
+    assert_eq!(Some(Value::Float(61.0)), value(db, 1, "http://ex.org/mass"));
+    let edge = Edge::operations(db).get(Edge::id_from(3))?.unwrap();
+    assert_eq!("foaf:knows", edge.name);
+    assert_eq!(("_:c".into(), "entity".into()), node(db, 4));
+
+    // And written back with the same vocabulary
+    let mut out = vec![];
+    rdf::export_rdf(db, &vocabulary, RdfFormat::Turtle, &mut out)?;
+    let out = String::from_utf8(out)?;
+    assert!(out.contains("\n    a foaf:Person ;\n"), "{}", out);
+    assert!(
+        out.contains("\n    foaf:knows node:2, node:4, node:6 .\n"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("\n    <http://ex.org/mass> \"61.0\"^^xsd:double ;\n"),
+        "{}",
+        out
+    );
+    Ok(())
+}
+
+#[test]
+fn test_errors() -> Result<(), Box<dyn Error>> {
+    let read = |text: &str| {
+        rdf::read_triples(&mut text.as_bytes(), &mut |_| Ok(()))
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        "Bad import at line 2: no prefix \"ex\"",
+        read("<a> <b> <c> .\n<a> ex:b <c> .")
+    );
+    assert_eq!(
+        "Bad import at line 1: unterminated string",
+        read("<a> <b> \"c\n\" .")
+    );
+    assert_eq!(
+        "Bad import at line 1: collections are not supported",
+        read("<a> <b> (<c>) .")
+    );
+    assert_eq!(
+        "Bad import at line 1: expected Dot, not Eof",
+        read("<a> <b> <c>")
+    );
+    assert!(read("\"a\" <b> <c> .").contains("a literal is not a subject"));
+
+    // Parsed a statement at a time, so a bad triple stops an endless input
+    struct Endless;
+    impl Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let line = b"<a> <b> \"c\" .\n";
+            let n = buf.len().min(line.len());
+            buf[..n].copy_from_slice(&line[..n]);
+            Ok(n)
+        }
+    }
+    let mut n = 0;
+    let err = rdf::read_triples(&mut BufReader::new(Endless), &mut |_| {
+        n += 1;
+        match n {
+            1000 => Err("enough".into()),
+            _ => Ok(()),
+        }
+    })
+    .unwrap_err();
+    assert_eq!("Bad import at line 1000: enough", err.to_string());
+    Ok(())
+}
+
+// A MemStore that keeps the size of each batch committed to it.
+struct Batches {
+    store: MemStore,
+    sizes: RefCell<Vec<usize>>,
+}
+
+impl Store for Batches {
+    fn has_cf(&self, cf_name: &str) -> bool {
+        self.store.has_cf(cf_name)
+    }
+
+    fn get_value(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        self.store.get_value(cf_name, key)
+    }
+
+    fn put_value(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.store.put_value(cf_name, key, value)
+    }
+
+    fn scan_from(
+        &self,
+        cf_name: &str,
+        from: &[u8],
+        f: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.store.scan_from(cf_name, from, f)
+    }
+
+    fn commit(&self, batch: Batch) -> Result<(), Box<dyn Error>> {
+        self.sizes.borrow_mut().push(batch.len());
+        self.store.commit(batch)
+    }
+
+    fn estimate_keys(&self, cf_name: &str) -> Result<u64, Box<dyn Error>> {
+        self.store.estimate_keys(cf_name)
+    }
+}
+
+#[test]
+fn test_import_in_batches() -> Result<(), Box<dyn Error>> {
+    // A chain of 101 nodes, each labelled after it's first seen as an object
+    let mut text = String::new();
+    for i in 0..100 {
+        text.push_str(&format!(
+            "<http://example.org/node/{i}> <http://www.w3.org/2000/01/rdf-schema#label> \"n{i}\" .\n\
+             <http://example.org/node/{i}> <http://example.org/edge/next> <http://example.org/node/{}> .\n",
+            i + 1
+        ));
+    }
+    let db = Rc::new(Batches {
+        store: MemStore::new(&All),
+        sizes: RefCell::new(vec![]),
+    });
+    let counts = rdf::import_rdf(db.clone(), &Vocabulary::default(), &mut text.as_bytes(), 10)?;
+    assert_eq!((101, 100), (counts.nodes, counts.edges));
+
+    // Committed 10 triples at a time, not in one batch
+    let sizes = db.sizes.borrow().clone();
+    let total: usize = sizes.iter().sum();
+    assert!(sizes.len() >= 20, "{:?}", sizes);
+    assert!(sizes.iter().all(|n| *n < total / 10), "{:?}", sizes);
+
+    // Nodes seen in an earlier batch are found in the db
+    let db = db.as_ref();
+    let mut edges = vec![];
+    Edge::operations(db).visit(Edge::id_from(0), Box::new(db::Collect(&mut edges)))?;
+    assert_eq!(100, edges.len());
+    for edge in edges {
+        let (head, _) = node(db, edge.head);
+        let (tail, _) = node(db, edge.tail);
+        let i: usize = head.trim_start_matches('n').parse()?;
+        match i {
+            99 => assert_eq!("100", tail),
+            _ => assert_eq!(format!("n{}", i + 1), tail),
+        }
+    }
+
+    // and the map of IRIs to ids is gone
+    let mut left = 0;
+    db.scan_from(db::CF_SYSTEM, b"rdf.import.", &mut |k, _| {
+        left += k.starts_with(b"rdf.import.") as usize;
+        false
+    })?;
+    assert_eq!(0, left);
+    Ok(())
+}
+
+#[test]
+fn test_round_trip_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let from_info = TestDbInfo::new();
+    let from = db::init(&from_info, &All)?;
+    build(&from)?;
+    let text = export(&from, RdfFormat::Turtle)?;
+
+    let to_info = TestDbInfo::new();
+    let to = Rc::new(db::init(&to_info, &All)?);
+    import(to.clone(), &Vocabulary::default(), &text)?;
+    assert!(fsck::fsck(to.as_ref())?.is_clean());
+    assert_eq!(text, export(to.as_ref(), RdfFormat::Turtle)?);
+    Ok(())
+}
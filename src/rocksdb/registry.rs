@@ -0,0 +1,167 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::attribute::AttrKey;
+use crate::rocksdb::backfill::{self, Progress};
+use crate::rocksdb::db::{Entity, HasKey, IndexBuilder, KeyCodec, OperationsBuilder};
+use crate::rocksdb::document::Document;
+use crate::rocksdb::fsck::{self, Report};
+use crate::rocksdb::graph::{Attribute, Edge, Node};
+use crate::rocksdb::index::Indexes;
+use crate::rocksdb::store::Store;
+
+use std::error::Error;
+use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock};
+
+// An entity type known to the db, with the generic index maintenance bound
+// to it.
+pub trait EntityType: Send + Sync {
+    // Entity::TYPE
+    fn name(&self) -> &'static str;
+
+    // Column families of all the indexes, value index first.
+    fn cf_names(&self) -> Vec<String>;
+
+    fn value_cf(&self) -> &'static str;
+
+    fn check(&self, db: &dyn Store, report: &mut Report) -> Result<(), Box<dyn Error>>;
+
+    fn reindex(&self, db: &dyn Store, name: Option<&str>) -> Result<u64, Box<dyn Error>>;
+
+    fn backfill_step(
+        &self,
+        db: &dyn Store,
+        progress: &mut Progress,
+        n: usize,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+struct Registered<K, E>(PhantomData<fn() -> (K, E)>);
+
+impl<K, E> EntityType for Registered<K, E>
+where
+    K: KeyCodec + 'static,
+    E: Entity + HasKey<K> + Indexes<E> + OperationsBuilder<E> + 'static,
+{
+    fn name(&self) -> &'static str {
+        E::TYPE
+    }
+
+    fn cf_names(&self) -> Vec<String> {
+        let value_cf = self.value_cf();
+        let mut cfs = vec![value_cf.to_string()];
+        for i in E::indexes().iter() {
+            if i.cf_name() != value_cf {
+                cfs.push(i.cf_name().to_string());
+            }
+        }
+        cfs
+    }
+
+    fn value_cf(&self) -> &'static str {
+        E::value_index().cf_name()
+    }
+
+    fn check(&self, db: &dyn Store, report: &mut Report) -> Result<(), Box<dyn Error>> {
+        fsck::check::<E>(db, report)
+    }
+
+    fn reindex(&self, db: &dyn Store, name: Option<&str>) -> Result<u64, Box<dyn Error>> {
+        fsck::reindex::<E>(db, name)
+    }
+
+    fn backfill_step(
+        &self,
+        db: &dyn Store,
+        progress: &mut Progress,
+        n: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        match E::indexes()
+            .iter()
+            .find(|i| i.cf_name() == progress.cf_name)
+        {
+            Some(index) => backfill::step::<E>(db, index.as_ref(), progress, n),
+            None => Err(format!("{:?} is not an index of {}", progress.cf_name, E::TYPE).into()),
+        }
+    }
+}
+
+// The entity types stored in a db.  Its column families are the indexes of
+// every registered type.
+#[derive(Clone, Default)]
+pub struct Registry {
+    types: Vec<Arc<dyn EntityType>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    // Adds the type, replacing one registered under the same name.
+    pub fn register<K, E>(&mut self) -> &mut Registry
+    where
+        K: KeyCodec + 'static,
+        E: Entity + HasKey<K> + Indexes<E> + OperationsBuilder<E> + 'static,
+    {
+        if self.find(E::TYPE).is_some() {
+            warn!("Replacing registered type {:?}", E::TYPE);
+            self.types.retain(|t| t.name() != E::TYPE);
+        }
+        self.types.push(Arc::new(Registered::<K, E>(PhantomData)));
+        self
+    }
+
+    pub fn types(&self) -> &[Arc<dyn EntityType>] {
+        &self.types
+    }
+
+    pub fn find(&self, name: &str) -> Option<&dyn EntityType> {
+        self.types
+            .iter()
+            .find(|t| t.name() == name)
+            .map(|t| t.as_ref())
+    }
+
+    // The type that declares the index.
+    pub fn find_index(&self, cf_name: &str) -> Option<&dyn EntityType> {
+        self.types
+            .iter()
+            .find(|t| t.cf_names().iter().any(|c| c == cf_name))
+            .map(|t| t.as_ref())
+    }
+}
+
+impl IndexBuilder for Registry {
+    fn cf_names(&self) -> Vec<String> {
+        self.types.iter().flat_map(|t| t.cf_names()).collect()
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.types.iter().map(|t| t.name()))
+            .finish()
+    }
+}
+
+static GLOBAL: OnceLock<Registry> = OnceLock::new();
+
+// The entity types of the graph: nodes, edges, documents and attributes.
+// Code that stores types of its own builds a Registry with them and passes
+// it to db::init, fsck_with, reindex_with and backfill_with.
+pub fn global() -> Registry {
+    GLOBAL
+        .get_or_init(|| {
+            let mut registry = Registry::new();
+            registry
+                .register::<u64, Node>()
+                .register::<u64, Edge>()
+                .register::<u64, Document>()
+                .register::<AttrKey, Attribute>();
+            registry
+        })
+        .clone()
+}
@@ -0,0 +1,214 @@
+use std::error::Error;
+
+use crate::rocksdb::backfill;
+use crate::rocksdb::db::{self, Entity, HasKey, IndexBuilder, OperationsBuilder};
+use crate::rocksdb::fsck::{self, Problem};
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::{Index, Indexes};
+use crate::rocksdb::registry::{self, Registry};
+use crate::rocksdb::store::{Batch, Store};
+use crate::rocksdb::testing::TestDbInfo;
+
+// An entity type from outside the graph module.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Tag {
+    id: u64,
+    label: String,
+}
+
+impl HasKey<u64> for Tag {
+    fn key(&self) -> Option<u64> {
+        if self.id > 0 {
+            Some(self.id)
+        } else {
+            None
+        }
+    }
+}
+
+impl Entity for Tag {
+    const TYPE: &'static str = "Tag";
+    fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(self.label.as_bytes());
+        bytes
+    }
+    fn from_bytes(_key: &[u8], bytes: &[u8]) -> Result<Tag, Box<dyn Error>> {
+        Ok(Tag {
+            id: u64::from_le_bytes(bytes[0..8].try_into()?),
+            label: String::from_utf8(bytes[8..].to_vec())?,
+        })
+    }
+}
+
+struct TagById;
+struct TagByLabel;
+
+impl Index<Tag> for TagById {
+    fn cf_name(&self) -> &'static str {
+        "index.tag.id"
+    }
+    fn key_value(&self, t: &Tag) -> (Vec<u8>, Vec<u8>) {
+        (t.id().as_bytes(), t.as_bytes())
+    }
+}
+
+impl Index<Tag> for TagByLabel {
+    fn cf_name(&self) -> &'static str {
+        "index.tag.label"
+    }
+    fn key_value(&self, t: &Tag) -> (Vec<u8>, Vec<u8>) {
+        (t.label.as_bytes().to_vec(), t.id.to_le_bytes().to_vec())
+    }
+}
+
+impl Indexes<Tag> for Tag {
+    fn indexes() -> Vec<Box<dyn Index<Tag>>> {
+        vec![Box::new(TagById), Box::new(TagByLabel)]
+    }
+    fn value_index() -> Box<dyn Index<Tag>> {
+        Box::new(TagById)
+    }
+}
+
+struct TagHelper;
+
+impl db::IndexHelper<u64, Tag> for TagHelper {
+    fn value_index(&self) -> &dyn Index<Tag> {
+        &TagById
+    }
+    fn indexes(&self) -> Vec<Box<dyn Index<Tag>>> {
+        Tag::indexes()
+    }
+    fn before_put(
+        &self,
+        db: &dyn Store,
+        txn: &mut Batch,
+        t: &mut Tag,
+    ) -> Result<(), Box<dyn Error>> {
+        if t.id == 0 {
+            t.id = db::next_id(db, txn)?;
+        }
+        Ok(())
+    }
+    fn from_bytes(&self, buff: &[u8]) -> Result<Tag, Box<dyn Error>> {
+        Tag::from_bytes(&[], buff)
+    }
+}
+
+impl OperationsBuilder<Tag> for Tag {
+    fn operations(db: &dyn Store) -> Box<dyn db::Operations<Tag> + '_> {
+        db::entity_operations::<u64, Tag>(db, Box::new(TagHelper))
+    }
+}
+
+fn graph_and_tags() -> Registry {
+    let mut registry = Registry::new();
+    registry
+        .register::<u64, Node>()
+        .register::<u64, Edge>()
+        .register::<u64, Tag>();
+    registry
+}
+
+#[test]
+fn test_registry() {
+    let registry = graph_and_tags();
+    assert_eq!(
+        vec!["Node", "Edge", "Tag"],
+        registry
+            .types()
+            .iter()
+            .map(|t| t.name())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["index.tag.id", "index.tag.label"],
+        registry.find("Tag").unwrap().cf_names()
+    );
+    assert_eq!(
+        "Edge",
+        registry.find_index("index.edge.head-tail").unwrap().name()
+    );
+    assert!(registry.find_index("index.nope").is_none());
+
+    // Registering again replaces
+    let mut again = registry.clone();
+    again.register::<u64, Tag>();
+    assert_eq!(3, again.types().len());
+
+    // Node and Edge are always there
+    let global = registry::global();
+    assert!(global.find("Node").is_some());
+    assert!(global.find("Edge").is_some());
+    assert!(registry
+        .cf_names()
+        .iter()
+        .all(|c| global.cf_names().contains(c) || c.starts_with("index.tag.")));
+}
+
+#[test]
+fn test_registered_type() -> Result<(), Box<dyn Error>> {
+    let registry = graph_and_tags();
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &registry)?;
+
+    let mut tag_ops = Tag::operations(&db);
+    for label in ["red", "green"] {
+        tag_ops.put(&mut Tag {
+            label: label.into(),
+            ..Default::default()
+        })?;
+    }
+    let found = tag_ops.first("index.tag.label", b"green")?;
+    assert_eq!(Some(2), found.map(|t| t.id));
+
+    let report = fsck::fsck_with(&db, &registry)?;
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(2, report.entities);
+
+    // Lose an entry, then rebuild it
+    let mut txn = Batch::default();
+    txn.delete("index.tag.label", b"red");
+    db.commit(txn)?;
+    let report = fsck::fsck_with(&db, &registry)?;
+    assert_eq!(
+        vec![Problem::Missing {
+            index: "index.tag.label".to_string(),
+            id: 1u64.to_le_bytes().to_vec(),
+        }],
+        report.problems
+    );
+    fsck::reindex_with(&db, &registry, Some("index.tag.label"))?;
+    assert!(fsck::fsck_with(&db, &registry)?.is_clean());
+
+    assert!(fsck::reindex_with(&db, &registry, Some("index.tag.id")).is_err());
+    assert!(fsck::reindex_with(&db, &registry, Some("index.nope")).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_register_on_existing_db() -> Result<(), Box<dyn Error>> {
+    let mut graph = Registry::new();
+    graph.register::<u64, Node>().register::<u64, Edge>();
+    let db_info = TestDbInfo::new();
+    drop(db::init(&db_info, &graph)?);
+
+    // The new type's indexes are created and queued for backfill
+    let registry = graph_and_tags();
+    let db = db::open_db(&db_info, &registry)?;
+    let pending: Vec<String> = backfill::pending(&db)?
+        .into_iter()
+        .map(|p| p.cf_name)
+        .collect();
+    assert_eq!(vec!["index.tag.id", "index.tag.label"], pending);
+
+    backfill::backfill_with(&db, &registry, None)?;
+    assert!(backfill::pending(&db)?.is_empty());
+    Tag::operations(&db).put(&mut Tag {
+        label: "blue".into(),
+        ..Default::default()
+    })?;
+    assert!(fsck::fsck_with(&db, &registry)?.is_clean());
+    Ok(())
+}
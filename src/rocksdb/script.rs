@@ -0,0 +1,222 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{Collect, HasKey, OperationsBuilder};
+use crate::rocksdb::edge;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::index::Index;
+use crate::rocksdb::node;
+use crate::rocksdb::query::{self, Cell};
+use crate::rocksdb::store::{Batch, Overlay, Store};
+use crate::rocksdb::value::Value;
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map};
+
+use std::error::Error;
+use std::rc::Rc;
+
+// Operations a script may run before it is stopped; see
+// Engine::set_max_operations.
+pub static DEFAULT_MAX_STEPS: u64 = 1_000_000;
+
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+// Runs the script against an overlay of the db and returns all its writes as
+// one batch, for the caller to commit or show.  Nothing is written to db.
+//
+// Nodes and edges are maps with the keys id, name and type, and head and tail
+// for edges.  The bindings:
+//
+//   get_node(id), find_node(name), put_node(map), delete_node(id), nodes()
+//   get_edge(id), put_edge(map), delete_edge(id), edges()
+//   out_edges(id), in_edges(id), query(text)
+//
+// get and find return () if not found; put returns the id; query returns a
+// map of column to value per row.
+pub fn run(db: Rc<dyn Store>, script: &str, max_steps: u64) -> Result<Batch, Box<dyn Error>> {
+    let overlay = Rc::new(Overlay::new(db));
+    engine(overlay.clone(), max_steps).run(script)?;
+    Ok(overlay.batch())
+}
+
+fn engine(db: Rc<Overlay>, max_steps: u64) -> Engine {
+    let mut engine = Engine::new();
+    // No imports, no eval and no runaway loops
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(max_steps);
+
+    let d = db.clone();
+    engine.register_fn("get_node", move |id: i64| -> Fallible<Dynamic> {
+        let found = Node::operations(&*d)
+            .get(Node::id_from(id as u64))
+            .map_err(fail)?;
+        Ok(found.as_ref().map_or(Dynamic::UNIT, node_map))
+    });
+    let d = db.clone();
+    engine.register_fn("find_node", move |name: &str| -> Fallible<Dynamic> {
+        let found = Node::operations(&*d)
+            .first(node::ByName.cf_name(), name.as_bytes())
+            .map_err(fail)?;
+        Ok(found.as_ref().map_or(Dynamic::UNIT, node_map))
+    });
+    let d = db.clone();
+    engine.register_fn("put_node", move |m: Map| -> Fallible<i64> {
+        let mut node = Node {
+            id: int(&m, "id")?.unwrap_or(0) as u64,
+            type_name: string(&m, "type")?.unwrap_or_else(|| "entity".to_string()),
+            name: string(&m, "name")?.ok_or("put_node: name is required")?,
+            ..Default::default()
+        };
+        Node::operations(&*d).put(&mut node).map_err(fail)?;
+        Ok(node.id as i64)
+    });
+    let d = db.clone();
+    engine.register_fn("delete_node", move |id: i64| -> Fallible<bool> {
+        let mut ops = Node::operations(&*d);
+        match ops.get(Node::id_from(id as u64)).map_err(fail)? {
+            Some(n) => ops.delete(&n).map_err(fail),
+            None => Ok(false),
+        }
+    });
+    let d = db.clone();
+    engine.register_fn("nodes", move || -> Fallible<Array> {
+        let mut nodes = vec![];
+        Node::operations(&*d)
+            .visit(Node::id_from(0), Box::new(Collect(&mut nodes)))
+            .map_err(fail)?;
+        Ok(nodes.iter().map(node_map).collect())
+    });
+
+    let d = db.clone();
+    engine.register_fn("get_edge", move |id: i64| -> Fallible<Dynamic> {
+        let found = Edge::operations(&*d)
+            .get(Edge::id_from(id as u64))
+            .map_err(fail)?;
+        Ok(found.as_ref().map_or(Dynamic::UNIT, edge_map))
+    });
+    let d = db.clone();
+    engine.register_fn("put_edge", move |m: Map| -> Fallible<i64> {
+        let name = string(&m, "name")?.ok_or("put_edge: name is required")?;
+        let mut edge = Edge {
+            id: int(&m, "id")?.unwrap_or(0) as u64,
+            type_name: string(&m, "type")?.unwrap_or_else(|| name.clone()),
+            name,
+            head: int(&m, "head")?.ok_or("put_edge: head is required")? as u64,
+            tail: int(&m, "tail")?.ok_or("put_edge: tail is required")? as u64,
+            ..Default::default()
+        };
+        Edge::operations(&*d).put(&mut edge).map_err(fail)?;
+        Ok(edge.id as i64)
+    });
+    let d = db.clone();
+    engine.register_fn("delete_edge", move |id: i64| -> Fallible<bool> {
+        let mut ops = Edge::operations(&*d);
+        match ops.get(Edge::id_from(id as u64)).map_err(fail)? {
+            Some(e) => ops.delete(&e).map_err(fail),
+            None => Ok(false),
+        }
+    });
+    let d = db.clone();
+    engine.register_fn("edges", move || -> Fallible<Array> {
+        let mut edges = vec![];
+        Edge::operations(&*d)
+            .visit(Edge::id_from(0), Box::new(Collect(&mut edges)))
+            .map_err(fail)?;
+        Ok(edges.iter().map(edge_map).collect())
+    });
+
+    for (name, index) in [
+        ("out_edges", edge::ByHeadTail.cf_name()),
+        ("in_edges", edge::ByTailHead.cf_name()),
+    ] {
+        let d = db.clone();
+        engine.register_fn(name, move |id: i64| -> Fallible<Array> {
+            let mut edges = vec![];
+            Edge::operations(&*d)
+                .scan(
+                    index,
+                    (id as u64).to_le_bytes().to_vec(),
+                    Box::new(Collect(&mut edges)),
+                )
+                .map_err(fail)?;
+            Ok(edges.iter().map(edge_map).collect())
+        });
+    }
+
+    let d = db;
+    engine.register_fn("query", move |text: &str| -> Fallible<Array> {
+        let results = query::run(&*d, text).map_err(fail)?;
+        Ok(results
+            .rows
+            .iter()
+            .map(|row| {
+                let mut m = Map::new();
+                for (column, cell) in results.columns.iter().zip(row.iter()) {
+                    m.insert(column.into(), cell_value(cell));
+                }
+                Dynamic::from_map(m)
+            })
+            .collect())
+    });
+    engine
+}
+
+fn fail(e: Box<dyn Error>) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+fn int(m: &Map, key: &str) -> Fallible<Option<i64>> {
+    match m.get(key) {
+        None => Ok(None),
+        Some(v) => match v.as_int() {
+            Ok(v) => Ok(Some(v)),
+            Err(t) => Err(format!("{}: expected an int, got {}", key, t).into()),
+        },
+    }
+}
+
+fn string(m: &Map, key: &str) -> Fallible<Option<String>> {
+    match m.get(key) {
+        None => Ok(None),
+        Some(v) => match v.clone().into_string() {
+            Ok(v) => Ok(Some(v)),
+            Err(t) => Err(format!("{}: expected a string, got {}", key, t).into()),
+        },
+    }
+}
+
+fn node_map(n: &Node) -> Dynamic {
+    let mut m = Map::new();
+    m.insert("id".into(), (n.id as i64).into());
+    m.insert("name".into(), n.name.clone().into());
+    m.insert("type".into(), n.type_name.clone().into());
+    Dynamic::from_map(m)
+}
+
+fn edge_map(e: &Edge) -> Dynamic {
+    let mut m = Map::new();
+    m.insert("id".into(), (e.id as i64).into());
+    m.insert("name".into(), e.name.clone().into());
+    m.insert("type".into(), e.type_name.clone().into());
+    m.insert("head".into(), (e.head as i64).into());
+    m.insert("tail".into(), (e.tail as i64).into());
+    Dynamic::from_map(m)
+}
+
+// Timestamps are nanoseconds, like ts_nano.
+fn cell_value(c: &Cell) -> Dynamic {
+    match c {
+        Cell::Node(n) => node_map(n),
+        Cell::Edge(e) => edge_map(e),
+        Cell::Value(None) => Dynamic::UNIT,
+        Cell::Value(Some(v)) => match v {
+            Value::Int(v) => (*v).into(),
+            Value::Float(v) => (*v).into(),
+            Value::Bool(v) => (*v).into(),
+            Value::String(v) => v.clone().into(),
+            Value::Timestamp(v) => (*v as i64).into(),
+        },
+    }
+}
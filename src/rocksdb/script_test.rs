@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::rc::Rc;
+
+use crate::rocksdb::db::{self, Collect, HasKey, OperationsBuilder};
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::script;
+use crate::rocksdb::store::{MemStore, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+
+static BUILD: &str = r#"
+    let api = put_node(#{ name: "api", type: "service" });
+    let db = put_node(#{ name: "db", type: "database" });
+    let cache = put_node(#{ name: "cache" });
+    put_edge(#{ name: "depends-on", head: api, tail: db });
+    put_edge(#{ name: "depends-on", head: api, tail: cache });
+    put_edge(#{ name: "reads", head: cache, tail: db });
+
+    // Reads see the writes so far
+    if find_node("db").id != db { throw "find_node"; }
+    if get_node(cache).type != "entity" { throw "default type"; }
+    if out_edges(api).len() != 2 { throw "out_edges"; }
+    if in_edges(db).len() != 2 { throw "in_edges"; }
+    let rows = query(`MATCH (a {name: "api"})-->(b) RETURN b.name`);
+    let names = rows.map(|r| r["b.name"]);
+    names.sort();
+    if names != ["cache", "db"] { throw "query"; }
+
+    // Retire the cache
+    for e in in_edges(cache) + out_edges(cache) { delete_edge(e.id); }
+    delete_node(cache);
+    if get_node(cache) != () { throw "delete_node"; }
+    if nodes().len() != 2 || edges().len() != 1 { throw "scans"; }
+"#;
+
+fn exercise(db: Rc<dyn Store>) -> Result<(), Box<dyn Error>> {
+    // Nothing is written until the batch is committed
+    let batch = script::run(db.clone(), BUILD, script::DEFAULT_MAX_STEPS)?;
+    assert!(!batch.is_empty());
+    assert_eq!(None, Node::operations(&*db).get(Node::id_from(1))?);
+    db.commit(batch)?;
+
+    let mut nodes: Vec<Node> = vec![];
+    Node::operations(&*db).visit(Node::id_from(0), Box::new(Collect(&mut nodes)))?;
+    let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+    assert_eq!(vec!["api", "db"], names);
+    let mut edges: Vec<Edge> = vec![];
+    Edge::operations(&*db).visit(Edge::id_from(0), Box::new(Collect(&mut edges)))?;
+    assert_eq!(1, edges.len());
+    assert_eq!((nodes[0].id, nodes[1].id), (edges[0].head, edges[0].tail));
+
+    // A failed script leaves nothing to commit
+    let err = script::run(
+        db.clone(),
+        r#"put_node(#{ name: "x" }); put_edge(#{ name: "e", head: 1 });"#,
+        script::DEFAULT_MAX_STEPS,
+    );
+    assert!(err.unwrap_err().to_string().contains("tail is required"));
+    Ok(())
+}
+
+#[test]
+fn test_script_in_memory() -> Result<(), Box<dyn Error>> {
+    exercise(Rc::new(MemStore::new(&All)))
+}
+
+#[test]
+fn test_script_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    exercise(Rc::new(db::init(&db_info, &All)?))
+}
+
+#[test]
+fn test_sandbox() {
+    let db: Rc<dyn Store> = Rc::new(MemStore::new(&All));
+    let run = |text: &str| script::run(db.clone(), text, 1000);
+
+    assert!(run("let n = 0; loop { n += 1; }").is_err());
+    assert!(run(r#"eval("1 + 1")"#).is_err());
+    assert!(run(r#"import "other" as other;"#).is_err());
+    assert!(run("put_node(#{ type: 1 })").is_err());
+    assert!(run("let n = 0; while n < 10 { n += 1; }").is_ok());
+}
@@ -1,2 +1,56 @@
 #[allow(unused_imports)]
 use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Database};
+use crate::rocksdb::graphql::{self, Db, GraphSchema};
+
+use async_graphql::http::GraphiQLSource;
+use axum::extract::{Request, State};
+use axum::middleware::{self, Next};
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+
+use std::error::Error;
+use std::sync::Arc;
+
+// Path of the endpoint; a GET serves GraphiQL.
+pub static GRAPHQL_PATH: &str = "/graphql";
+
+async fn execute(
+    State(schema): State<GraphSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint(GRAPHQL_PATH).finish())
+}
+
+pub fn router(db: Db) -> Router {
+    Router::new()
+        .route(GRAPHQL_PATH, get(graphiql).post(execute))
+        .with_state(graphql::schema(db))
+}
+
+// Serves a secondary instance, catching it up with the primary before each
+// request so reads see the latest writes.
+#[tokio::main]
+pub async fn serve_graphql(addr: &str, database: Arc<Database>) -> Result<(), Box<dyn Error>> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(
+        "Serving GraphQL at http://{}{}",
+        listener.local_addr()?,
+        GRAPHQL_PATH
+    );
+    let secondary = database.clone();
+    let app = router(database).layer(middleware::from_fn(move |request: Request, next: Next| {
+        if let Err(e) = db::catch_up(&secondary) {
+            warn!("Catching up with the primary failed: {:?}", e);
+        }
+        next.run(request)
+    }));
+    axum::serve(listener, app).await?;
+    Ok(())
+}
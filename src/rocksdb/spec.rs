@@ -0,0 +1,227 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::error::ErrBadSpec;
+
+use rocksdb::{BlockBasedOptions, Cache, DBCompressionType, Options, SliceTransform};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// A db spec in YAML, e.g.
+//
+// path: graph.db
+// wal_ttl_seconds: 86400
+// defaults:
+//   compression: lz4
+//   block_cache_mb: 64
+// column_families:
+//   index.edge.head-tail:
+//     prefix_length: 8
+//     bloom_filter_bits: 10
+//
+// A relative path is relative to the spec file.  Column families not
+// listed get the defaults.  The block cache of the defaults is one cache
+// shared by all the column families that don't size their own.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DbSpec {
+    pub path: String,
+    // Keeps the WAL around this long for `rocksdb changes`; defaults to
+    // DEFAULT_WAL_TTL_SECONDS
+    pub wal_ttl_seconds: Option<u64>,
+    #[serde(default)]
+    pub defaults: CfSpec,
+    #[serde(default)]
+    pub column_families: BTreeMap<String, CfSpec>,
+    #[serde(skip)]
+    cache: SharedCache,
+}
+
+// Made the first time a column family asks for it.  Not part of the spec
+// as written, so specs compare the same whether or not it's made.
+#[derive(Default, Clone)]
+struct SharedCache(OnceLock<Cache>);
+
+impl fmt::Debug for SharedCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SharedCache({})", self.0.get().is_some())
+    }
+}
+
+impl PartialEq for SharedCache {
+    fn eq(&self, _: &SharedCache) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CfSpec {
+    pub block_cache_mb: Option<usize>,
+    pub bloom_filter_bits: Option<f64>,
+    pub compression: Option<Compression>,
+    pub write_buffer_mb: Option<usize>,
+    // Length of the fixed key prefix, e.g. 8 for the head id of the
+    // head-tail index.
+    pub prefix_length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Snappy,
+    Zlib,
+    Bz2,
+    Lz4,
+    Lz4hc,
+    Zstd,
+}
+
+impl From<Compression> for DBCompressionType {
+    fn from(c: Compression) -> DBCompressionType {
+        match c {
+            Compression::None => DBCompressionType::None,
+            Compression::Snappy => DBCompressionType::Snappy,
+            Compression::Zlib => DBCompressionType::Zlib,
+            Compression::Bz2 => DBCompressionType::Bz2,
+            Compression::Lz4 => DBCompressionType::Lz4,
+            Compression::Lz4hc => DBCompressionType::Lz4hc,
+            Compression::Zstd => DBCompressionType::Zstd,
+        }
+    }
+}
+
+const MB: usize = 1024 * 1024;
+
+// How long the WAL is kept when the spec doesn't say, so `rocksdb changes`
+// can resume after a day's downtime.
+pub const DEFAULT_WAL_TTL_SECONDS: u64 = 86400;
+
+impl CfSpec {
+    // Fields set here win over the ones in base.
+    fn merge(&self, base: &CfSpec) -> CfSpec {
+        CfSpec {
+            block_cache_mb: self.block_cache_mb.or(base.block_cache_mb),
+            bloom_filter_bits: self.bloom_filter_bits.or(base.bloom_filter_bits),
+            compression: self.compression.or(base.compression),
+            write_buffer_mb: self.write_buffer_mb.or(base.write_buffer_mb),
+            prefix_length: self.prefix_length.or(base.prefix_length),
+        }
+    }
+
+    fn validate(&self, cf_name: &str) -> Result<(), ErrBadSpec> {
+        if self.block_cache_mb == Some(0) {
+            return Err(ErrBadSpec::new(cf_name, "block_cache_mb must be > 0"));
+        }
+        if self.write_buffer_mb == Some(0) {
+            return Err(ErrBadSpec::new(cf_name, "write_buffer_mb must be > 0"));
+        }
+        if self.prefix_length == Some(0) {
+            return Err(ErrBadSpec::new(cf_name, "prefix_length must be > 0"));
+        }
+        if let Some(bits) = self.bloom_filter_bits {
+            if !(bits > 0.0 && bits <= 64.0) {
+                return Err(ErrBadSpec::new(
+                    cf_name,
+                    "bloom_filter_bits must be in (0, 64]",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, options: &mut Options, cache: Option<&Cache>) {
+        if let Some(c) = self.compression {
+            options.set_compression_type(c.into());
+        }
+        if let Some(mb) = self.write_buffer_mb {
+            options.set_write_buffer_size(mb * MB);
+        }
+        if let Some(len) = self.prefix_length {
+            options.set_prefix_extractor(SliceTransform::create_fixed_prefix(len));
+        }
+        if self.block_cache_mb.is_some() || self.bloom_filter_bits.is_some() {
+            let mut table = BlockBasedOptions::default();
+            if let Some(cache) = cache {
+                table.set_block_cache(cache);
+            }
+            if let Some(bits) = self.bloom_filter_bits {
+                table.set_bloom_filter(bits, false);
+            }
+            options.set_block_based_table_factory(&table);
+        }
+    }
+}
+
+impl DbSpec {
+    pub fn from_yaml(yaml: &str) -> Result<DbSpec, Box<dyn Error>> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn load(file: &str) -> Result<DbSpec, Box<dyn Error>> {
+        let mut spec = DbSpec::from_yaml(&std::fs::read_to_string(file)?)?;
+        let path = Path::new(&spec.path);
+        if path.is_relative() {
+            if let Some(dir) = Path::new(file).parent() {
+                spec.path = dir.join(path).to_string_lossy().to_string();
+            }
+        }
+        trace!("Loaded spec {:?}: {:?}", file, spec);
+        Ok(spec)
+    }
+
+    // Checks the values, and that every column family named is one the
+    // db has.
+    pub fn validate(&self, cf_names: &[String]) -> Result<(), Box<dyn Error>> {
+        self.defaults.validate("defaults")?;
+        for (cf_name, cf) in self.column_families.iter() {
+            if !cf_names.contains(cf_name) {
+                return Err(Box::new(ErrBadSpec::new(cf_name, "no such column family")));
+            }
+            cf.validate(cf_name)?;
+        }
+        Ok(())
+    }
+
+    // Sets the db wide options.
+    pub fn db_options(&self, options: &mut Options) {
+        options.set_wal_ttl_seconds(self.wal_ttl_seconds.unwrap_or(DEFAULT_WAL_TTL_SECONDS));
+    }
+
+    // The options for the column family, on top of the db options.
+    pub fn cf_options(&self, base: &Options, cf_name: &str) -> Options {
+        let mut options = base.clone();
+        let own = self.column_families.get(cf_name);
+        let cf = match own {
+            Some(cf) => cf.merge(&self.defaults),
+            None => self.defaults.clone(),
+        };
+        let cache = match (
+            own.and_then(|c| c.block_cache_mb),
+            self.defaults.block_cache_mb,
+        ) {
+            (Some(mb), _) => Some(Cache::new_lru_cache(mb * MB)),
+            (None, Some(mb)) => Some(
+                self.cache
+                    .0
+                    .get_or_init(|| Cache::new_lru_cache(mb * MB))
+                    .clone(),
+            ),
+            (None, None) => None,
+        };
+        cf.apply(&mut options, cache.as_ref());
+        options
+    }
+
+    // The cache shared by the column families, if made yet.
+    #[cfg(test)]
+    pub(crate) fn shared_cache(&self) -> Option<&Cache> {
+        self.cache.0.get()
+    }
+}
@@ -0,0 +1,141 @@
+use std::error::Error;
+use tempfile::tempdir;
+
+use crate::rocksdb::db::{self, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::Node;
+use crate::rocksdb::spec::{CfSpec, Compression, DbSpec};
+use crate::rocksdb::store::Store;
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use rocksdb::Options;
+
+static SPEC: &str = r#"
+path: graph.db
+defaults:
+  compression: snappy
+  write_buffer_mb: 8
+column_families:
+  index.edge.head-tail:
+    prefix_length: 8
+    bloom_filter_bits: 10
+    block_cache_mb: 16
+  index.node.name:
+    compression: none
+"#;
+
+#[test]
+fn test_parse() -> Result<(), Box<dyn Error>> {
+    let spec = DbSpec::from_yaml(SPEC)?;
+    assert_eq!("graph.db", spec.path);
+    assert_eq!(Some(Compression::Snappy), spec.defaults.compression);
+    assert_eq!(
+        CfSpec {
+            prefix_length: Some(8),
+            bloom_filter_bits: Some(10.0),
+            block_cache_mb: Some(16),
+            ..Default::default()
+        },
+        spec.column_families["index.edge.head-tail"]
+    );
+
+    // Typos are errors, not silently ignored
+    assert!(DbSpec::from_yaml("path: x\ndefaults:\n  compresion: lz4\n").is_err());
+    assert!(DbSpec::from_yaml("path: x\ndefaults:\n  compression: gzip\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_load_relative_path() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let file = dir.path().join("db.yaml");
+    std::fs::write(&file, SPEC)?;
+    let spec = DbSpec::load(file.to_str().unwrap())?;
+    assert_eq!(dir.path().join("graph.db").to_str().unwrap(), spec.path);
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<(), Box<dyn Error>> {
+    let cf_names = vec!["index.node.name".to_string()];
+    let mut spec = DbSpec::from_yaml("path: x\ncolumn_families:\n  index.node.nope: {}\n")?;
+    assert!(spec.validate(&cf_names).is_err());
+
+    spec =
+        DbSpec::from_yaml("path: x\ncolumn_families:\n  index.node.name:\n    prefix_length: 0\n")?;
+    assert!(spec.validate(&cf_names).is_err());
+
+    spec = DbSpec::from_yaml("path: x\ndefaults:\n  bloom_filter_bits: -1\n")?;
+    assert!(spec.validate(&cf_names).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_open_with_spec() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let mut spec = DbSpec::from_yaml(SPEC)?;
+    spec.path = dir.path().join("graph.db").to_str().unwrap().to_string();
+    let db_info = TestDbInfo::at(&spec.path).with_spec(spec);
+    {
+        let db = db::init(&db_info, &All)?;
+        Node::operations(&db).put(&mut Node {
+            name: "api".into(),
+            ..Default::default()
+        })?;
+    }
+    let db = db::open_db(&db_info, &All)?;
+    let found = Node::operations(&db).first("index.node.name", b"api")?;
+    assert_eq!("api", found.unwrap().name);
+
+    // A spec naming a column family the db doesn't have is refused
+    let bad = TestDbInfo::at(&db_info.path).with_spec(DbSpec::from_yaml(
+        "path: x\ncolumn_families:\n  index.node.nope: {}\n",
+    )?);
+    drop(db);
+    assert!(db::open_db(&bad, &All).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_shared_block_cache() -> Result<(), Box<dyn Error>> {
+    let spec = DbSpec::from_yaml(
+        "path: x\ndefaults:\n  block_cache_mb: 1\ncolumn_families:\n  index.node.name:\n    block_cache_mb: 2\n",
+    )?;
+    let base = Options::default();
+
+    // A column family with a cache of its own doesn't make the shared one
+    spec.cf_options(&base, "index.node.name");
+    assert!(spec.shared_cache().is_none());
+    spec.cf_options(&base, "index.node.id");
+    spec.cf_options(&base, "index.edge.id");
+    assert!(spec.shared_cache().is_some());
+    Ok(())
+}
+
+#[test]
+fn test_scan_across_prefixes() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let mut spec = DbSpec::from_yaml(
+        "path: x\ncolumn_families:\n  index.node.name:\n    prefix_length: 2\n    bloom_filter_bits: 10\n",
+    )?;
+    spec.path = dir.path().join("graph.db").to_str().unwrap().to_string();
+    let db_info = TestDbInfo::at(&spec.path).with_spec(spec);
+    let db = db::init(&db_info, &All)?;
+    let mut ops = Node::operations(&db);
+    for name in ["api", "db", "cache", "queue"] {
+        ops.put(&mut Node {
+            name: name.into(),
+            ..Default::default()
+        })?;
+    }
+    db.flush_cf(db.cf_handle("index.node.name").unwrap())?;
+
+    let mut names = vec![];
+    db.scan_from("index.node.name", &[], &mut |k, _| {
+        names.push(String::from_utf8_lossy(k).to_string());
+        true
+    })?;
+    assert_eq!(vec!["api", "cache", "db", "queue"], names);
+    assert!(fsck::fsck(&db)?.is_clean());
+    Ok(())
+}
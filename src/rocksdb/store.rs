@@ -0,0 +1,442 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::db::{self, Database, IndexBuilder, Transaction};
+use crate::rocksdb::error::ErrMissingIndex;
+
+use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Bound;
+use std::rc::Rc;
+use std::sync::RwLock;
+
+// Column family aware key-value storage under the graph logic.  The
+// operations, indexes and counters only talk to a Store, so the same code
+// runs on RocksDB and in memory.  There are three: Database is the RocksDB
+// store, MemStore keeps everything in memory and Overlay buffers writes over
+// another store.
+pub trait Store {
+    // True if the column family exists.
+    fn has_cf(&self, cf_name: &str) -> bool;
+
+    fn get_value(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>>;
+
+    // Writes a single key outside of any batch.
+    fn put_value(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn Error>>;
+
+    // Calls f for each (key, value) in key order starting at from, until f
+    // returns false.
+    fn scan_from(
+        &self,
+        cf_name: &str,
+        from: &[u8],
+        f: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+    ) -> Result<(), Box<dyn Error>>;
+
+    // Applies all the writes in the batch atomically.
+    fn commit(&self, batch: Batch) -> Result<(), Box<dyn Error>>;
+
+    // About how many keys the column family has, for planning queries.
+    fn estimate_keys(&self, cf_name: &str) -> Result<u64, Box<dyn Error>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOp {
+    Put {
+        cf_name: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        cf_name: String,
+        key: Vec<u8>,
+    },
+}
+
+// Writes to apply together, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Batch {
+    ops: Vec<BatchOp>,
+}
+
+impl Batch {
+    pub fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, cf_name: &str, key: K, value: V) {
+        self.ops.push(BatchOp::Put {
+            cf_name: cf_name.to_string(),
+            key: key.as_ref().to_vec(),
+            value: value.as_ref().to_vec(),
+        });
+    }
+
+    pub fn delete<K: AsRef<[u8]>>(&mut self, cf_name: &str, key: K) {
+        self.ops.push(BatchOp::Delete {
+            cf_name: cf_name.to_string(),
+            key: key.as_ref().to_vec(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+}
+
+// One line per op, with the bytes escaped.
+impl fmt::Display for BatchOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchOp::Put {
+                cf_name,
+                key,
+                value,
+            } => write!(
+                f,
+                "put {} \"{}\" \"{}\"",
+                cf_name,
+                key.escape_ascii(),
+                value.escape_ascii()
+            ),
+            BatchOp::Delete { cf_name, key } => {
+                write!(f, "delete {} \"{}\"", cf_name, key.escape_ascii())
+            }
+        }
+    }
+}
+
+impl Store for Database {
+    fn has_cf(&self, cf_name: &str) -> bool {
+        self.cf_handle(cf_name).is_some()
+    }
+
+    fn get_value(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        match self.cf_handle(cf_name) {
+            Some(cf) => Ok(self.get_cf(cf, key)?),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        }
+    }
+
+    fn put_value(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn Error>> {
+        match self.cf_handle(cf_name) {
+            Some(cf) => Ok(self.put_cf(cf, key, value)?),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        }
+    }
+
+    fn scan_from(
+        &self,
+        cf_name: &str,
+        from: &[u8],
+        f: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let cf = match self.cf_handle(cf_name) {
+            Some(cf) => cf,
+            None => return Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        };
+        // Scans run on past the prefix of from (fsck, export, visit), so they
+        // must not be cut short by a prefix extractor from the spec.
+        let mut options = ReadOptions::default();
+        options.set_total_order_seek(true);
+        let mode = IteratorMode::From(from, Direction::Forward);
+        for item in self.iterator_cf_opt(cf, options, mode) {
+            let (k, v) = item?;
+            if !f(&k, &v) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn commit(&self, batch: Batch) -> Result<(), Box<dyn Error>> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let mut txn = Transaction::default();
+        for op in batch.ops {
+            match op {
+                BatchOp::Put {
+                    cf_name,
+                    key,
+                    value,
+                } => match self.cf_handle(&cf_name) {
+                    Some(cf) => txn.put_cf(cf, key, value),
+                    None => return Err(Box::new(ErrMissingIndex::new(cf_name))),
+                },
+                BatchOp::Delete { cf_name, key } => match self.cf_handle(&cf_name) {
+                    Some(cf) => txn.delete_cf(cf, key),
+                    None => return Err(Box::new(ErrMissingIndex::new(cf_name))),
+                },
+            }
+        }
+        Ok(self.write(txn)?)
+    }
+
+    fn estimate_keys(&self, cf_name: &str) -> Result<u64, Box<dyn Error>> {
+        match self.cf_handle(cf_name) {
+            Some(cf) => Ok(self
+                .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?
+                .unwrap_or(0)),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        }
+    }
+}
+
+// Number of entries copied out per lock in MemStore::scan_from, so that the
+// callback can read from and write to the store.
+const MEM_SCAN_CHUNK: usize = 256;
+
+// A Store that keeps each column family in a BTreeMap.  Nothing is persisted.
+// It runs the same graph logic as Database, fsck and backfill included,
+// without a directory, e.g. for scratch graphs and tests.
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Default)]
+pub struct MemStore {
+    cfs: RwLock<BTreeMap<String, ColumnFamily>>,
+}
+
+type ColumnFamily = BTreeMap<Vec<u8>, Vec<u8>>;
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl MemStore {
+    // Creates an empty store with the column families for the indexes and the
+    // system tables, the same as db::init.
+    pub fn new(builder: &dyn IndexBuilder) -> MemStore {
+        let cfs = db::all_column_families(builder)
+            .into_iter()
+            .map(|name| (name, BTreeMap::new()))
+            .collect();
+        MemStore {
+            cfs: RwLock::new(cfs),
+        }
+    }
+}
+
+impl Store for MemStore {
+    fn has_cf(&self, cf_name: &str) -> bool {
+        self.cfs.read().unwrap().contains_key(cf_name)
+    }
+
+    fn get_value(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        match self.cfs.read().unwrap().get(cf_name) {
+            Some(cf) => Ok(cf.get(key).cloned()),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        }
+    }
+
+    fn put_value(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut batch = Batch::default();
+        batch.put(cf_name, key, value);
+        self.commit(batch)
+    }
+
+    fn scan_from(
+        &self,
+        cf_name: &str,
+        from: &[u8],
+        f: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut start = Bound::Included(from.to_vec());
+        loop {
+            let chunk: Vec<(Vec<u8>, Vec<u8>)> = match self.cfs.read().unwrap().get(cf_name) {
+                Some(cf) => cf
+                    .range((start.clone(), Bound::Unbounded))
+                    .take(MEM_SCAN_CHUNK)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+                None => return Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+            };
+            for (k, v) in chunk.iter() {
+                if !f(k, v) {
+                    return Ok(());
+                }
+            }
+            match chunk.last() {
+                Some((k, _)) if chunk.len() == MEM_SCAN_CHUNK => {
+                    start = Bound::Excluded(k.clone());
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn commit(&self, batch: Batch) -> Result<(), Box<dyn Error>> {
+        let mut cfs = self.cfs.write().unwrap();
+        // Check first so that a bad batch writes nothing.
+        for op in batch.ops.iter() {
+            let cf_name = match op {
+                BatchOp::Put { cf_name, .. } => cf_name,
+                BatchOp::Delete { cf_name, .. } => cf_name,
+            };
+            if !cfs.contains_key(cf_name) {
+                return Err(Box::new(ErrMissingIndex::new(cf_name.to_string())));
+            }
+        }
+        for op in batch.ops {
+            match op {
+                BatchOp::Put {
+                    cf_name,
+                    key,
+                    value,
+                } => {
+                    cfs.get_mut(&cf_name).unwrap().insert(key, value);
+                }
+                BatchOp::Delete { cf_name, key } => {
+                    cfs.get_mut(&cf_name).unwrap().remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Exact, not an estimate.
+    fn estimate_keys(&self, cf_name: &str) -> Result<u64, Box<dyn Error>> {
+        match self.cfs.read().unwrap().get(cf_name) {
+            Some(cf) => Ok(cf.len() as u64),
+            None => Err(Box::new(ErrMissingIndex::new(cf_name.to_string()))),
+        }
+    }
+}
+
+// Latest buffered value of each key; None if deleted
+type Pending = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+// A Store that keeps every write over another store in one batch instead of
+// writing it, so that many operations can be committed, or shown, together.
+// Reads see the buffered writes.
+pub struct Overlay {
+    base: Rc<dyn Store>,
+    pending: RwLock<BTreeMap<String, Pending>>,
+    batch: RwLock<Batch>,
+}
+
+impl Overlay {
+    pub fn new(base: Rc<dyn Store>) -> Overlay {
+        Overlay {
+            base,
+            pending: RwLock::new(BTreeMap::new()),
+            batch: RwLock::new(Batch::default()),
+        }
+    }
+
+    // All the writes so far, in order.
+    pub fn batch(&self) -> Batch {
+        self.batch.read().unwrap().clone()
+    }
+}
+
+impl Store for Overlay {
+    fn has_cf(&self, cf_name: &str) -> bool {
+        self.base.has_cf(cf_name)
+    }
+
+    fn get_value(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        if let Some(v) = self
+            .pending
+            .read()
+            .unwrap()
+            .get(cf_name)
+            .and_then(|cf| cf.get(key))
+        {
+            return Ok(v.clone());
+        }
+        self.base.get_value(cf_name, key)
+    }
+
+    fn put_value(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut batch = Batch::default();
+        batch.put(cf_name, key, value);
+        self.commit(batch)
+    }
+
+    fn scan_from(
+        &self,
+        cf_name: &str,
+        from: &[u8],
+        f: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        // Copied out so that f can write to the overlay.
+        let pending: Vec<(Vec<u8>, Option<Vec<u8>>)> =
+            match self.pending.read().unwrap().get(cf_name) {
+                Some(cf) => cf
+                    .range(from.to_vec()..)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+                None => vec![],
+            };
+        let mut next = 0;
+        let mut done = false;
+        self.base.scan_from(cf_name, from, &mut |k, v| {
+            // Buffered keys before this one, then this one as buffered
+            while next < pending.len() && pending[next].0.as_slice() <= k {
+                let (pk, pv) = &pending[next];
+                next += 1;
+                if let Some(pv) = pv {
+                    if !f(pk, pv) {
+                        done = true;
+                        return false;
+                    }
+                }
+                if pk.as_slice() == k {
+                    return true;
+                }
+            }
+            done = !f(k, v);
+            !done
+        })?;
+        if done {
+            return Ok(());
+        }
+        for (k, v) in pending[next..].iter() {
+            if let Some(v) = v {
+                if !f(k, v) {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn commit(&self, batch: Batch) -> Result<(), Box<dyn Error>> {
+        // Check first so that a bad batch buffers nothing.
+        for op in batch.ops.iter() {
+            let cf_name = match op {
+                BatchOp::Put { cf_name, .. } => cf_name,
+                BatchOp::Delete { cf_name, .. } => cf_name,
+            };
+            if !self.base.has_cf(cf_name) {
+                return Err(Box::new(ErrMissingIndex::new(cf_name.to_string())));
+            }
+        }
+        let mut pending = self.pending.write().unwrap();
+        for op in batch.ops.iter() {
+            let (cf_name, key, value) = match op {
+                BatchOp::Put {
+                    cf_name,
+                    key,
+                    value,
+                } => (cf_name, key, Some(value.clone())),
+                BatchOp::Delete { cf_name, key } => (cf_name, key, None),
+            };
+            pending
+                .entry(cf_name.clone())
+                .or_default()
+                .insert(key.clone(), value);
+        }
+        self.batch.write().unwrap().ops.extend(batch.ops);
+        Ok(())
+    }
+
+    // Of the base store; buffered writes are not counted.
+    fn estimate_keys(&self, cf_name: &str) -> Result<u64, Box<dyn Error>> {
+        self.base.estimate_keys(cf_name)
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+
+use crate::rocksdb::db::{self, HasKey, OperationsBuilder};
+use crate::rocksdb::fsck;
+use crate::rocksdb::graph::{Edge, Node};
+use crate::rocksdb::store::{Batch, MemStore, Overlay, Store};
+use crate::rocksdb::testing::TestDbInfo;
+use crate::rocksdb::All;
+use std::rc::Rc;
+
+// The same graph operations, whatever the backend.
+fn exercise(store: &dyn Store) -> Result<(), Box<dyn Error>> {
+    let mut node_ops = Node::operations(store);
+    let mut api = Node {
+        type_name: "service".into(),
+        name: "api".into(),
+        ..Default::default()
+    };
+    let mut web = Node {
+        type_name: "service".into(),
+        name: "web".into(),
+        ..Default::default()
+    };
+    node_ops.put(&mut api)?;
+    node_ops.put(&mut web)?;
+    assert_eq!((1, 2), (api.id, web.id));
+    assert_eq!(api.type_code, web.type_code);
+
+    let found = node_ops.first("index.node.name", b"web")?;
+    assert_eq!(Some(web.id), found.map(|n| n.id));
+
+    let mut named = Vec::<u64>::new();
+    node_ops.scan("index.node.name", vec![], Box::new(NodeIds(&mut named)))?;
+    assert_eq!(vec![api.id, web.id], named);
+
+    let mut edge_ops = Edge::operations(store);
+    let mut calls = Edge {
+        type_name: "calls".into(),
+        name: "web-api".into(),
+        head: web.id,
+        tail: api.id,
+        ..Default::default()
+    };
+    edge_ops.put(&mut calls)?;
+    assert_eq!(
+        Some("web-api".to_string()),
+        edge_ops.get(Edge::id_from(calls.id))?.map(|e| e.name)
+    );
+
+    // Renaming moves the name index entry
+    api.name = "gateway".into();
+    node_ops.put(&mut api)?;
+    assert!(node_ops.first("index.node.name", b"api")?.is_none());
+    assert!(node_ops.first("index.node.name", b"gateway")?.is_some());
+
+    assert!(node_ops.delete(&web)?);
+    assert!(node_ops.get(Node::id_from(web.id))?.is_none());
+    assert_eq!(1, db::default_counters(store).get("Node")?.get());
+    assert_eq!(1, db::default_counters(store).get("Edge")?.get());
+    assert_eq!(3, db::last_id(store)?);
+    assert!(fsck::fsck(store)?.is_clean());
+    Ok(())
+}
+
+struct NodeIds<'a>(&'a mut Vec<u64>);
+
+impl db::Visitor<Node> for NodeIds<'_> {
+    fn visit(&mut self, n: Node) -> bool {
+        self.0.push(n.id);
+        true
+    }
+}
+
+#[test]
+fn test_operations_in_memory() -> Result<(), Box<dyn Error>> {
+    exercise(&MemStore::new(&All))
+}
+
+#[test]
+fn test_operations_on_rocksdb() -> Result<(), Box<dyn Error>> {
+    let db_info = TestDbInfo::new();
+    let db = db::init(&db_info, &All)?;
+    exercise(&db)
+}
+
+#[test]
+fn test_mem_store() -> Result<(), Box<dyn Error>> {
+    let store = MemStore::new(&All);
+    assert!(store.has_cf("index.node.id"));
+    assert!(!store.has_cf("index.missing"));
+    assert!(store.get_value("index.missing", b"k").is_err());
+
+    // A batch naming a missing column family writes nothing
+    let mut batch = Batch::default();
+    batch.put("cf.system", b"a", b"1");
+    batch.put("index.missing", b"a", b"1");
+    assert!(store.commit(batch).is_err());
+    assert_eq!(None, store.get_value("cf.system", b"a")?);
+
+    // Later writes in a batch win
+    let mut batch = Batch::default();
+    batch.put("cf.system", b"a", b"1");
+    batch.delete("cf.system", b"a");
+    batch.put("cf.system", b"b", b"2");
+    assert_eq!(3, batch.len());
+    store.commit(batch)?;
+    assert_eq!(None, store.get_value("cf.system", b"a")?);
+    assert_eq!(Some(b"2".to_vec()), store.get_value("cf.system", b"b")?);
+
+    // Scans cross the chunks and may write to the store as they go
+    for i in 0..1000u32 {
+        store.put_value("index.node.name", &i.to_be_bytes(), b"")?;
+    }
+    let mut seen = 0;
+    store.scan_from("index.node.name", &10u32.to_be_bytes(), &mut |k, _| {
+        store.put_value("cf.system", k, b"seen").unwrap();
+        seen += 1;
+        true
+    })?;
+    assert_eq!(990, seen);
+    assert_eq!(
+        Some(b"seen".to_vec()),
+        store.get_value("cf.system", &999u32.to_be_bytes())?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_overlay() -> Result<(), Box<dyn Error>> {
+    let base = Rc::new(MemStore::new(&All));
+    for k in [b"a", b"c", b"e"] {
+        base.put_value("cf.system", k, b"base")?;
+    }
+    let overlay = Overlay::new(base.clone());
+    overlay.put_value("cf.system", b"b", b"new")?;
+    overlay.put_value("cf.system", b"c", b"new")?;
+    let mut batch = Batch::default();
+    batch.delete("cf.system", b"e");
+    batch.put("cf.system", b"f", b"new");
+    overlay.commit(batch)?;
+    assert!(overlay.put_value("index.missing", b"a", b"1").is_err());
+
+    // Reads see the buffered writes in key order; the base is untouched
+    let mut seen = vec![];
+    overlay.scan_from("cf.system", b"a", &mut |k, v| {
+        seen.push((k.to_vec(), v.to_vec()));
+        true
+    })?;
+    let expected: Vec<(Vec<u8>, Vec<u8>)> =
+        [("a", "base"), ("b", "new"), ("c", "new"), ("f", "new")]
+            .iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect();
+    assert_eq!(expected, seen);
+    assert_eq!(None, overlay.get_value("cf.system", b"e")?);
+    assert_eq!(Some(b"base".to_vec()), base.get_value("cf.system", b"e")?);
+    assert_eq!(None, base.get_value("cf.system", b"b")?);
+
+    let mut first = vec![];
+    overlay.scan_from("cf.system", b"b", &mut |k, _| {
+        first.push(k.to_vec());
+        false
+    })?;
+    assert_eq!(vec![b"b".to_vec()], first);
+
+    // The batch has every write in order
+    let batch = overlay.batch();
+    assert_eq!(4, batch.len());
+    assert_eq!("delete cf.system \"e\"", batch.ops()[2].to_string());
+    base.commit(batch)?;
+    assert_eq!(None, base.get_value("cf.system", b"e")?);
+
+    // Graph operations run the same over an overlay
+    let overlay = Overlay::new(Rc::new(MemStore::new(&All)));
+    exercise(&overlay)
+}
@@ -0,0 +1,55 @@
+use crate::rocksdb::db::DbInfo;
+use crate::rocksdb::spec::DbSpec;
+use rocksdb::Options;
+use tempfile::TempDir;
+
+// A helper struct to create a temporary database for testing.  The
+// directory is removed when this is dropped, so keep it around for as long
+// as the test uses the db.
+pub(crate) struct TestDbInfo {
+    pub path: String,
+    spec: Option<DbSpec>,
+    _dir: Option<TempDir>,
+}
+
+impl TestDbInfo {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        Self {
+            path: dir.path().to_str().unwrap().to_string(),
+            spec: None,
+            _dir: Some(dir),
+        }
+    }
+
+    // A db at a path the test owns, e.g. a backup or a checkpoint.
+    pub fn at(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            spec: None,
+            _dir: None,
+        }
+    }
+
+    pub fn with_spec(mut self, spec: DbSpec) -> Self {
+        self.spec = Some(spec);
+        self
+    }
+}
+
+impl DbInfo for TestDbInfo {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn options(&self) -> Options {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        opts
+    }
+
+    fn spec(&self) -> Option<&DbSpec> {
+        self.spec.as_ref()
+    }
+}
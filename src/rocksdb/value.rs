@@ -0,0 +1,226 @@
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::rocksdb::error::ErrBadValue;
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// Attribute.content_type of the typed values.  Anything else is opaque bytes.
+pub static INT: &str = "int";
+pub static FLOAT: &str = "float";
+pub static BOOL: &str = "bool";
+pub static STRING: &str = "string";
+pub static TIMESTAMP: &str = "timestamp";
+
+// A typed attribute value.  Timestamps are nanoseconds since the epoch, like
+// ts_nano.  Ints and floats are compared as numbers, so Int(3) == Float(3.0).
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Timestamp(i128),
+}
+
+impl Value {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Value::Int(_) => INT,
+            Value::Float(_) => FLOAT,
+            Value::Bool(_) => BOOL,
+            Value::String(_) => STRING,
+            Value::Timestamp(_) => TIMESTAMP,
+        }
+    }
+
+    pub fn is_typed(content_type: &str) -> bool {
+        [INT, FLOAT, BOOL, STRING, TIMESTAMP].contains(&content_type)
+    }
+
+    // The Attribute.content
+    pub fn content(&self) -> Vec<u8> {
+        match self {
+            Value::Int(v) => v.to_le_bytes().to_vec(),
+            Value::Float(v) => v.to_le_bytes().to_vec(),
+            Value::Bool(v) => vec![*v as u8],
+            Value::String(v) => v.as_bytes().to_vec(),
+            Value::Timestamp(v) => v.to_le_bytes().to_vec(),
+        }
+    }
+
+    pub fn from_content(content_type: &str, content: &[u8]) -> Result<Value, Box<dyn Error>> {
+        let bad = |reason: &str| Box::new(ErrBadValue::new(content_type, reason));
+        match content_type {
+            t if t == INT => Ok(Value::Int(i64::from_le_bytes(
+                content.try_into().map_err(|_| bad("expected 8 bytes"))?,
+            ))),
+            t if t == FLOAT => Ok(Value::Float(f64::from_le_bytes(
+                content.try_into().map_err(|_| bad("expected 8 bytes"))?,
+            ))),
+            t if t == BOOL => match content {
+                [0] => Ok(Value::Bool(false)),
+                [1] => Ok(Value::Bool(true)),
+                _ => Err(bad("expected 0 or 1")),
+            },
+            t if t == STRING => Ok(Value::String(String::from_utf8(content.to_vec())?)),
+            t if t == TIMESTAMP => Ok(Value::Timestamp(i128::from_le_bytes(
+                content.try_into().map_err(|_| bad("expected 16 bytes"))?,
+            ))),
+            _ => Err(bad("not a typed value")),
+        }
+    }
+
+    // Parses text as the content type: an RFC 3339 time or nanoseconds for
+    // timestamps.
+    pub fn parse(content_type: &str, s: &str) -> Result<Value, Box<dyn Error>> {
+        let bad = |reason: &str| Box::new(ErrBadValue::new(content_type, reason));
+        match content_type {
+            t if t == INT => Ok(Value::Int(s.parse()?)),
+            t if t == FLOAT => match s.parse::<f64>()? {
+                v if v.is_nan() => Err(bad("NaN does not sort")),
+                v => Ok(Value::Float(v)),
+            },
+            t if t == BOOL => Ok(Value::Bool(s.parse()?)),
+            t if t == STRING => Ok(Value::String(s.to_string())),
+            t if t == TIMESTAMP => match s.parse::<i128>() {
+                Ok(v) => Ok(Value::Timestamp(v)),
+                Err(_) => Ok(Value::Timestamp(
+                    OffsetDateTime::parse(s, &Rfc3339)?.unix_timestamp_nanos(),
+                )),
+            },
+            _ => Err(bad("not a typed value")),
+        }
+    }
+
+    // The first of int, float and bool that parses, else a string.
+    pub fn infer(s: &str) -> Value {
+        if let Ok(v) = s.parse::<i64>() {
+            return Value::Int(v);
+        }
+        match s.parse::<f64>() {
+            Ok(v) if !v.is_nan() => return Value::Float(v),
+            _ => {}
+        }
+        match s.parse::<bool>() {
+            Ok(v) => Value::Bool(v),
+            Err(_) => Value::String(s.to_string()),
+        }
+    }
+
+    // Bytes that sort like the value: a tag for the type, then the value.
+    // Ints and floats share a tag and sort as numbers; values of other
+    // different types never compare equal.
+    pub fn sort_key(&self) -> Vec<u8> {
+        let mut key = vec![self.tag()];
+        match self {
+            Value::Bool(v) => key.push(*v as u8),
+            // The nearest float, then how far the int is from it, for the
+            // ints beyond 2^53 that a float can't hold.
+            Value::Int(v) => {
+                let f = *v as f64;
+                key.extend(sortable_f64(f));
+                key.extend(sortable_i64((*v as i128 - f as i128) as i64));
+            }
+            Value::Float(v) => {
+                key.extend(sortable_f64(*v));
+                key.extend(sortable_i64(0));
+            }
+            Value::String(v) => key.extend(sortable_str(v)),
+            Value::Timestamp(v) => key.extend(((*v as u128) ^ (1 << 127)).to_be_bytes()),
+        }
+        key
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::Float(_) => 2,
+            Value::String(_) => 4,
+            Value::Timestamp(_) => 5,
+        }
+    }
+
+    // The sort_key of every value of the type starts with this.
+    pub fn type_prefix(&self) -> Vec<u8> {
+        vec![self.tag()]
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Float(f), Value::Int(_)) | (Value::Int(_), Value::Float(f)) => {
+                if f.is_nan() {
+                    None
+                } else {
+                    self.sort_key().partial_cmp(&other.sort_key())
+                }
+            }
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{:?}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{:?}", v),
+            Value::Timestamp(v) => match OffsetDateTime::from_unix_timestamp_nanos(*v)
+                .ok()
+                .and_then(|t| t.format(&Rfc3339).ok())
+            {
+                Some(t) => write!(f, "t{:?}", t),
+                None => write!(f, "t\"{}\"", v),
+            },
+        }
+    }
+}
+
+// Flips all the bits of negatives and the sign of positives; -0.0 is 0.0.
+fn sortable_f64(v: f64) -> [u8; 8] {
+    let bits = if v == 0.0 { 0 } else { v.to_bits() };
+    let bits = if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | (1 << 63)
+    };
+    bits.to_be_bytes()
+}
+
+fn sortable_i64(v: i64) -> [u8; 8] {
+    ((v as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+// The string with each 0 escaped as 0 0xff and ending in 0 1, so that a
+// string sorts before the strings it is a prefix of and its key is not a
+// prefix of theirs.
+pub fn sortable_str(s: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(s.len() + 2);
+    for b in s.bytes() {
+        key.push(b);
+        if b == 0 {
+            key.push(0xff);
+        }
+    }
+    key.extend([0, 1]);
+    key
+}
@@ -5,7 +5,7 @@ use tracing::{debug, error, info, trace, warn};
 
 pub fn encode(x: f64, y: f64) {
     trace!("json encode: x={:?} y={:?}", x, y);
-    let point = Point { x: x, y: y };
+    let point = Point { x, y };
 
     // Convert the Point to a JSON string.
     let serialized = serde_json::to_string(&point).unwrap();
@@ -6,7 +6,7 @@ use tracing::{debug, error, info, trace, warn};
 pub fn encode(x: f64, y: f64) {
     trace!("yaml encode: x={:?} y={:?}", x, y);
 
-    let point = Point { x: x, y: y };
+    let point = Point { x, y };
 
     // Convert the Point to a YAML string.
     let serialized = serde_yaml::to_string(&point).unwrap();
@@ -20,7 +20,7 @@ pub fn watch(cmd: &command::Command) {
         Run(args) => {
             println!("Runing {:?}", args);
 
-            let mut signals = match Signals::new(&[SIGINT, SIGTERM]) {
+            let mut signals = match Signals::new([SIGINT, SIGTERM]) {
                 Err(err) => {
                     println!("Error {:?}", err);
                     return;